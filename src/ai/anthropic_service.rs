@@ -2,9 +2,15 @@ use anyhow::{Context, Result};
 use async_trait::async_trait;
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
-use tracing::{debug, error};
+use std::time::Instant;
+use tracing::{debug, error, trace};
 
-use super::{AIClient, ModelConfig, Message as AIMessage};
+use super::{http, prompts, redact_secret, AIClient, AIError, Completion, ExplainOptions, ModelConfig, Message as AIMessage, Usage};
+
+pub(super) const DEFAULT_API_BASE_URL: &str = "https://api.anthropic.com";
+
+/// Headers every request sets, which `ai.extra_headers` may not replace.
+const RESERVED_HEADERS: [&str; 3] = ["x-api-key", "anthropic-version", "content-type"];
 
 #[derive(Debug, Serialize)]
 struct Message {
@@ -20,16 +26,67 @@ struct Request {
     temperature: f32,
     #[serde(rename = "system")]
     system_prompt: String,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    stream: bool,
 }
 
 #[derive(Debug, Deserialize, Serialize)]
 struct Response {
     content: Vec<Content>,
+    /// Why generation ended; `"refusal"` means the model declined to answer
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    stop_reason: Option<String>,
 }
 
+/// The `stop_reason` reported when the model or a safety filter declines a request.
+const REFUSAL_STOP_REASON: &str = "refusal";
+
+/// A single block in the response `content` array. Only `text` blocks make up
+/// the answer; the others are recognised so they don't break parsing.
 #[derive(Debug, Deserialize, Serialize)]
-struct Content {
-    text: String,
+#[serde(tag = "type", rename_all = "snake_case")]
+enum Content {
+    Text { text: String },
+    Thinking { thinking: String },
+    ToolUse { id: String, name: String, input: serde_json::Value },
+    #[serde(other)]
+    Other,
+}
+
+/// One server-sent event from a streamed response. Only the events that carry
+/// text, usage, or errors matter here.
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum StreamEvent {
+    MessageStart { message: StreamMessage },
+    ContentBlockDelta { delta: StreamDelta },
+    MessageDelta { delta: Option<MessageDeltaBody>, usage: Option<Usage> },
+    Error { error: StreamError },
+    #[serde(other)]
+    Other,
+}
+
+#[derive(Debug, Deserialize)]
+struct MessageDeltaBody {
+    stop_reason: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct StreamMessage {
+    usage: Option<Usage>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum StreamDelta {
+    TextDelta { text: String },
+    #[serde(other)]
+    Other,
+}
+
+#[derive(Debug, Deserialize)]
+struct StreamError {
+    message: String,
 }
 
 pub struct AnthropicClient {
@@ -39,86 +96,230 @@ pub struct AnthropicClient {
 
 impl AnthropicClient {
     pub fn new(config: ModelConfig) -> Result<Self> {
-        let client = Client::builder()
-            .timeout(std::time::Duration::from_secs(60))
-            .build()
-            .context("Failed to create HTTP client")?;
-
+        config.http.check_extra_headers(&RESERVED_HEADERS)?;
+        let client = http::client(&config.http)?;
         Ok(Self { client, config })
     }
 
-    fn build_prompt(&self, code: &str, language: &str) -> String {
-        format!(
-            "You are an expert programmer. Please explain the following {} code in a clear and concise way:\n\n```{}\n{}\n```",
-            language,
-            language,
-            code
-        )
+    fn build_prompt(&self, code: &str, language: &str, options: &ExplainOptions) -> String {
+        let template = options.template.as_deref().unwrap_or(prompts::DEFAULT_EXPLAIN_TEMPLATE);
+        let mut prompt = prompts::render(template, language, code);
+        prompt.push_str("\n\n");
+        prompt.push_str(options.detail.instruction());
+        if let Some(length) = options.length {
+            prompt.push_str("\n\n");
+            prompt.push_str(length.instruction());
+        }
+        prompt
     }
 
-    fn build_system_message(&self, project_context: Option<&str>) -> Message {
-        let system_content = match project_context {
-            Some(context) => format!(
-                "You are an AI programming assistant. You're helping the user with their code project. Project context: {}",
-                context
-            ),
-            None => "You are an AI programming assistant. You're helping the user with their code project.".to_string(),
-        };
-        
-        Message {
-            role: "assistant".to_string(),
-            content: system_content,
+    /// The request's top-level `system` prompt: the configured instruction,
+    /// then the language to answer in and the project context when set. This
+    /// is the only place the system prompt is put together.
+    fn build_system_prompt(&self, project_context: Option<&str>) -> String {
+        let mut prompt = self.config.system_prompt().to_string();
+        if let Some(instruction) = self.config.output_language_instruction() {
+            prompt.push(' ');
+            prompt.push_str(&instruction);
         }
+        if let Some(context) = project_context {
+            prompt.push_str(" Project context: ");
+            prompt.push_str(context);
+        }
+        prompt
     }
 
-    async fn send_request(&self, messages: Vec<Message>) -> Result<String> {
-        let request = Request {
+    fn build_request(
+        &self,
+        messages: Vec<Message>,
+        project_context: Option<&str>,
+        max_tokens: usize,
+        stream: bool,
+    ) -> Request {
+        Request {
             model: self.config.model_name.clone(),
-            system_prompt: "You are an AI programming assistant. You're helping the user with their code project.".to_string(),
+            system_prompt: self.build_system_prompt(project_context),
             messages,
-            max_tokens: self.config.max_tokens,
+            max_tokens,
             temperature: self.config.temperature,
-        };
+            stream,
+        }
+    }
 
-        let response = self
-            .client
-            .post("https://api.anthropic.com/v1/messages")
+    /// `text` with the API key taken out, for anything that is logged. The
+    /// key only goes in a header, but a gateway may echo it back.
+    fn redact(&self, text: &str) -> String {
+        redact_secret(text, &self.config.api_key)
+    }
+
+    /// POSTs `body` to the Messages endpoint, turning non-2xx responses into
+    /// an `AIError`.
+    async fn post_messages(&self, body: &impl Serialize) -> Result<reqwest::Response> {
+        let base_url = self
+            .config
+            .api_base_url
+            .as_deref()
+            .unwrap_or(DEFAULT_API_BASE_URL)
+            .trim_end_matches('/');
+        let url = format!("{}/v1/messages", base_url);
+        debug!("Sending request to {}", url);
+        if tracing::enabled!(tracing::Level::TRACE) {
+            let body = serde_json::to_string(body).unwrap_or_default();
+            trace!("Request body: {}", self.redact(&body));
+        }
+
+        let request = self.config.http.apply_headers(self.client.post(&url));
+        let response = request
             .header("x-api-key", &self.config.api_key)
             .header("anthropic-version", "2023-06-01")
-            .json(&request)
+            .json(body)
             .send()
             .await
+            .map_err(AIError::from)
             .context("Failed to send request to Anthropic API")?;
 
-        if !response.status().is_success() {
+        let status = response.status();
+        if !status.is_success() {
             let error = response
                 .text()
                 .await
                 .unwrap_or_else(|_| "Unknown error".to_string());
-            anyhow::bail!("Anthropic API error: {}", error);
+            let error = self.redact(&error);
+            error!("Anthropic API returned {}: {}", status, error);
+            return Err(AIError::from_status(status, &error).into());
         }
 
-        let response_text = response.text().await?;
-        
-        let response: Response = match serde_json::from_str(&response_text) {
-            Ok(resp) => resp,
-            Err(e) => {
-                anyhow::bail!("Failed to parse Anthropic API response: {}", e)
+        Ok(response)
+    }
+
+    async fn send_request(
+        &self,
+        messages: Vec<Message>,
+        project_context: Option<&str>,
+        max_tokens: usize,
+    ) -> Result<String> {
+        let request = self.build_request(messages, project_context, max_tokens, false);
+
+        debug!("Requesting a completion from {}", request.model);
+        let started = Instant::now();
+        let response_text = self.post_messages(&request).await?.text().await?;
+        debug!("{} answered in {:.2?}", request.model, started.elapsed());
+        trace!("Response body: {}", self.redact(&response_text));
+
+        let response: Response = serde_json::from_str(&response_text)
+            .map_err(AIError::from)
+            .context("Failed to parse Anthropic API response")?;
+
+        // A refusal may come with no text at all; it isn't a malformed response
+        if response.stop_reason.as_deref() == Some(REFUSAL_STOP_REASON) {
+            debug!("The model declined the request");
+            return Err(AIError::Refused.into());
+        }
+
+        if response.content.is_empty() {
+            return Err(AIError::InvalidResponse("Empty content in Anthropic API response".to_string()).into());
+        }
+
+        let mut answer = String::new();
+        for block in &response.content {
+            match block {
+                Content::Text { text } => answer.push_str(text),
+                Content::ToolUse { name, .. } => debug!("Skipping tool_use block for tool {}", name),
+                Content::Thinking { .. } | Content::Other => {}
             }
+        }
+
+        if answer.is_empty() {
+            return Err(AIError::InvalidResponse("No text content in Anthropic API response".to_string()).into());
+        }
+
+        Ok(answer)
+    }
+
+    /// Like `send_request`, but asks for server-sent events and hands each
+    /// piece of text to `on_delta` as it arrives.
+    async fn send_request_stream(
+        &self,
+        messages: Vec<Message>,
+        project_context: Option<&str>,
+        max_tokens: usize,
+        on_delta: &mut (dyn for<'d> FnMut(&'d str) + Send),
+    ) -> Result<Completion> {
+        let request = self.build_request(messages, project_context, max_tokens, true);
+
+        debug!("Streaming a completion from {}", request.model);
+        let started = Instant::now();
+        let mut response = self.post_messages(&request).await?;
+
+        let mut completion = Completion {
+            text: String::new(),
+            usage: None,
         };
+        // Events are newline-delimited, but a chunk can end mid-line (or mid-character)
+        let mut pending: Vec<u8> = Vec::new();
+        while let Some(chunk) = response.chunk().await.map_err(AIError::from)? {
+            pending.extend_from_slice(&chunk);
+            while let Some(end) = pending.iter().position(|&byte| byte == b'\n') {
+                let line: Vec<u8> = pending.drain(..=end).collect();
+                let line = String::from_utf8_lossy(&line);
+                trace!("Response event: {}", self.redact(line.trim_end()));
+                if let Some(data) = line.trim_end().strip_prefix("data:") {
+                    apply_stream_event(data.trim_start(), &mut completion, on_delta)?;
+                }
+            }
+        }
 
-        if response.content.is_empty() {
-            anyhow::bail!("Empty content in Anthropic API response");
+        debug!("{} finished streaming in {:.2?}", request.model, started.elapsed());
+
+        if completion.text.is_empty() {
+            return Err(AIError::InvalidResponse("No text content in Anthropic API stream".to_string()).into());
         }
 
-        Ok(response.content[0].text.clone())
+        Ok(completion)
+    }
+}
+
+/// Folds one SSE `data:` payload into `completion`.
+fn apply_stream_event(
+    data: &str,
+    completion: &mut Completion,
+    on_delta: &mut (dyn for<'d> FnMut(&'d str) + Send),
+) -> Result<()> {
+    let event: StreamEvent = serde_json::from_str(data)
+        .map_err(AIError::from)
+        .context("Failed to parse Anthropic API stream event")?;
+
+    match event {
+        StreamEvent::MessageStart { message } => completion.usage = message.usage,
+        StreamEvent::ContentBlockDelta {
+            delta: StreamDelta::TextDelta { text },
+        } => {
+            on_delta(&text);
+            completion.text.push_str(&text);
+        }
+        StreamEvent::MessageDelta { delta, usage } => {
+            if delta.and_then(|delta| delta.stop_reason).as_deref() == Some(REFUSAL_STOP_REASON) {
+                debug!("The model declined the request");
+                return Err(AIError::Refused.into());
+            }
+            // The final output count replaces the running one from message_start
+            if let Some(usage) = usage {
+                completion.usage.get_or_insert_with(Usage::default).output_tokens = usage.output_tokens;
+            }
+        }
+        StreamEvent::Error { error } => {
+            error!("Anthropic API stream error: {}", error.message);
+            return Err(AIError::RequestError(error.message).into());
+        }
+        _ => {}
     }
+    Ok(())
 }
 
 #[async_trait]
 impl AIClient for AnthropicClient {
-    async fn explain(&self, code: &str, language: &str) -> Result<String> {
-        let prompt = self.build_prompt(code, language);
+    async fn explain(&self, code: &str, language: &str, options: &ExplainOptions) -> Result<String> {
+        let prompt = self.build_prompt(code, language, options);
         let messages = vec![
             Message {
                 role: "user".to_string(),
@@ -126,29 +327,69 @@ impl AIClient for AnthropicClient {
             },
         ];
 
-        self.send_request(messages).await
+        let max_tokens = options.max_tokens(self.config.max_tokens);
+        self.send_request(messages, None, max_tokens).await
     }
 
     async fn chat(&self, messages: &[AIMessage], project_context: Option<&str>) -> Result<String> {
-        let mut anthropic_messages = vec![self.build_system_message(project_context)];
-        
         // Convert AIMessage to Anthropic Message format
-        for message in messages {
-            anthropic_messages.push(Message {
+        let anthropic_messages = messages
+            .iter()
+            .map(|message| Message {
                 role: message.role.clone(),
                 content: message.content.clone(),
-            });
-        }
+            })
+            .collect();
 
-        self.send_request(anthropic_messages).await
+        self.send_request(anthropic_messages, project_context, self.config.max_tokens)
+            .await
+    }
+
+    async fn explain_stream(
+        &self,
+        code: &str,
+        language: &str,
+        options: &ExplainOptions,
+        on_delta: &mut (dyn for<'d> FnMut(&'d str) + Send),
+    ) -> Result<Completion> {
+        let messages = vec![Message {
+            role: "user".to_string(),
+            content: self.build_prompt(code, language, options),
+        }];
+
+        let max_tokens = options.max_tokens(self.config.max_tokens);
+        self.send_request_stream(messages, None, max_tokens, on_delta).await
+    }
+
+    async fn chat_stream(
+        &self,
+        messages: &[AIMessage],
+        project_context: Option<&str>,
+        on_delta: &mut (dyn for<'d> FnMut(&'d str) + Send),
+    ) -> Result<Completion> {
+        let anthropic_messages = messages
+            .iter()
+            .map(|message| Message {
+                role: message.role.clone(),
+                content: message.content.clone(),
+            })
+            .collect();
+
+        self.send_request_stream(anthropic_messages, project_context, self.config.max_tokens, on_delta)
+            .await
+    }
+
+    async fn raw(&self, body: &serde_json::Value) -> Result<String> {
+        Ok(self.post_messages(body).await?.text().await?)
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::ai::{test_model_config, DetailLevel, ResponseLength};
     use wiremock::{
-        matchers::{header, method, path},
+        matchers::{body_json, body_partial_json, header, method, path},
         Mock, MockServer, ResponseTemplate,
     };
 
@@ -156,20 +397,18 @@ mod tests {
     async fn test_explain_success() {
         let mock_server = MockServer::start().await;
         let config = ModelConfig {
-            provider: "anthropic".to_string(),
-            model_name: "claude-3-sonnet-20240229".to_string(),
-            api_key: "test-key".to_string(),
-            temperature: 0.7,
-            max_tokens: 1000,
+            api_base_url: Some(mock_server.uri()),
+            ..test_model_config()
         };
 
         Mock::given(method("POST"))
             .and(path("/v1/messages"))
             .and(header("x-api-key", "test-key"))
             .respond_with(ResponseTemplate::new(200).set_body_json(Response {
-                content: vec![Content {
+                content: vec![Content::Text {
                     text: "This is a test explanation".to_string(),
                 }],
+                stop_reason: None,
             }))
             .mount(&mock_server)
             .await;
@@ -179,7 +418,7 @@ mod tests {
             config,
         };
 
-        let result = client.explain("fn main() {}", "rust").await;
+        let result = client.explain("fn main() {}", "rust", &ExplainOptions::default()).await;
         assert!(result.is_ok());
         assert_eq!(result.unwrap(), "This is a test explanation");
     }
@@ -188,11 +427,9 @@ mod tests {
     async fn test_explain_error() {
         let mock_server = MockServer::start().await;
         let config = ModelConfig {
-            provider: "anthropic".to_string(),
-            model_name: "claude-3-sonnet-20240229".to_string(),
             api_key: "dummy-test-key".to_string(),
-            temperature: 0.7,
-            max_tokens: 1000,
+            api_base_url: Some(mock_server.uri()),
+            ..test_model_config()
         };
 
         Mock::given(method("POST"))
@@ -207,8 +444,449 @@ mod tests {
             config,
         };
 
-        let result = client.explain("fn main() {}", "rust").await;
+        let result = client.explain("fn main() {}", "rust", &ExplainOptions::default()).await;
         assert!(result.is_err());
         assert!(result.unwrap_err().to_string().contains("401"));
     }
+
+    #[tokio::test]
+    async fn test_explain_concatenates_text_blocks() {
+        let mock_server = MockServer::start().await;
+        let config = ModelConfig {
+            api_base_url: Some(mock_server.uri()),
+            ..test_model_config()
+        };
+
+        Mock::given(method("POST"))
+            .and(path("/v1/messages"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "content": [
+                    { "type": "thinking", "thinking": "Let me look at this.", "signature": "abc" },
+                    { "type": "text", "text": "First part. " },
+                    { "type": "tool_use", "id": "tool_1", "name": "lookup", "input": {} },
+                    { "type": "redacted_thinking", "data": "..." },
+                    { "type": "text", "text": "Second part." }
+                ]
+            })))
+            .mount(&mock_server)
+            .await;
+
+        let client = AnthropicClient {
+            client: Client::new(),
+            config,
+        };
+
+        let result = client.explain("fn main() {}", "rust", &ExplainOptions::default()).await.unwrap();
+        assert_eq!(result, "First part. Second part.");
+    }
+
+    #[tokio::test]
+    async fn test_raw_posts_body_unchanged() {
+        let mock_server = MockServer::start().await;
+        let config = ModelConfig {
+            api_base_url: Some(mock_server.uri()),
+            // Gateways may need their own headers on every request
+            http: crate::ai::HttpOptions {
+                extra_headers: [("X-Team-Id".to_string(), "42".to_string())].into(),
+                ..Default::default()
+            },
+            ..test_model_config()
+        };
+        let body = serde_json::json!({
+            "model": "claude-3-opus-20240229",
+            "max_tokens": 5,
+            "top_k": 3,
+            "messages": [{ "role": "user", "content": "hi" }]
+        });
+
+        Mock::given(method("POST"))
+            .and(path("/v1/messages"))
+            .and(header("x-api-key", "test-key"))
+            .and(header("anthropic-version", "2023-06-01"))
+            .and(header("x-team-id", "42"))
+            .and(body_json(&body))
+            .respond_with(ResponseTemplate::new(200).set_body_string(r#"{"id":"msg_1","content":[]}"#))
+            .mount(&mock_server)
+            .await;
+
+        let client = AnthropicClient {
+            client: Client::new(),
+            config,
+        };
+
+        // The response comes back as-is, even though it has no text content
+        let result = client.raw(&body).await.unwrap();
+        assert_eq!(result, r#"{"id":"msg_1","content":[]}"#);
+    }
+
+    /// Collects everything logged through it.
+    #[derive(Clone, Default)]
+    struct CapturedLogs(std::sync::Arc<std::sync::Mutex<Vec<u8>>>);
+
+    impl std::io::Write for CapturedLogs {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_trace_logs_bodies_without_the_api_key() {
+        let logs = CapturedLogs::default();
+        let writer = logs.clone();
+        let subscriber = tracing_subscriber::fmt()
+            .with_max_level(tracing::Level::TRACE)
+            .with_ansi(false)
+            .with_writer(move || writer.clone())
+            .finish();
+        let _guard = tracing::subscriber::set_default(subscriber);
+
+        let mock_server = MockServer::start().await;
+        let config = ModelConfig {
+            api_key: "sk-ant-secret".to_string(),
+            api_base_url: Some(mock_server.uri()),
+            ..test_model_config()
+        };
+        // A response that echoes the key must not leak it either
+        Mock::given(method("POST"))
+            .and(path("/v1/messages"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(Response {
+                content: vec![Content::Text {
+                    text: "Your key is sk-ant-secret".to_string(),
+                }],
+                stop_reason: None,
+            }))
+            .mount(&mock_server)
+            .await;
+
+        let client = AnthropicClient::new(config).unwrap();
+        client.explain("fn traced() {}", "rust", &ExplainOptions::default()).await.unwrap();
+
+        let logs = String::from_utf8(logs.0.lock().unwrap().clone()).unwrap();
+        assert!(logs.contains("Request body:") && logs.contains("fn traced() {}"), "{}", logs);
+        assert!(logs.contains("Response body:") && logs.contains("Your key is [REDACTED]"), "{}", logs);
+        assert!(!logs.contains("sk-ant-secret"), "{}", logs);
+    }
+
+    #[tokio::test]
+    async fn test_requests_go_through_the_configured_proxy() {
+        // The mock server stands in for the proxy; the API host is never resolved
+        let proxy = MockServer::start().await;
+        let config = ModelConfig {
+            api_base_url: Some("http://api.anthropic.invalid".to_string()),
+            http: crate::ai::HttpOptions {
+                proxy: Some(proxy.uri().replace("http://", "http://user:secret@")),
+                ..Default::default()
+            },
+            ..test_model_config()
+        };
+
+        Mock::given(method("POST"))
+            .and(path("/v1/messages"))
+            // base64 of "user:secret"
+            .and(header("proxy-authorization", "Basic dXNlcjpzZWNyZXQ="))
+            .respond_with(ResponseTemplate::new(200).set_body_json(Response {
+                content: vec![Content::Text {
+                    text: "proxied".to_string(),
+                }],
+                stop_reason: None,
+            }))
+            .mount(&proxy)
+            .await;
+
+        let client = AnthropicClient::new(config).unwrap();
+        let answer = client.explain("fn main() {}", "rust", &ExplainOptions::default()).await.unwrap();
+        assert_eq!(answer, "proxied");
+    }
+
+    #[tokio::test]
+    async fn test_refusal_is_reported_as_declined() {
+        let mock_server = MockServer::start().await;
+        let config = ModelConfig {
+            api_base_url: Some(mock_server.uri()),
+            ..test_model_config()
+        };
+
+        Mock::given(method("POST"))
+            .and(path("/v1/messages"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "id": "msg_1",
+                "type": "message",
+                "role": "assistant",
+                "content": [],
+                "stop_reason": "refusal",
+            })))
+            .mount(&mock_server)
+            .await;
+
+        let client = AnthropicClient {
+            client: Client::new(),
+            config,
+        };
+
+        let error = client.explain("fn main() {}", "rust", &ExplainOptions::default()).await.unwrap_err();
+        assert!(matches!(error.downcast_ref::<AIError>(), Some(AIError::Refused)));
+        assert_eq!(error.to_string(), "The model declined to answer this request");
+
+        let mut completion = Completion {
+            text: String::new(),
+            usage: None,
+        };
+        let event = r#"{"type":"message_delta","delta":{"stop_reason":"refusal"},"usage":{"output_tokens":0}}"#;
+        let error = apply_stream_event(event, &mut completion, &mut |_: &str| {}).unwrap_err();
+        assert!(matches!(error.downcast_ref::<AIError>(), Some(AIError::Refused)));
+    }
+
+    #[tokio::test]
+    async fn test_configured_system_prompt_is_sent() {
+        let mock_server = MockServer::start().await;
+        let config = ModelConfig {
+            api_base_url: Some(mock_server.uri()),
+            system_prompt: Some("Respond only in Python.".to_string()),
+            ..test_model_config()
+        };
+
+        Mock::given(method("POST"))
+            .and(path("/v1/messages"))
+            .and(body_partial_json(serde_json::json!({ "system": "Respond only in Python." })))
+            .respond_with(ResponseTemplate::new(200).set_body_json(Response {
+                content: vec![Content::Text {
+                    text: "print('hi')".to_string(),
+                }],
+                stop_reason: None,
+            }))
+            .mount(&mock_server)
+            .await;
+
+        let client = AnthropicClient {
+            client: Client::new(),
+            config,
+        };
+
+        let result = client.explain("x", "rust", &ExplainOptions::default()).await.unwrap();
+        assert_eq!(result, "print('hi')");
+    }
+
+    #[test]
+    fn test_request_system_prompt() {
+        let config = ModelConfig {
+            system_prompt: Some("Be brief.".to_string()),
+            ..test_model_config()
+        };
+        let mut client = AnthropicClient::new(config).unwrap();
+
+        let request = client.build_request(Vec::new(), None, 100, false);
+        assert_eq!(request.system_prompt, "Be brief.");
+        let request = client.build_request(Vec::new(), Some("Rust workspace"), 100, true);
+        assert_eq!(request.system_prompt, "Be brief. Project context: Rust workspace");
+        assert!(request.stream);
+
+        client.config.output_language = Some("ja".to_string());
+        let request = client.build_request(Vec::new(), Some("Rust workspace"), 100, false);
+        assert_eq!(
+            request.system_prompt,
+            "Be brief. Always respond in Japanese (ja), whatever language the question is in. \
+             Project context: Rust workspace"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_chat_sends_project_context_as_system_prompt() {
+        let mock_server = MockServer::start().await;
+        let config = ModelConfig {
+            api_base_url: Some(mock_server.uri()),
+            ..test_model_config()
+        };
+
+        Mock::given(method("POST"))
+            .and(path("/v1/messages"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(Response {
+                content: vec![Content::Text {
+                    text: "Hello".to_string(),
+                }],
+                stop_reason: None,
+            }))
+            .mount(&mock_server)
+            .await;
+
+        let client = AnthropicClient {
+            client: Client::new(),
+            config,
+        };
+        let messages = vec![AIMessage {
+            role: "user".to_string(),
+            content: "hi".to_string(),
+        }];
+        client.chat(&messages, Some("Current directory: /work")).await.unwrap();
+
+        let requests = mock_server.received_requests().await.unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&requests[0].body).unwrap();
+        assert_eq!(
+            body["system"],
+            format!("{} Project context: Current directory: /work", crate::ai::DEFAULT_SYSTEM_PROMPT)
+        );
+        assert_eq!(body["messages"], serde_json::json!([{ "role": "user", "content": "hi" }]));
+    }
+
+    #[tokio::test]
+    async fn test_chat_stream_emits_deltas_and_usage() {
+        let mock_server = MockServer::start().await;
+        let config = ModelConfig {
+            api_base_url: Some(mock_server.uri()),
+            ..test_model_config()
+        };
+        let events = concat!(
+            "event: message_start\n",
+            "data: {\"type\":\"message_start\",\"message\":{\"id\":\"msg_1\",\"usage\":{\"input_tokens\":25,\"output_tokens\":1}}}\n\n",
+            "event: content_block_start\n",
+            "data: {\"type\":\"content_block_start\",\"index\":0,\"content_block\":{\"type\":\"text\",\"text\":\"\"}}\n\n",
+            "event: ping\n",
+            "data: {\"type\":\"ping\"}\n\n",
+            "event: content_block_delta\n",
+            "data: {\"type\":\"content_block_delta\",\"index\":0,\"delta\":{\"type\":\"text_delta\",\"text\":\"Hello\"}}\n\n",
+            "event: content_block_delta\n",
+            "data: {\"type\":\"content_block_delta\",\"index\":0,\"delta\":{\"type\":\"text_delta\",\"text\":\", world\"}}\n\n",
+            "event: message_delta\n",
+            "data: {\"type\":\"message_delta\",\"delta\":{\"stop_reason\":\"end_turn\"},\"usage\":{\"output_tokens\":15}}\n\n",
+            "event: message_stop\n",
+            "data: {\"type\":\"message_stop\"}\n\n",
+        );
+
+        Mock::given(method("POST"))
+            .and(path("/v1/messages"))
+            .and(body_partial_json(serde_json::json!({ "stream": true })))
+            .respond_with(ResponseTemplate::new(200).set_body_raw(events, "text/event-stream"))
+            .mount(&mock_server)
+            .await;
+
+        let client = AnthropicClient {
+            client: Client::new(),
+            config,
+        };
+
+        let messages = vec![AIMessage {
+            role: "user".to_string(),
+            content: "hi".to_string(),
+        }];
+        let mut deltas = Vec::new();
+        let completion = client
+            .chat_stream(&messages, None, &mut |delta: &str| deltas.push(delta.to_string()))
+            .await
+            .unwrap();
+
+        assert_eq!(deltas, vec!["Hello", ", world"]);
+        assert_eq!(completion.text, "Hello, world");
+        assert_eq!(
+            completion.usage,
+            Some(Usage {
+                input_tokens: 25,
+                output_tokens: 15
+            })
+        );
+    }
+
+    #[test]
+    fn test_stream_error_event() {
+        let mut completion = Completion {
+            text: String::new(),
+            usage: None,
+        };
+        let result = apply_stream_event(
+            r#"{"type":"error","error":{"type":"overloaded_error","message":"Overloaded"}}"#,
+            &mut completion,
+            &mut |_: &str| {},
+        );
+        assert!(result.unwrap_err().to_string().contains("Overloaded"));
+    }
+
+    #[tokio::test]
+    async fn test_deterministic_forces_zero_temperature() {
+        let mock_server = MockServer::start().await;
+        let mut config = ModelConfig {
+            api_base_url: Some(mock_server.uri()),
+            ..test_model_config()
+        };
+        assert!(config.make_deterministic().is_some());
+
+        Mock::given(method("POST"))
+            .and(path("/v1/messages"))
+            .and(body_partial_json(serde_json::json!({ "temperature": 0.0 })))
+            .respond_with(ResponseTemplate::new(200).set_body_json(Response {
+                content: vec![Content::Text {
+                    text: "deterministic".to_string(),
+                }],
+                stop_reason: None,
+            }))
+            .mount(&mock_server)
+            .await;
+
+        let client = AnthropicClient {
+            client: Client::new(),
+            config,
+        };
+
+        let result = client.explain("fn main() {}", "rust", &ExplainOptions::default()).await;
+        assert_eq!(result.unwrap(), "deterministic");
+    }
+
+    #[tokio::test]
+    async fn test_response_length_sets_prompt_and_max_tokens() {
+        let mock_server = MockServer::start().await;
+        let config = ModelConfig {
+            api_base_url: Some(mock_server.uri()),
+            ..test_model_config()
+        };
+        let client = AnthropicClient {
+            client: Client::new(),
+            config,
+        };
+
+        let short = ExplainOptions { length: Some(ResponseLength::Short), ..Default::default() };
+        let long = ExplainOptions { length: Some(ResponseLength::Long), ..Default::default() };
+        assert!(client.build_prompt("x", "rust", &short).ends_with(ResponseLength::Short.instruction()));
+        assert!(client.build_prompt("x", "rust", &long).ends_with(ResponseLength::Long.instruction()));
+
+        Mock::given(method("POST"))
+            .and(path("/v1/messages"))
+            .and(body_partial_json(serde_json::json!({ "max_tokens": ResponseLength::Short.max_tokens(1000) })))
+            .respond_with(ResponseTemplate::new(200).set_body_json(Response {
+                content: vec![Content::Text {
+                    text: "short".to_string(),
+                }],
+                stop_reason: None,
+            }))
+            .mount(&mock_server)
+            .await;
+
+        assert_eq!(client.explain("x", "rust", &short).await.unwrap(), "short");
+    }
+
+    #[test]
+    fn test_detail_level_sets_prompt() {
+        let client = AnthropicClient {
+            client: Client::new(),
+            config: test_model_config(),
+        };
+
+        let basic = ExplainOptions { detail: DetailLevel::Basic, ..Default::default() };
+        let detailed = ExplainOptions { detail: DetailLevel::Detailed, ..Default::default() };
+        assert!(client.build_prompt("x", "rust", &basic).ends_with(DetailLevel::Basic.instruction()));
+        assert!(client.build_prompt("x", "rust", &detailed).ends_with(DetailLevel::Detailed.instruction()));
+
+        let house = ExplainOptions {
+            template: Some("Explain this {language}, always mentioning Big-O complexity:\n{code}".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(
+            client.build_prompt("x", "rust", &house),
+            format!(
+                "Explain this rust, always mentioning Big-O complexity:\nx\n\n{}",
+                DetailLevel::Medium.instruction()
+            )
+        );
+    }
 }
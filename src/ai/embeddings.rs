@@ -0,0 +1,109 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use tokio::time::timeout;
+
+use super::{offline, voyage_service, AIError, RetryPolicy};
+
+/// Largest number of texts sent in one embeddings request.
+const MAX_BATCH: usize = 64;
+
+/// Settings for the model that turns code into vectors for `index`. Kept
+/// apart from `ai` because chat providers don't necessarily offer one.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EmbeddingConfig {
+    pub provider: String,
+    pub model: String,
+    pub api_key: String,
+    pub api_base_url: Option<String>,
+}
+
+/// What the texts being embedded are for. Retrieval models embed a search
+/// query a little differently from the documents it is matched against.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InputType {
+    Document,
+    Query,
+}
+
+impl InputType {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            InputType::Document => "document",
+            InputType::Query => "query",
+        }
+    }
+}
+
+#[async_trait]
+pub trait EmbeddingClient: Send + Sync {
+    /// Embeds each of `texts` as one vector, in the same order.
+    async fn embed(&self, texts: &[String], input_type: InputType) -> Result<Vec<Vec<f32>>>;
+}
+
+pub struct EmbeddingService {
+    client: Box<dyn EmbeddingClient>,
+    config: EmbeddingConfig,
+    retry: RetryPolicy,
+}
+
+impl EmbeddingService {
+    pub fn new(config: EmbeddingConfig) -> Result<Self> {
+        let client: Box<dyn EmbeddingClient> = match config.provider.as_str() {
+            "voyage" => {
+                offline::check("voyage", true)?;
+                Box::new(voyage_service::VoyageClient::new(config.clone())?)
+            }
+            other => {
+                return Err(AIError::ConfigError(format!("Unsupported embeddings provider: {}", other)).into())
+            }
+        };
+        Ok(Self {
+            client,
+            config,
+            retry: RetryPolicy::default(),
+        })
+    }
+
+    #[cfg(test)]
+    pub fn from_client(client: Box<dyn EmbeddingClient>, config: EmbeddingConfig) -> Self {
+        Self {
+            client,
+            config,
+            retry: RetryPolicy::default(),
+        }
+    }
+
+    pub fn config(&self) -> &EmbeddingConfig {
+        &self.config
+    }
+
+    /// Embeds `texts` in batches of `MAX_BATCH`, retrying transient failures.
+    pub async fn embed(&self, texts: &[String], input_type: InputType) -> Result<Vec<Vec<f32>>> {
+        let timeout_duration = Duration::from_secs(60);
+        let mut vectors = Vec::with_capacity(texts.len());
+        for batch in texts.chunks(MAX_BATCH) {
+            let embedded = self
+                .retry
+                .run(|| async {
+                    match timeout(timeout_duration, self.client.embed(batch, input_type)).await {
+                        Ok(result) => result,
+                        Err(_) => Err(anyhow::Error::new(AIError::Timeout(timeout_duration))
+                            .context(format!("Embeddings request timed out after {:?}", timeout_duration))),
+                    }
+                })
+                .await?;
+            if embedded.len() != batch.len() {
+                return Err(AIError::InvalidResponse(format!(
+                    "asked for {} embeddings, got {}",
+                    batch.len(),
+                    embedded.len()
+                ))
+                .into());
+            }
+            vectors.extend(embedded);
+        }
+        Ok(vectors)
+    }
+}
@@ -18,11 +18,48 @@ pub enum AIError {
     #[error("Authentication failed: {0}")]
     AuthenticationError(String),
 
+    #[allow(dead_code)] // No client reports model-side failures yet
     #[error("Model error: {0}")]
     ModelError(String),
 
     #[error("Configuration error: {0}")]
     ConfigError(String),
+
+    #[error("Refusing to send an empty prompt: {0}")]
+    EmptyPrompt(String),
+
+    #[error("The model declined to answer this request")]
+    Refused,
+
+    #[error("Offline mode: {0}")]
+    Offline(String),
+}
+
+impl AIError {
+    /// Maps an unsuccessful HTTP status and its body to an error.
+    pub fn from_status(status: reqwest::StatusCode, body: &str) -> Self {
+        match status.as_u16() {
+            401 => AIError::AuthenticationError(format!("{}: {}", status, body)),
+            429 => AIError::RateLimitExceeded,
+            _ => AIError::RequestError(format!("HTTP error {}: {}", status, body)),
+        }
+    }
+
+    /// A stable, machine-readable name for the kind of failure.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            AIError::RequestError(_) => "request",
+            AIError::InvalidResponse(_) => "invalid_response",
+            AIError::RateLimitExceeded => "rate_limit",
+            AIError::Timeout(_) => "timeout",
+            AIError::AuthenticationError(_) => "authentication",
+            AIError::ModelError(_) => "model",
+            AIError::ConfigError(_) => "config",
+            AIError::EmptyPrompt(_) => "empty_prompt",
+            AIError::Refused => "refusal",
+            AIError::Offline(_) => "offline",
+        }
+    }
 }
 
 impl From<reqwest::Error> for AIError {
@@ -0,0 +1,135 @@
+//! The `mock` provider: canned answers instead of API calls, so the whole CLI
+//! can run in CI. Built into tests, and into release builds with the
+//! `mock-provider` feature.
+
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use super::{AIClient, ExplainOptions, Message};
+
+/// Models the mock provider accepts.
+pub(super) const MOCK_MODELS: &[&str] = &["mock"];
+
+/// The answer given when neither variable below is set.
+const DEFAULT_RESPONSE: &str = "This is a mock response.";
+
+/// Every request gets this text back.
+const RESPONSE_VAR: &str = "MONK_MOCK_RESPONSE";
+
+/// A file of answers separated by lines holding only `---`, given out in
+/// turn and then from the start again.
+const RESPONSES_FILE_VAR: &str = "MONK_MOCK_RESPONSES_FILE";
+
+pub struct MockClient {
+    responses: Vec<String>,
+    next: AtomicUsize,
+}
+
+impl MockClient {
+    /// Reads the answers from `MONK_MOCK_RESPONSE` or, failing that, the file
+    /// `MONK_MOCK_RESPONSES_FILE` names.
+    pub fn from_env() -> Result<Self> {
+        if let Ok(response) = std::env::var(RESPONSE_VAR) {
+            return Ok(Self::new(vec![response]));
+        }
+        match std::env::var_os(RESPONSES_FILE_VAR) {
+            Some(path) => Self::from_file(Path::new(&path)),
+            None => Ok(Self::new(vec![DEFAULT_RESPONSE.to_string()])),
+        }
+    }
+
+    pub fn from_file(path: &Path) -> Result<Self> {
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read mock responses from {:?}", path))?;
+        let mut responses = vec![String::new()];
+        for line in text.lines() {
+            if line.trim() == "---" {
+                responses.push(String::new());
+                continue;
+            }
+            let current = responses.last_mut().expect("there is always a response being read");
+            current.push_str(line);
+            current.push('\n');
+        }
+        let responses: Vec<String> = responses
+            .into_iter()
+            .map(|response| response.trim().to_string())
+            .filter(|response| !response.is_empty())
+            .collect();
+        if responses.is_empty() {
+            anyhow::bail!("{:?} has no mock responses in it", path);
+        }
+        Ok(Self::new(responses))
+    }
+
+    fn new(responses: Vec<String>) -> Self {
+        Self {
+            responses,
+            next: AtomicUsize::new(0),
+        }
+    }
+
+    fn next_response(&self) -> String {
+        let index = self.next.fetch_add(1, Ordering::Relaxed);
+        self.responses[index % self.responses.len()].clone()
+    }
+}
+
+#[async_trait]
+impl AIClient for MockClient {
+    async fn explain(&self, _code: &str, _language: &str, _options: &ExplainOptions) -> Result<String> {
+        Ok(self.next_response())
+    }
+
+    async fn chat(&self, _messages: &[Message], _project_context: Option<&str>) -> Result<String> {
+        Ok(self.next_response())
+    }
+
+    /// Answers in the shape of a Messages API response.
+    async fn raw(&self, _body: &serde_json::Value) -> Result<String> {
+        let response = serde_json::json!({
+            "type": "message",
+            "role": "assistant",
+            "model": MOCK_MODELS[0],
+            "content": [{"type": "text", "text": self.next_response()}],
+        });
+        Ok(serde_json::to_string_pretty(&response)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::{test_model_config, AIService, ModelConfig};
+
+    #[tokio::test]
+    async fn test_mock_provider_cycles_through_responses() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("responses.txt");
+        std::fs::write(&path, "First answer.\n\n---\n  Second answer,\nover two lines.\n---\n").unwrap();
+        let client = MockClient::from_file(&path).unwrap();
+
+        let options = ExplainOptions::default();
+        assert_eq!(client.explain("fn f() {}", "rust", &options).await.unwrap(), "First answer.");
+        assert_eq!(client.chat(&[], None).await.unwrap(), "Second answer,\nover two lines.");
+        assert_eq!(client.chat(&[], None).await.unwrap(), "First answer.");
+
+        std::fs::write(&path, "---\n\n").unwrap();
+        assert!(MockClient::from_file(&path).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_mock_provider_is_selectable() {
+        let config = ModelConfig {
+            provider: "mock".to_string(),
+            model_name: "mock".to_string(),
+            api_key: String::new(),
+            ..test_model_config()
+        };
+        let service = AIService::new(config).unwrap();
+        let raw = service.raw(&serde_json::json!({})).await.unwrap();
+        assert!(raw.contains("\"type\": \"text\""), "{}", raw);
+    }
+}
@@ -0,0 +1,49 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use super::AIError;
+
+static OFFLINE: AtomicBool = AtomicBool::new(false);
+
+/// Turns offline mode on or off for the rest of the run, as set by `--offline`.
+pub fn set(offline: bool) {
+    OFFLINE.store(offline, Ordering::Relaxed);
+}
+
+/// Whether providers that reach over the network are off limits: with
+/// `--offline`, or `MONK_OFFLINE` set to anything but empty or `0`.
+pub fn enabled() -> bool {
+    OFFLINE.load(Ordering::Relaxed)
+        || std::env::var("MONK_OFFLINE").is_ok_and(|value| !matches!(value.trim(), "" | "0"))
+}
+
+/// Refuses a remote provider in offline mode, before it can send anything.
+pub(super) fn check(provider: &str, remote: bool) -> Result<(), AIError> {
+    match refusal(provider, remote, enabled()) {
+        Some(error) => Err(error),
+        None => Ok(()),
+    }
+}
+
+fn refusal(provider: &str, remote: bool, offline: bool) -> Option<AIError> {
+    (offline && remote).then(|| {
+        AIError::Offline(format!(
+            "the {} provider sends requests over the network; use a local provider, or drop --offline and MONK_OFFLINE",
+            provider
+        ))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_offline_refuses_only_remote_providers() {
+        let error = refusal("anthropic", true, true).unwrap();
+        assert_eq!(error.kind(), "offline");
+        assert!(error.to_string().starts_with("Offline mode: the anthropic provider"), "{}", error);
+
+        assert!(refusal("mock", false, true).is_none());
+        assert!(refusal("anthropic", true, false).is_none());
+    }
+}
@@ -0,0 +1,152 @@
+use serde::{Deserialize, Serialize};
+
+/// The built-in explain prompt; detail and length instructions follow it.
+pub const DEFAULT_EXPLAIN_TEMPLATE: &str =
+    "You are an expert programmer. Please explain the following {language} code in a clear and concise way:\n\n```{language}\n{code}\n```";
+
+/// The built-in prompt for explaining a diff, which goes in as `{code}`.
+pub const DEFAULT_DIFF_TEMPLATE: &str =
+    "Explain what changed in the following diff and why it might matter:\n\n```diff\n{code}\n```";
+
+/// Names a template may use inside braces. `{{` and `}}` stand for literal braces.
+const PLACEHOLDERS: [&str; 2] = ["language", "code"];
+
+/// House-style prompts that replace the built-in ones. Unset templates keep
+/// the defaults.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PromptTemplates {
+    /// Prompt for `explain`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub explain: Option<String>,
+    /// Prompt for `diff`, given the diff as `{code}` and `diff` as `{language}`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub diff: Option<String>,
+}
+
+impl PromptTemplates {
+    pub fn diff(&self) -> &str {
+        self.diff.as_deref().unwrap_or(DEFAULT_DIFF_TEMPLATE)
+    }
+
+    /// Checks every configured template, naming the first one that uses an
+    /// unknown placeholder or leaves out `{code}`.
+    pub fn validate(&self) -> Result<(), String> {
+        for (name, template) in [("explain", &self.explain), ("diff", &self.diff)] {
+            let Some(template) = template else { continue };
+            let used = placeholders(template).map_err(|e| format!("prompts.{}: {}", name, e))?;
+            if !used.contains(&"code") {
+                return Err(format!("prompts.{}: the template must include {{code}}", name));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Fills `{language}` and `{code}` into `template`. Anything else in braces
+/// is left as it is; `validate` is what rejects it.
+pub fn render(template: &str, language: &str, code: &str) -> String {
+    let mut rendered = String::with_capacity(template.len() + code.len());
+    let mut rest = template;
+    while let Some(at) = rest.find(['{', '}']) {
+        rendered.push_str(&rest[..at]);
+        rest = &rest[at..];
+        if rest.starts_with("{{") || rest.starts_with("}}") {
+            rendered.push_str(&rest[..1]);
+            rest = &rest[2..];
+            continue;
+        }
+        let value = placeholder_at(rest).and_then(|name| match name {
+            "language" => Some(language),
+            "code" => Some(code),
+            _ => None,
+        });
+        match value {
+            Some(value) => {
+                rendered.push_str(value);
+                rest = &rest[rest.find('}').map_or(rest.len(), |end| end + 1)..];
+            }
+            None => {
+                rendered.push_str(&rest[..1]);
+                rest = &rest[1..];
+            }
+        }
+    }
+    rendered.push_str(rest);
+    rendered
+}
+
+/// The name in a `{name}` at the start of `text`.
+fn placeholder_at(text: &str) -> Option<&str> {
+    let inner = text.strip_prefix('{')?;
+    let name = &inner[..inner.find('}')?];
+    (!name.is_empty() && name.chars().all(|c| c.is_alphanumeric() || c == '_')).then_some(name)
+}
+
+/// The placeholders `template` uses, or an error for one that isn't known.
+fn placeholders(template: &str) -> Result<Vec<&str>, String> {
+    let mut used = Vec::new();
+    let mut rest = template;
+    while let Some(at) = rest.find('{') {
+        rest = &rest[at..];
+        if rest.starts_with("{{") {
+            rest = &rest[2..];
+            continue;
+        }
+        match placeholder_at(rest) {
+            Some(name) if PLACEHOLDERS.contains(&name) => used.push(name),
+            Some(name) => {
+                return Err(format!(
+                    "unknown placeholder {{{}}}; templates can use {{language}} and {{code}}, and {{{{ }}}} for literal braces",
+                    name
+                ))
+            }
+            None => {}
+        }
+        rest = &rest[1..];
+    }
+    Ok(used)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_fills_placeholders() {
+        let rendered = render("Explain this {language}, and mention Big-O:\n{code}", "rust", "fn f() {}");
+        assert_eq!(rendered, "Explain this rust, and mention Big-O:\nfn f() {}");
+
+        // Doubled braces are literal, and the code isn't scanned for placeholders
+        assert_eq!(render("{{code}} {code}", "go", "{language}"), "{code} {language}");
+    }
+
+    #[test]
+    fn test_validate_rejects_unknown_placeholders() {
+        let templates = PromptTemplates {
+            explain: Some("Explain {code} in {lang}".to_string()),
+            diff: None,
+        };
+        let error = templates.validate().unwrap_err();
+        assert!(error.starts_with("prompts.explain: unknown placeholder {lang}"), "{}", error);
+
+        let templates = PromptTemplates {
+            explain: None,
+            diff: Some("Review this change for {language} style".to_string()),
+        };
+        assert_eq!(templates.validate().unwrap_err(), "prompts.diff: the template must include {code}");
+
+        let templates = PromptTemplates {
+            explain: Some("Explain {code}; use {{braces}} as needed".to_string()),
+            diff: None,
+        };
+        assert!(templates.validate().is_ok());
+        assert!(PromptTemplates::default().validate().is_ok());
+    }
+
+    #[test]
+    fn test_defaults_render_as_before() {
+        let rendered = render(DEFAULT_EXPLAIN_TEMPLATE, "rust", "fn main() {}");
+        assert!(rendered.ends_with("the following rust code in a clear and concise way:\n\n```rust\nfn main() {}\n```"));
+        assert!(PromptTemplates::default().validate().is_ok());
+    }
+}
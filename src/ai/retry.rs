@@ -0,0 +1,99 @@
+use anyhow::Result;
+use std::fmt;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+
+use super::AIError;
+
+/// Wait before the first retry; each later retry waits twice as long.
+const BASE_DELAY: Duration = Duration::from_secs(1);
+/// Longest wait between two attempts.
+const MAX_DELAY: Duration = Duration::from_secs(30);
+
+/// A failed attempt that is about to be retried, as reported to `on_retry`.
+pub struct RetryNotice<'a> {
+    /// The attempt about to be made, counting the first one as 1
+    pub attempt: u32,
+    pub max_attempts: u32,
+    pub delay: Duration,
+    pub reason: &'a anyhow::Error,
+}
+
+impl fmt::Display for RetryNotice<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}; retrying in {}s (attempt {} of {})",
+            self.reason,
+            self.delay.as_secs_f32(),
+            self.attempt,
+            self.max_attempts
+        )
+    }
+}
+
+/// Called before each retry, for showing progress.
+pub type RetryCallback = Arc<dyn Fn(&RetryNotice) + Send + Sync>;
+
+/// How often a failed request is retried, and who hears about it.
+#[derive(Clone, Default)]
+pub struct RetryPolicy {
+    pub max_retries: u32,
+    pub on_retry: Option<RetryCallback>,
+}
+
+impl RetryPolicy {
+    /// Runs `call`, retrying transient failures with exponential backoff up to
+    /// `max_retries` times.
+    pub async fn run<T, F, Fut>(&self, mut call: F) -> Result<T>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T>>,
+    {
+        let mut attempt = 1;
+        loop {
+            let error = match call().await {
+                Ok(value) => return Ok(value),
+                Err(error) => error,
+            };
+            match self.backoff(attempt, &error) {
+                Some(delay) => tokio::time::sleep(delay).await,
+                None => return Err(error),
+            }
+            attempt += 1;
+        }
+    }
+
+    /// Decides whether to retry after attempt number `attempt` failed with
+    /// `error`. If so, reports the retry and returns how long to wait first.
+    pub fn backoff(&self, attempt: u32, error: &anyhow::Error) -> Option<Duration> {
+        let max_attempts = self.max_retries + 1;
+        if attempt >= max_attempts || !is_transient(error) {
+            return None;
+        }
+
+        let delay = BASE_DELAY.saturating_mul(1 << (attempt - 1).min(16)).min(MAX_DELAY);
+        if let Some(on_retry) = &self.on_retry {
+            on_retry(&RetryNotice {
+                attempt: attempt + 1,
+                max_attempts,
+                delay,
+                reason: error,
+            });
+        }
+        Some(delay)
+    }
+}
+
+/// Whether a failure is worth trying again: rate limits, timeouts and
+/// server-side errors usually clear up on their own.
+fn is_transient(error: &anyhow::Error) -> bool {
+    match error.chain().find_map(|cause| cause.downcast_ref::<AIError>()) {
+        Some(AIError::RateLimitExceeded | AIError::Timeout(_)) => true,
+        Some(AIError::RequestError(message)) => {
+            message.starts_with("HTTP error 5") || message.starts_with("Server error")
+        }
+        _ => false,
+    }
+}
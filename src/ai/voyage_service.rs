@@ -0,0 +1,138 @@
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use tracing::{debug, error};
+
+use super::{
+    embeddings::{EmbeddingClient, EmbeddingConfig, InputType},
+    http::{self, HttpOptions},
+    AIError,
+};
+
+const DEFAULT_API_BASE_URL: &str = "https://api.voyageai.com";
+
+#[derive(Debug, Serialize)]
+struct Request<'a> {
+    model: &'a str,
+    input: &'a [String],
+    /// Whether the texts are stored for retrieval or searched with
+    input_type: &'static str,
+}
+
+#[derive(Debug, Deserialize)]
+struct Response {
+    data: Vec<Embedding>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Embedding {
+    embedding: Vec<f32>,
+    index: usize,
+}
+
+/// Embeddings from Voyage AI, the provider Anthropic recommends for them.
+pub struct VoyageClient {
+    client: Client,
+    config: EmbeddingConfig,
+}
+
+impl VoyageClient {
+    pub fn new(config: EmbeddingConfig) -> Result<Self> {
+        // Goes through HTTPS_PROXY when it is set, like the chat provider
+        let client = http::client(&HttpOptions::default())?;
+        Ok(Self { client, config })
+    }
+}
+
+#[async_trait]
+impl EmbeddingClient for VoyageClient {
+    async fn embed(&self, texts: &[String], input_type: InputType) -> Result<Vec<Vec<f32>>> {
+        let base_url = self
+            .config
+            .api_base_url
+            .as_deref()
+            .unwrap_or(DEFAULT_API_BASE_URL)
+            .trim_end_matches('/');
+        let url = format!("{}/v1/embeddings", base_url);
+        debug!("Requesting {} embeddings from {}", texts.len(), url);
+
+        let response = self
+            .client
+            .post(&url)
+            .bearer_auth(&self.config.api_key)
+            .json(&Request {
+                model: &self.config.model,
+                input: texts,
+                input_type: input_type.as_str(),
+            })
+            .send()
+            .await
+            .map_err(AIError::from)
+            .context("Failed to send request to Voyage AI")?;
+
+        let status = response.status();
+        if !status.is_success() {
+            let error = response
+                .text()
+                .await
+                .unwrap_or_else(|_| "Unknown error".to_string());
+            error!("Voyage AI returned {}: {}", status, error);
+            return Err(AIError::from_status(status, &error).into());
+        }
+
+        let mut response: Response = serde_json::from_str(&response.text().await.map_err(AIError::from)?)
+            .map_err(AIError::from)
+            .context("Failed to parse Voyage AI response")?;
+        // The data is documented to be in input order, but carries its index anyway
+        response.data.sort_by_key(|embedding| embedding.index);
+        Ok(response.data.into_iter().map(|embedding| embedding.embedding).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wiremock::{
+        matchers::{body_partial_json, header, method, path},
+        Mock, MockServer, ResponseTemplate,
+    };
+
+    #[tokio::test]
+    async fn test_embed_returns_vectors_in_input_order() {
+        let mock_server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/v1/embeddings"))
+            .and(header("authorization", "Bearer test-key"))
+            .and(body_partial_json(serde_json::json!({
+                "model": "voyage-code-3",
+                "input": ["fn a() {}", "fn b() {}"],
+                "input_type": "document",
+            })))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "object": "list",
+                "data": [
+                    { "object": "embedding", "embedding": [0.0, 1.0], "index": 1 },
+                    { "object": "embedding", "embedding": [1.0, 0.0], "index": 0 },
+                ],
+                "model": "voyage-code-3",
+                "usage": { "total_tokens": 8 },
+            })))
+            .mount(&mock_server)
+            .await;
+
+        let client = VoyageClient::new(EmbeddingConfig {
+            provider: "voyage".to_string(),
+            model: "voyage-code-3".to_string(),
+            api_key: "test-key".to_string(),
+            api_base_url: Some(mock_server.uri()),
+        })
+        .unwrap();
+
+        let vectors = client
+            .embed(&["fn a() {}".to_string(), "fn b() {}".to_string()], InputType::Document)
+            .await
+            .unwrap();
+        assert_eq!(vectors, vec![vec![1.0, 0.0], vec![0.0, 1.0]]);
+    }
+}
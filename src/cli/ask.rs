@@ -0,0 +1,329 @@
+use anyhow::{Context, Result};
+use clap::Args;
+use std::io::{Read, Write};
+use std::time::{Duration, Instant};
+
+use super::{pager, quiet, spinner::Spinner, stream};
+use crate::{
+    ai::{AIService, DetailLevel, Message, Overrides},
+    concurrency::{run_bounded, DEFAULT_MAX_CONCURRENCY},
+    config::Config,
+    error::CommandError,
+};
+
+#[derive(Args, Debug)]
+pub struct AskArgs {
+    /// The question to send to the model, or `-` to read it from stdin
+    #[arg(required = true)]
+    pub prompt: String,
+
+    /// Output format (markdown, plain, or ndjson with --stream)
+    #[arg(short, long, default_value = "markdown")]
+    pub format: String,
+
+    /// Print the answer as it is generated
+    #[arg(long)]
+    pub stream: bool,
+
+    /// Comma-separated list of models to ask the same question side by side
+    #[arg(long, value_delimiter = ',', conflicts_with = "stream")]
+    pub compare_models: Vec<String>,
+
+    /// Use temperature 0 (and a fixed seed where supported) for reproducible output
+    #[arg(long)]
+    pub deterministic: bool,
+
+    /// Sampling temperature for this run only, from 0.0 to 1.0 (overrides ai.temperature)
+    #[arg(long, value_parser = super::parse_temperature, conflicts_with = "deterministic")]
+    pub temperature: Option<f32>,
+
+    /// Most tokens the answer may use, for this run only (overrides ai.max_tokens)
+    #[arg(long, value_parser = super::parse_max_tokens)]
+    pub max_tokens: Option<usize>,
+
+    /// Language to answer in, as a name or locale code such as `ja` (overrides ai.output_language)
+    #[arg(long, value_name = "LOCALE")]
+    pub lang_out: Option<String>,
+
+    /// How deep the answer should go (by default the question is sent as is)
+    #[arg(long, value_enum)]
+    pub detail: Option<DetailLevel>,
+}
+
+/// One model's answer from a `--compare-models` run.
+pub struct ModelAnswer {
+    pub model: String,
+    pub latency: Duration,
+    pub result: Result<String>,
+}
+
+pub async fn execute(args: AskArgs) -> Result<()> {
+    check_format(&args.format, args.stream)?;
+    let prompt = resolve_prompt(&args.prompt, std::io::stdin())?;
+
+    // Load configuration
+    let mut config = Config::load()?;
+    config.ai.apply(Overrides {
+        temperature: args.temperature,
+        max_tokens: args.max_tokens,
+        output_language: args.lang_out.clone(),
+    });
+    if args.deterministic {
+        if let (Some(warning), false) = (config.ai.make_deterministic(), quiet::enabled()) {
+            eprintln!("Warning: {}", warning);
+        }
+    }
+
+    let messages = vec![Message {
+        role: "user".to_string(),
+        content: with_detail(&prompt, args.detail),
+    }];
+
+    if args.stream {
+        let ai_service = AIService::new(config.ai)?;
+        return stream_answer(&ai_service, &prompt, &messages, &args.format, &mut std::io::stdout()).await;
+    }
+
+    if args.compare_models.is_empty() {
+        let ai_service = AIService::new(config.ai)?;
+        let answer = {
+            let _spinner = Spinner::start("Thinking...");
+            ai_service.chat(&messages, None).await?
+        };
+        pager::print(&format_answer(&prompt, &answer, &args.format)?);
+        return Ok(());
+    }
+
+    // Build one service per model, sharing everything but the model name
+    let services = args
+        .compare_models
+        .iter()
+        .map(|model| {
+            let model_config = config.ai.with_model(model.trim())?;
+            if let (Some(warning), false) = (model_config.unknown_model_warning(), quiet::enabled()) {
+                eprintln!("Warning: {}", warning);
+            }
+            AIService::new(model_config)
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let mut spinner = Spinner::start(&format!("Asking {} models...", services.len()));
+    let answers = compare_models(&services, &messages).await;
+    spinner.stop();
+    pager::print(&format_comparison(&answers));
+
+    if answers.iter().all(|answer| answer.result.is_err()) {
+        anyhow::bail!("None of the compared models returned an answer");
+    }
+
+    Ok(())
+}
+
+/// Returns the prompt text, reading it from `stdin` when the argument is `-`.
+fn resolve_prompt(arg: &str, mut stdin: impl Read) -> Result<String> {
+    let prompt = if arg == "-" {
+        let mut buffer = String::new();
+        stdin
+            .read_to_string(&mut buffer)
+            .context("Failed to read prompt from stdin")?;
+        buffer
+    } else {
+        arg.to_string()
+    };
+
+    let prompt = prompt.trim().to_string();
+    if prompt.is_empty() {
+        return Err(CommandError::MissingArgument("the prompt is empty".to_string()).into());
+    }
+    Ok(prompt)
+}
+
+/// Adds the `--detail` instruction to the question, when one was asked for.
+fn with_detail(prompt: &str, detail: Option<DetailLevel>) -> String {
+    match detail {
+        Some(detail) => format!("{}\n\n{}", prompt, detail.instruction()),
+        None => prompt.to_string(),
+    }
+}
+
+/// Rejects a format that doesn't fit the chosen mode before any request is made.
+fn check_format(format: &str, stream: bool) -> Result<()> {
+    match format {
+        "markdown" | "plain" => Ok(()),
+        "ndjson" if stream => Ok(()),
+        "ndjson" => Err(CommandError::InvalidArgument(
+            "--format ndjson streams its output; pass --stream as well".to_string(),
+        )
+        .into()),
+        _ => Err(CommandError::InvalidArgument(format!("unsupported output format: {}", format)).into()),
+    }
+}
+
+/// Writes the answer to `out` as it arrives: under the usual heading for
+/// markdown and plain, or as NDJSON delta lines closed by a `done` line.
+async fn stream_answer(
+    service: &AIService,
+    prompt: &str,
+    messages: &[Message],
+    format: &str,
+    out: &mut (dyn Write + Send),
+) -> Result<()> {
+    if format == "ndjson" {
+        let completion = service
+            .chat_stream(messages, None, &mut stream::write_deltas(out, true))
+            .await?;
+        writeln!(out, "{}", stream::ndjson_done(completion.usage, None))?;
+        return Ok(());
+    }
+
+    // The formatted answer minus its closing newline is the heading to stream under
+    let heading = format_answer(prompt, "", format)?;
+    write!(out, "{}", heading.strip_suffix('\n').unwrap_or(&heading))?;
+    service
+        .chat_stream(messages, None, &mut stream::write_deltas(out, false))
+        .await?;
+    writeln!(out)?;
+    Ok(())
+}
+
+fn format_answer(prompt: &str, answer: &str, format: &str) -> Result<String> {
+    match format {
+        "markdown" => Ok(format!("## Question\n\n{}\n\n## Answer\n\n{}\n", prompt, answer)),
+        "plain" => Ok(format!("{}\n", answer)),
+        _ => anyhow::bail!("Unsupported output format: {}", format),
+    }
+}
+
+/// Sends the same conversation to every service concurrently and collects
+/// the answers in the order the services were given.
+pub async fn compare_models(services: &[AIService], messages: &[Message]) -> Vec<ModelAnswer> {
+    run_bounded(services, DEFAULT_MAX_CONCURRENCY, |service| async move {
+        let start = Instant::now();
+        let result = service.chat(messages, None).await;
+        ModelAnswer {
+            model: service.config().model_name.clone(),
+            latency: start.elapsed(),
+            result,
+        }
+    })
+    .await
+}
+
+pub fn format_comparison(answers: &[ModelAnswer]) -> String {
+    let mut output = String::new();
+    for answer in answers {
+        output.push_str(&format!(
+            "=== {} ({:.2}s) ===\n",
+            answer.model,
+            answer.latency.as_secs_f64()
+        ));
+        match &answer.result {
+            Ok(text) => output.push_str(text),
+            Err(e) => output.push_str(&format!("Error: {}", e)),
+        }
+        output.push_str("\n\n");
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::{AIClient, ExplainOptions, ModelConfig};
+    use async_trait::async_trait;
+
+    struct FixedClient(&'static str);
+
+    #[async_trait]
+    impl AIClient for FixedClient {
+        async fn explain(&self, _code: &str, _language: &str, _options: &ExplainOptions) -> Result<String> {
+            Ok(self.0.to_string())
+        }
+
+        async fn chat(&self, _messages: &[Message], _project_context: Option<&str>) -> Result<String> {
+            Ok(self.0.to_string())
+        }
+
+        async fn raw(&self, _body: &serde_json::Value) -> Result<String> {
+            Ok(self.0.to_string())
+        }
+    }
+
+    fn service(model: &str, answer: &'static str) -> AIService {
+        let config = ModelConfig {
+            model_name: model.to_string(),
+            ..crate::ai::test_model_config()
+        };
+        AIService::from_client(Box::new(FixedClient(answer)), config)
+    }
+
+    #[test]
+    fn test_detail_only_added_when_asked() {
+        assert_eq!(with_detail("why?", None), "why?");
+        assert_eq!(
+            with_detail("why?", Some(DetailLevel::Basic)),
+            format!("why?\n\n{}", DetailLevel::Basic.instruction())
+        );
+    }
+
+    #[test]
+    fn test_resolve_prompt_from_stdin() {
+        let stdin = "  explain this regex: ^a+$\n".as_bytes();
+        assert_eq!(resolve_prompt("-", stdin).unwrap(), "explain this regex: ^a+$");
+        assert_eq!(resolve_prompt("inline", "ignored".as_bytes()).unwrap(), "inline");
+        assert!(resolve_prompt("-", "   \n".as_bytes()).is_err());
+    }
+
+    #[test]
+    fn test_format_answer() {
+        assert_eq!(format_answer("q", "a", "plain").unwrap(), "a\n");
+        let markdown = format_answer("q", "a", "markdown").unwrap();
+        assert!(markdown.contains("## Question\n\nq"));
+        assert!(markdown.contains("## Answer\n\na"));
+        assert!(format_answer("q", "a", "html").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_stream_answer_as_ndjson() {
+        let service = service("claude-3-haiku", "streamed answer");
+        let messages = vec![Message {
+            role: "user".to_string(),
+            content: "hi".to_string(),
+        }];
+
+        let mut out = Vec::new();
+        stream_answer(&service, "hi", &messages, "ndjson", &mut out).await.unwrap();
+
+        let text = String::from_utf8(out).unwrap();
+        let lines: Vec<serde_json::Value> = text
+            .lines()
+            .map(|line| serde_json::from_str(line).expect("each line is JSON"))
+            .collect();
+        assert_eq!(lines.first().unwrap()["delta"], "streamed answer");
+        assert_eq!(lines.last().unwrap()["done"], true);
+
+        assert!(check_format("ndjson", false).is_err());
+        assert!(check_format("ndjson", true).is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_compare_models_shows_every_answer() {
+        let services = vec![
+            service("claude-3-haiku", "haiku says hi"),
+            service("claude-3-5-sonnet", "sonnet says hello"),
+        ];
+        let messages = vec![Message {
+            role: "user".to_string(),
+            content: "greet me".to_string(),
+        }];
+
+        let answers = compare_models(&services, &messages).await;
+        let output = format_comparison(&answers);
+
+        assert!(output.contains("=== claude-3-haiku ("));
+        assert!(output.contains("haiku says hi"));
+        assert!(output.contains("=== claude-3-5-sonnet ("));
+        assert!(output.contains("sonnet says hello"));
+        assert!(output.find("haiku says hi") < output.find("sonnet says hello"));
+    }
+}
@@ -0,0 +1,129 @@
+use std::borrow::Cow;
+use std::io::IsTerminal;
+use std::sync::OnceLock;
+
+/// When to color output, as chosen with `--color` or `--no-color`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum)]
+pub enum ColorChoice {
+    /// Color a terminal, unless `NO_COLOR` is set
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+static CHOICE: OnceLock<ColorChoice> = OnceLock::new();
+
+/// Sets the color choice for the rest of the run. Only the first call counts.
+pub fn set_choice(choice: ColorChoice) {
+    let _ = CHOICE.set(choice);
+}
+
+/// Whether stdout should get ANSI colors. Unless forced on or off, only on a
+/// terminal, and never when `NO_COLOR` is set to a non-empty value.
+pub fn enabled() -> bool {
+    match CHOICE.get().copied().unwrap_or_default() {
+        ColorChoice::Always => true,
+        ColorChoice::Never => false,
+        ColorChoice::Auto => {
+            let no_color = matches!(std::env::var_os("NO_COLOR"), Some(value) if !value.is_empty());
+            std::io::stdout().is_terminal() && !no_color
+        }
+    }
+}
+
+/// The escape that sets the foreground to the color called `name`.
+pub fn named(name: &str) -> Option<&'static str> {
+    let escape = match name.to_ascii_lowercase().as_str() {
+        "black" => "\x1B[30m",
+        "red" => "\x1B[31m",
+        "green" => "\x1B[32m",
+        "yellow" => "\x1B[33m",
+        "blue" => "\x1B[34m",
+        "magenta" => "\x1B[35m",
+        "cyan" => "\x1B[36m",
+        "white" => "\x1B[37m",
+        _ => return None,
+    };
+    Some(escape)
+}
+
+/// Readies text holding ANSI escapes for stdout. With color off, colors are
+/// stripped; when stdout isn't a terminal, cursor movement and line clearing
+/// go too, so redirected output is plain text.
+pub fn paint(text: &str) -> Cow<'_, str> {
+    if enabled() {
+        Cow::Borrowed(text)
+    } else {
+        Cow::Owned(strip_escapes(text, !std::io::stdout().is_terminal()))
+    }
+}
+
+/// `text` dimmed for stdout, for notes that shouldn't draw the eye; plain
+/// when color is off.
+pub fn dim(text: &str) -> String {
+    paint(&format!("\x1B[2m{}\x1B[0m", text)).into_owned()
+}
+
+/// Removes color escapes (`ESC [ ... m`) from `text`, and every other CSI
+/// escape as well when `all` is set.
+pub fn strip_escapes(text: &str, all: bool) -> String {
+    let mut stripped = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find("\x1B[") {
+        stripped.push_str(&rest[..start]);
+        let sequence = &rest[start + 2..];
+        // Parameters and intermediates, then a single final byte
+        let end = sequence.find(|c: char| ('@'..='~').contains(&c));
+        match end {
+            Some(end) if all || sequence[end..].starts_with('m') => rest = &sequence[end + 1..],
+            Some(end) => {
+                stripped.push_str(&rest[start..start + 2 + end + 1]);
+                rest = &sequence[end + 1..];
+            }
+            None => {
+                rest = &rest[start..];
+                break;
+            }
+        }
+    }
+    stripped.push_str(rest);
+    stripped
+}
+
+/// `print!` for text with ANSI escapes, passed through [`paint`].
+macro_rules! cprint {
+    ($($arg:tt)*) => {
+        print!("{}", $crate::cli::color::paint(&format!($($arg)*)))
+    };
+}
+
+/// `println!` for text with ANSI escapes, passed through [`paint`].
+macro_rules! cprintln {
+    ($($arg:tt)*) => {
+        println!("{}", $crate::cli::color::paint(&format!($($arg)*)))
+    };
+}
+
+pub(crate) use cprint;
+pub(crate) use cprintln;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_strip_escapes() {
+        let text = "\r\x1B[K\x1B[32mDone\x1B[0m";
+        assert_eq!(strip_escapes(text, false), "\r\x1B[KDone");
+        assert_eq!(strip_escapes(text, true), "\rDone");
+        assert_eq!(strip_escapes("24-bit \x1B[38;2;1;2;3mcode\x1B[0m", false), "24-bit code");
+        assert_eq!(strip_escapes("no escapes", true), "no escapes");
+    }
+
+    #[test]
+    fn test_named_colors() {
+        assert_eq!(named("Cyan"), Some("\x1B[36m"));
+        assert_eq!(named("chartreuse"), None);
+    }
+}
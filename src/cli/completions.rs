@@ -0,0 +1,40 @@
+use anyhow::Result;
+use clap::{Args, CommandFactory};
+use clap_complete::{generate, Shell};
+use std::io::Write;
+
+use super::Cli;
+
+#[derive(Args, Debug)]
+pub struct CompletionsArgs {
+    /// Shell to generate the completion script for
+    #[arg(value_enum)]
+    pub shell: Shell,
+}
+
+/// Prints a completion script for the requested shell. This deliberately does
+/// not load the configuration so it works before an API key is set up.
+pub fn execute(args: CompletionsArgs) -> Result<()> {
+    write_completions(args.shell, &mut std::io::stdout())
+}
+
+fn write_completions(shell: Shell, out: &mut dyn Write) -> Result<()> {
+    let mut command = Cli::command();
+    let name = command.get_name().to_string();
+    generate(shell, &mut command, name, out);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_generates_script_with_subcommands() {
+        let mut out = Vec::new();
+        write_completions(Shell::Bash, &mut out).unwrap();
+        let script = String::from_utf8(out).unwrap();
+        assert!(script.contains("explain"));
+        assert!(script.contains("completions"));
+    }
+}
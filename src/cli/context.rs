@@ -0,0 +1,347 @@
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use super::{truncation, walk};
+use crate::{ai::Message, config::Config, parse};
+
+/// Largest amount of a file `/file` adds to the conversation; the rest is cut off.
+pub const MAX_FILE_BYTES: usize = 256 * 1024;
+
+/// Cap on the whole project context sent with each chat request.
+pub const MAX_PROJECT_CONTEXT_BYTES: usize = 8 * 1024;
+
+/// Cap on the excerpt taken from each of the README and manifest files.
+const MAX_EXCERPT_BYTES: usize = 2 * 1024;
+
+/// Entries listed per directory in the project tree before the rest are counted.
+const MAX_TREE_ENTRIES: usize = 40;
+
+/// Files whose opening lines describe the project, in the order they're shown.
+const PROJECT_FILES: [&str; 3] = ["README.md", "Cargo.toml", "package.json"];
+
+/// A file read for `/file`, ready to be added to the conversation.
+#[derive(Debug)]
+pub struct FileMessage {
+    pub content: String,
+    /// Bytes of the file's text included in `content`
+    pub bytes: usize,
+    /// Bytes left out because the file was over `MAX_FILE_BYTES`
+    pub omitted: usize,
+}
+
+/// Returns the size of the file at `path` when it is over `max_bytes`, so
+/// callers can refuse it before reading it in.
+pub fn oversized(path: &Path, max_bytes: u64) -> Option<u64> {
+    std::fs::metadata(path)
+        .ok()
+        .map(|metadata| metadata.len())
+        .filter(|&size| size > max_bytes)
+}
+
+/// Reads a whole file to send to the model, redacted as configured. Binary
+/// files and ones over `commands.explain.max_file_bytes` are refused.
+pub fn read_text(path: &Path, config: &Config) -> Result<String> {
+    if let Some(size) = oversized(path, config.commands.explain.max_file_bytes) {
+        anyhow::bail!("the file is {} bytes, over commands.explain.max_file_bytes", size);
+    }
+    let bytes = std::fs::read(path).with_context(|| format!("Failed to read file: {:?}", path))?;
+    if parse::is_probably_binary(&bytes) {
+        return Err(parse::BinaryFile(path.display().to_string()).into());
+    }
+    let (content, _) = parse::decode_source(bytes, config.commands.detect_encoding)?;
+    Ok(redact_for(&content, config))
+}
+
+/// Reads `path` into a user message that names it `name` and holds the file
+/// in a code fence tagged with its detected language. Binary files are
+/// refused, and text beyond `max_bytes` is cut off, with a note for the model
+/// when `notices` is set.
+pub fn file_message(
+    path: &Path,
+    name: &str,
+    max_bytes: usize,
+    detect_encoding: bool,
+    notices: bool,
+) -> Result<FileMessage> {
+    let bytes = std::fs::read(path).with_context(|| format!("Failed to read file: {:?}", path))?;
+    if parse::is_probably_binary(&bytes) {
+        return Err(parse::BinaryFile(path.display().to_string()).into());
+    }
+    let (mut text, _) = parse::decode_source(bytes, detect_encoding)
+        .with_context(|| format!("Failed to read file: {:?}", path))?;
+    let language = parse::detect_language(Some(path), &text);
+
+    let mut omitted = 0;
+    if text.len() > max_bytes {
+        let mut cut = max_bytes;
+        while !text.is_char_boundary(cut) {
+            cut -= 1;
+        }
+        omitted = text.len() - cut;
+        text.truncate(cut);
+        if notices {
+            text.push('\n');
+            text.push_str(&truncation::omitted_bytes_note(omitted));
+        }
+    }
+
+    let fence_language = if language == "unknown" { "" } else { language.as_str() };
+    Ok(FileMessage {
+        bytes: text.len(),
+        content: format!("Contents of {}:\n\n```{}\n{}\n```", name, fence_language, text),
+        omitted,
+    })
+}
+
+/// Builds the project context sent alongside chat requests. With
+/// `security.redact_paths` set, absolute paths in it are rewritten so they
+/// don't reveal usernames or directory layout.
+pub fn project_context(project_root: &Path, config: &Config) -> String {
+    let context = describe_project(project_root, MAX_PROJECT_CONTEXT_BYTES);
+    redact_for(&context, config)
+}
+
+/// Describes the project at `root`: its path, an excerpt of the README and
+/// manifest files that exist, and the top two levels of its directory tree,
+/// cut off at `max_bytes` in all.
+pub fn describe_project(root: &Path, max_bytes: usize) -> String {
+    let mut sections = vec![format!("Current directory: {}", root.display())];
+    for name in PROJECT_FILES {
+        if let Some(bytes) = std::fs::read(root.join(name)).ok().filter(|bytes| !parse::is_probably_binary(bytes)) {
+            let text = String::from_utf8_lossy(&bytes);
+            sections.push(format!("{}:\n{}", name, excerpt(text.trim_end(), MAX_EXCERPT_BYTES)));
+        }
+    }
+    let tree = list_tree(root);
+    if !tree.is_empty() {
+        sections.push(format!("Directory tree (top two levels):\n{}", tree.trim_end()));
+    }
+    excerpt(&sections.join("\n\n"), max_bytes)
+}
+
+/// Cuts `text` to at most `max_bytes` on a character boundary, noting how
+/// much was left out.
+fn excerpt(text: &str, max_bytes: usize) -> String {
+    if text.len() <= max_bytes {
+        return text.to_string();
+    }
+    let mut cut = max_bytes;
+    while !text.is_char_boundary(cut) {
+        cut -= 1;
+    }
+    format!("{}\n{}", &text[..cut], truncation::omitted_bytes_note(text.len() - cut))
+}
+
+/// Lists the top two levels under `root`, one entry per line and indented
+/// by depth, leaving out what the ignore rules skip. Past `MAX_TREE_ENTRIES`
+/// in one directory, the rest are shown as a single `...`.
+fn list_tree(root: &Path) -> String {
+    let mut tree = String::new();
+    let mut listed: HashMap<PathBuf, usize> = HashMap::new();
+    for entry in walk::walk(root, Some(2), true) {
+        let indent = "  ".repeat(entry.depth - 1);
+        let count = listed.entry(entry.path.parent().unwrap_or(root).to_path_buf()).or_default();
+        *count += 1;
+        if *count == MAX_TREE_ENTRIES + 1 {
+            tree.push_str(&format!("{}...\n", indent));
+        }
+        if *count > MAX_TREE_ENTRIES {
+            continue;
+        }
+        let name = entry.path.file_name().unwrap_or_default().to_string_lossy();
+        let slash = if entry.is_dir { "/" } else { "" };
+        tree.push_str(&format!("{}{}{}\n", indent, name, slash));
+    }
+    tree
+}
+
+/// Applies the configured path redaction to every message of a request, so
+/// paths in typed questions and in files added with `/file` are covered as
+/// well as the project context.
+pub fn redact_messages(messages: &mut [Message], config: &Config) {
+    if config.security.redact_paths {
+        for message in messages {
+            message.content = redact_for(&message.content, config);
+        }
+    }
+}
+
+/// Applies the configured path redaction to text bound for the model.
+pub fn redact_for(text: &str, config: &Config) -> String {
+    if !config.security.redact_paths {
+        return text.to_string();
+    }
+    redact_paths(
+        text,
+        config.repository_home.as_deref().map(Path::new),
+        dirs::home_dir().as_deref(),
+    )
+}
+
+/// Rewrites absolute paths in `text`: paths under `repository_home` become
+/// relative to it (`./src/main.rs`), and other paths under `home` start
+/// with `~` instead.
+pub fn redact_paths(text: &str, repository_home: Option<&Path>, home: Option<&Path>) -> String {
+    let mut redacted = text.to_string();
+    for (prefix, replacement) in [(repository_home, "."), (home, "~")] {
+        let prefix = prefix
+            .and_then(|path| path.to_str())
+            .map(|path| path.trim_end_matches('/'))
+            .filter(|path| !path.is_empty());
+        if let Some(prefix) = prefix {
+            redacted = replace_path_prefix(&redacted, prefix, replacement);
+        }
+    }
+    redacted
+}
+
+/// Replaces `prefix` only where it ends at a path boundary, so `/home/al`
+/// doesn't match inside `/home/alice`.
+fn replace_path_prefix(text: &str, prefix: &str, replacement: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(index) = rest.find(prefix) {
+        let after = &rest[index + prefix.len()..];
+        let at_boundary = after
+            .chars()
+            .next()
+            .is_none_or(|c| !(c.is_alphanumeric() || matches!(c, '_' | '-' | '.')));
+
+        result.push_str(&rest[..index]);
+        result.push_str(if at_boundary { replacement } else { prefix });
+        rest = after;
+    }
+    result.push_str(rest);
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_home_prefix_is_redacted() {
+        let home = Path::new("/home/alice");
+        let context = "Current directory: /home/alice/secret-project/src";
+        assert_eq!(
+            redact_paths(context, None, Some(home)),
+            "Current directory: ~/secret-project/src"
+        );
+
+        // A different user whose name merely starts the same is left alone
+        assert_eq!(
+            redact_paths("/home/alice2/notes", None, Some(home)),
+            "/home/alice2/notes"
+        );
+    }
+
+    #[test]
+    fn test_oversized() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("generated.rs");
+        std::fs::write(&path, "x".repeat(500)).unwrap();
+        assert_eq!(oversized(&path, 100), Some(500));
+        assert_eq!(oversized(&path, 500), None);
+        assert_eq!(oversized(&temp_dir.path().join("missing.rs"), 100), None);
+    }
+
+    #[test]
+    fn test_file_message() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let read = |path: &Path, max_bytes| file_message(path, "the file", max_bytes, true, true);
+
+        let script = temp_dir.path().join("tool.py");
+        std::fs::write(&script, "print('hi')\n").unwrap();
+        let message = read(&script, MAX_FILE_BYTES).unwrap();
+        assert_eq!(message.content, "Contents of the file:\n\n```python\nprint('hi')\n\n```");
+        assert_eq!((message.bytes, message.omitted), (12, 0));
+
+        let large = temp_dir.path().join("large.txt");
+        std::fs::write(&large, "x".repeat(500)).unwrap();
+        let message = read(&large, 100).unwrap();
+        assert_eq!(message.omitted, 400);
+        assert!(message.content.contains(&truncation::omitted_bytes_note(400)));
+
+        let image = temp_dir.path().join("logo.png");
+        std::fs::write(&image, [0x89, b'P', b'N', b'G', 0, 0, 0, 13]).unwrap();
+        let error = read(&image, MAX_FILE_BYTES).unwrap_err();
+        assert!(error.to_string().contains("binary"));
+    }
+
+    #[test]
+    fn test_describe_project() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let root = temp_dir.path();
+        std::fs::write(root.join("README.md"), "# Demo\n\nA demo project.\n").unwrap();
+        std::fs::write(root.join("Cargo.toml"), "[package]\nname = \"demo\"\n").unwrap();
+        std::fs::create_dir_all(root.join("src/cli/deep")).unwrap();
+        std::fs::write(root.join("src/main.rs"), "fn main() {}").unwrap();
+        std::fs::create_dir(root.join("target")).unwrap();
+        std::fs::create_dir(root.join(".git")).unwrap();
+
+        let context = describe_project(root, MAX_PROJECT_CONTEXT_BYTES);
+        assert!(context.starts_with(&format!("Current directory: {}", root.display())));
+        assert!(context.contains("README.md:\n# Demo\n\nA demo project."));
+        assert!(context.contains("Cargo.toml:\n[package]\nname = \"demo\""));
+        assert!(!context.contains("package.json"));
+        assert!(context.ends_with("Directory tree (top two levels):\nCargo.toml\nREADME.md\nsrc/\n  cli/\n  main.rs"));
+
+        let capped = describe_project(root, 40);
+        assert!(capped.ends_with("bytes were omitted from the end of this message.]"));
+        assert!(capped.len() < context.len());
+    }
+
+    #[test]
+    fn test_redact_messages() {
+        let mut config: Config = serde_yaml::from_str(
+            r#"
+            ai:
+              provider: anthropic
+              model_name: claude-3-5-haiku-20241022
+              api_key: test
+              max_tokens: 1000
+              temperature: 0.7
+            logging:
+              level: info
+              format: pretty
+              output: stderr
+            commands:
+              default_language: rust
+              default_format: markdown
+              timeout: 30
+              explain:
+                max_context_lines: 10
+                language_detection: true
+            security:
+              secrets_file: null
+            repository_home: /home/alice/work/monk
+            "#,
+        )
+        .unwrap();
+        let mut messages = vec![Message {
+            role: "user".to_string(),
+            content: "Contents of /home/alice/work/monk/src/main.rs:\n\n```\n// see /home/alice/work/monk/README.md\n```".to_string(),
+        }];
+
+        redact_messages(&mut messages, &config);
+        assert!(messages[0].content.contains("/home/alice/work/monk/src/main.rs"));
+
+        config.security.redact_paths = true;
+        redact_messages(&mut messages, &config);
+        assert_eq!(
+            messages[0].content,
+            "Contents of ./src/main.rs:\n\n```\n// see ./README.md\n```"
+        );
+    }
+
+    #[test]
+    fn test_repository_paths_become_relative() {
+        let redacted = redact_paths(
+            "Current directory: /home/alice/work/monk/src, see /home/alice/work/monk",
+            Some(Path::new("/home/alice/work/monk/")),
+            Some(Path::new("/home/alice")),
+        );
+        assert_eq!(redacted, "Current directory: ./src, see .");
+    }
+}
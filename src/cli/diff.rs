@@ -0,0 +1,284 @@
+use anyhow::{Context, Result};
+use clap::Args;
+use std::path::Path;
+use std::process::Command;
+
+use super::spinner::Spinner;
+use crate::{
+    ai::{render_prompt, AIService, Message, Overrides},
+    concurrency::{run_bounded, DEFAULT_MAX_CONCURRENCY},
+    config::Config,
+};
+
+/// Diffs larger than this many characters are explained file by file.
+const MAX_DIFF_CHARS: usize = 12_000;
+
+#[derive(Args, Debug)]
+pub struct DiffArgs {
+    /// Revision to diff against (defaults to uncommitted changes)
+    pub rev: Option<String>,
+
+    /// Explain staged changes instead of unstaged ones
+    #[arg(long)]
+    pub staged: bool,
+
+    /// Sampling temperature for this run only, from 0.0 to 1.0 (overrides ai.temperature)
+    #[arg(long, value_parser = super::parse_temperature)]
+    pub temperature: Option<f32>,
+
+    /// Most tokens the answer may use, for this run only (overrides ai.max_tokens)
+    #[arg(long, value_parser = super::parse_max_tokens)]
+    pub max_tokens: Option<usize>,
+
+    /// Language to answer in, as a name or locale code such as `ja` (overrides ai.output_language)
+    #[arg(long, value_name = "LOCALE")]
+    pub lang_out: Option<String>,
+}
+
+/// The portion of a unified diff that touches a single file.
+#[derive(Debug, PartialEq)]
+pub struct FileDiff {
+    pub path: String,
+    pub patch: String,
+}
+
+pub async fn execute(args: DiffArgs) -> Result<()> {
+    // Load configuration
+    let mut config = Config::load()?;
+    config.ai.apply(Overrides {
+        temperature: args.temperature,
+        max_tokens: args.max_tokens,
+        output_language: args.lang_out.clone(),
+    });
+
+    let diff = run_git_diff(&args, &config.project_root()?)?;
+    if diff.trim().is_empty() {
+        println!("No changes to explain.");
+        return Ok(());
+    }
+    let ai_service = AIService::new(config.ai)?;
+
+    let explanation = {
+        let _spinner = Spinner::start("Explaining the diff...");
+        explain_diff(&ai_service, &diff, config.prompts.diff()).await?
+    };
+    println!("{}", explanation);
+
+    Ok(())
+}
+
+/// Runs `git diff` in the repository at `root`.
+fn run_git_diff(args: &DiffArgs, root: &Path) -> Result<String> {
+    let mut command = Command::new("git");
+    command.current_dir(root).args(["diff", "--no-color", "--no-ext-diff"]);
+    if args.staged {
+        command.arg("--staged");
+    }
+    if let Some(rev) = &args.rev {
+        command.arg(rev);
+    }
+
+    let output = command.output().context("Failed to run git diff")?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "git diff failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Explains a diff in one request, or per file followed by a combined
+/// summary when the diff is too large to send at once.
+async fn explain_diff(ai_service: &AIService, diff: &str, template: &str) -> Result<String> {
+    if diff.len() <= MAX_DIFF_CHARS {
+        return ask(ai_service, render_prompt(template, "diff", diff)).await;
+    }
+
+    let files: Vec<FileDiff> = split_diff_by_file(diff)
+        .into_iter()
+        .flat_map(|file| split_by_hunks(file, MAX_DIFF_CHARS))
+        .collect();
+    let explanations = run_bounded(&files, DEFAULT_MAX_CONCURRENCY, |file| async move {
+        ask(ai_service, render_prompt(template, "diff", &file.patch)).await
+    })
+    .await;
+
+    let mut sections = Vec::new();
+    for (file, explanation) in files.iter().zip(explanations) {
+        sections.push(format!("## {}\n\n{}", file.path, explanation?));
+    }
+    let per_file = sections.join("\n\n");
+
+    let summary = ask(
+        ai_service,
+        format!(
+            "Here are explanations of the changes to each file in a diff. \
+             Write a short combined summary of what changed overall and why it might matter:\n\n{}",
+            per_file
+        ),
+    )
+    .await?;
+
+    Ok(format!("{}\n\n## Summary\n\n{}", per_file, summary))
+}
+
+async fn ask(ai_service: &AIService, prompt: String) -> Result<String> {
+    let messages = vec![Message {
+        role: "user".to_string(),
+        content: prompt,
+    }];
+    ai_service.chat(&messages, None).await
+}
+
+/// Splits a unified diff into per-file sections on `diff --git` headers.
+pub fn split_diff_by_file(diff: &str) -> Vec<FileDiff> {
+    let mut files: Vec<FileDiff> = Vec::new();
+    for line in diff.lines() {
+        if let Some(header) = line.strip_prefix("diff --git ") {
+            files.push(FileDiff {
+                path: header_path(header).unwrap_or_default(),
+                patch: String::new(),
+            });
+        } else if let Some(file) = files.last_mut() {
+            // The `+++`/`---` lines name the file unambiguously, even with spaces
+            if let Some(path) = line.strip_prefix("+++ b/") {
+                file.path = path.trim_end_matches('\t').to_string();
+            } else if let Some(path) = line.strip_prefix("--- a/") {
+                if file.path.is_empty() {
+                    file.path = path.trim_end_matches('\t').to_string();
+                }
+            } else if let Some(path) = line.strip_prefix("rename to ") {
+                file.path = path.to_string();
+            }
+        }
+        if let Some(file) = files.last_mut() {
+            file.patch.push_str(line);
+            file.patch.push('\n');
+        }
+    }
+    files
+}
+
+/// The path in a `diff --git a/<path> b/<path>` header, when both sides name
+/// the same file. Renames are left to the `rename to` line.
+fn header_path(header: &str) -> Option<String> {
+    let rest = header.strip_prefix("a/")?;
+    // "<path> b/<path>": the path is everything before the middle " b/"
+    let path_len = rest.len().checked_sub(3)? / 2;
+    let (path, other) = rest.split_at_checked(path_len)?;
+    (other.strip_prefix(" b/")? == path).then(|| path.to_string())
+}
+
+/// Splits a file's diff that is too large to send whole into parts of whole
+/// hunks, each repeating the file header. A single hunk over the limit still
+/// goes as one part.
+pub fn split_by_hunks(file: FileDiff, max_chars: usize) -> Vec<FileDiff> {
+    if file.patch.len() <= max_chars {
+        return vec![file];
+    }
+
+    let mut header = String::new();
+    let mut hunks: Vec<String> = Vec::new();
+    for line in file.patch.split_inclusive('\n') {
+        if line.starts_with("@@") {
+            hunks.push(String::new());
+        }
+        match hunks.last_mut() {
+            Some(hunk) => hunk.push_str(line),
+            None => header.push_str(line),
+        }
+    }
+
+    let mut parts: Vec<String> = Vec::new();
+    for hunk in hunks {
+        match parts.last_mut() {
+            Some(part) if part.len() + hunk.len() <= max_chars => part.push_str(&hunk),
+            _ => parts.push(header.clone() + &hunk),
+        }
+    }
+    if parts.len() <= 1 {
+        return vec![file];
+    }
+
+    let count = parts.len();
+    parts
+        .into_iter()
+        .enumerate()
+        .map(|(index, patch)| FileDiff {
+            path: format!("{} (part {} of {})", file.path, index + 1, count),
+            patch,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_diff_by_file() {
+        let diff = "diff --git a/src/a.rs b/src/a.rs\n\
+                    --- a/src/a.rs\n\
+                    +++ b/src/a.rs\n\
+                    @@ -1 +1 @@\n\
+                    -old\n\
+                    +new\n\
+                    diff --git a/README.md b/README.md\n\
+                    @@ -1 +1 @@\n\
+                    -x\n\
+                    +y\n";
+
+        let files = split_diff_by_file(diff);
+        assert_eq!(files.len(), 2);
+        assert_eq!(files[0].path, "src/a.rs");
+        assert!(files[0].patch.contains("+new"));
+        assert!(!files[0].patch.contains("README"));
+        assert_eq!(files[1].path, "README.md");
+        assert!(files[1].patch.starts_with("diff --git a/README.md"));
+    }
+
+    #[test]
+    fn test_split_diff_paths_with_spaces_and_prefixes() {
+        let diff = "diff --git a/my notes.txt b/my notes.txt\n\
+                    --- a/my notes.txt\n\
+                    +++ b/my notes.txt\n\
+                    @@ -1 +1 @@\n\
+                    -a\n\
+                    +b\n\
+                    diff --git a/b/b/x.rs b/b/b/x.rs\n\
+                    deleted file mode 100644\n\
+                    --- a/b/b/x.rs\n\
+                    +++ /dev/null\n\
+                    diff --git a/logo.png b/logo.png\n\
+                    Binary files differ\n";
+
+        let paths: Vec<String> = split_diff_by_file(diff).into_iter().map(|file| file.path).collect();
+        assert_eq!(paths, ["my notes.txt", "b/b/x.rs", "logo.png"]);
+    }
+
+    #[test]
+    fn test_split_large_file_by_hunks() {
+        let header = "diff --git a/a.rs b/a.rs\n--- a/a.rs\n+++ b/a.rs\n";
+        let hunk = |n: usize| format!("@@ -{n} +{n} @@\n-{}\n+{}\n", "x".repeat(40), "y".repeat(40));
+        let patch = format!("{}{}{}{}", header, hunk(1), hunk(10), hunk(20));
+        let file = FileDiff {
+            path: "a.rs".to_string(),
+            patch,
+        };
+
+        let parts = split_by_hunks(file, header.len() + 2 * hunk(10).len());
+        assert_eq!(parts.len(), 2);
+        assert_eq!(parts[0].path, "a.rs (part 1 of 2)");
+        assert!(parts[0].patch.starts_with(header));
+        assert!(parts[0].patch.contains("@@ -10 +10 @@"));
+        assert!(parts[1].patch.starts_with(header));
+        assert!(parts[1].patch.contains("@@ -20 +20 @@"));
+    }
+
+    #[test]
+    fn test_split_empty_diff() {
+        assert!(split_diff_by_file("").is_empty());
+    }
+}
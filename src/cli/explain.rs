@@ -1,80 +1,938 @@
 use anyhow::{Context, Result};
 use clap::Args;
-use std::path::PathBuf;
+use futures::StreamExt;
+use std::io::{self, BufRead, IsTerminal, Read, Write};
+use std::path::{Path, PathBuf};
+use std::pin::pin;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
 // use tracing::{debug, info}; // Commented out debug and info
 
+use super::{color, context, highlight, history, pager, quiet, spinner::Spinner, render, stream, truncation, walk};
 use crate::{
-    ai::AIService,
+    ai::{AIService, DetailLevel, ExplainOptions, Message, Overrides, ResponseLength},
+    concurrency::{run_as_completed, run_bounded},
     config::Config,
+    error::CommandError,
+    error::{error_json, AlreadyReported},
+    parse::{self, Confidence},
 };
 
 #[derive(Args, Debug)]
 pub struct ExplainArgs {
-    /// Path to the file to explain
-    #[arg(required = true)]
-    pub file: PathBuf,
+    /// Paths or glob patterns of the files to explain, directories to explain
+    /// every file in, or `-` to read code from stdin
+    pub file: Vec<PathBuf>,
 
     /// Programming language of the code
     #[arg(short, long)]
     pub language: Option<String>,
 
-    /// Number of context lines to include
+    /// Only explain this 1-based, inclusive line range (START:END)
+    #[arg(long, value_name = "START:END")]
+    pub lines: Option<LineRange>,
+
+    /// Number of surrounding lines to show the model as context around --lines
+    /// (defaults to commands.explain.max_context_lines)
     #[arg(short, long)]
     pub context_lines: Option<usize>,
 
-    /// Output format (markdown, plain)
+    /// Only explain the function with this name (or any item, such as a struct
+    /// or impl block, in builds with the tree-sitter feature)
+    #[arg(long, value_name = "NAME", conflicts_with = "lines")]
+    pub function: Option<String>,
+
+    /// Only explain the public interface of a Rust file: `pub` signatures and their docs
+    #[arg(long, conflicts_with_all = ["lines", "function"])]
+    pub api_only: bool,
+
+    /// Output format (markdown, plain, json, html, or ndjson with --stream)
     #[arg(short, long, default_value = "markdown")]
     pub format: String,
+
+    /// Print each explanation as it is generated, one file at a time
+    #[arg(long, conflicts_with = "output")]
+    pub stream: bool,
+
+    /// Use temperature 0 (and a fixed seed where supported) for reproducible output
+    #[arg(long)]
+    pub deterministic: bool,
+
+    /// Sampling temperature for this run only, from 0.0 to 1.0 (overrides ai.temperature)
+    #[arg(long, value_parser = super::parse_temperature, conflicts_with = "deterministic")]
+    pub temperature: Option<f32>,
+
+    /// Most tokens the answer may use, for this run only (overrides ai.max_tokens)
+    #[arg(long, value_parser = super::parse_max_tokens)]
+    pub max_tokens: Option<usize>,
+
+    /// Language to answer in, as a name or locale code such as `ja` (overrides ai.output_language)
+    #[arg(long, value_name = "LOCALE")]
+    pub lang_out: Option<String>,
+
+    /// Show how long each request took under its explanation (markdown and plain output)
+    #[arg(long)]
+    pub timing: bool,
+
+    /// With several files, print the explanations in the order the files were
+    /// given, once all are done, instead of each as soon as it is ready
+    #[arg(long)]
+    pub ordered: bool,
+
+    /// How long the explanation should be
+    #[arg(long, value_enum)]
+    pub length: Option<ResponseLength>,
+
+    /// How deep the explanation should go
+    #[arg(long, value_enum, default_value_t = DetailLevel::Medium)]
+    pub detail: DetailLevel,
+
+    /// Report failures as JSON on stdout (implied by `--format json`)
+    #[arg(long)]
+    pub json_errors: bool,
+
+    /// Write the result to this file instead of stdout
+    #[arg(short, long)]
+    pub output: Option<PathBuf>,
+
+    /// Overwrite the --output file if it already exists, and explain files
+    /// larger than commands.explain.max_file_bytes
+    #[arg(long)]
+    pub force: bool,
+
+    /// Explain a file over commands.max_context_tokens in parts, split between
+    /// top-level items, instead of refusing or truncating it
+    #[arg(long)]
+    pub chunk: bool,
+
+    /// After explaining a file in parts, also ask for a summary of the whole file
+    #[arg(long, requires = "chunk", conflicts_with = "stream")]
+    pub summary: bool,
+
+    /// Include files that .gitignore, hidden-file and build-output rules would
+    /// leave out when explaining a directory
+    #[arg(long)]
+    pub no_ignore: bool,
+}
+
+/// A 1-based, inclusive range of lines, parsed from `START:END`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LineRange {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl FromStr for LineRange {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (start, end) = s
+            .split_once(':')
+            .ok_or_else(|| format!("expected START:END, got '{}'", s))?;
+        let start: usize = start
+            .trim()
+            .parse()
+            .map_err(|_| format!("invalid start line '{}'", start))?;
+        let end: usize = end
+            .trim()
+            .parse()
+            .map_err(|_| format!("invalid end line '{}'", end))?;
+        if start == 0 || end < start {
+            return Err(format!("invalid line range {}:{}; lines start at 1 and END must not be before START", start, end));
+        }
+        Ok(Self { start, end })
+    }
+}
+
+/// A piece of code ready to be sent to the model.
+struct ExplainTarget {
+    source: String,
+    content: String,
+    language: String,
+    /// How sure the language guess is; `None` when `--language` was given
+    confidence: Option<Confidence>,
+    /// The file this is one part of, when it was explained in chunks
+    chunk_of: Option<String>,
 }
 
 pub async fn execute(args: ExplainArgs) -> Result<()> {
     // debug!("Executing explain command with args: {:?}", args); // Commented out
 
+    // Structured errors go to stdout as JSON so wrapping tools can parse them
+    let json_errors = args.json_errors || matches!(args.format.as_str(), "json" | "ndjson");
+    match run(args, json_errors).await {
+        Err(e) if json_errors && !e.is::<AlreadyReported>() => {
+            println!("{}", error_json(&e, None));
+            Err(AlreadyReported(format!("{:#}", e)).into())
+        }
+        result => result,
+    }
+}
+
+async fn run(args: ExplainArgs, json_errors: bool) -> Result<()> {
     // Load configuration
     let config = Config::load()?;
+    let context_lines = args
+        .context_lines
+        .unwrap_or(config.commands.explain.max_context_lines);
+
+    // Reject an unknown format or a clobbered output before spending any requests
+    check_format(&args.format, args.stream)?;
+    if let Some(path) = &args.output {
+        check_output_path(path, &args.format, args.force)?;
+    }
+
+    // With no files, fall back to reading piped stdin
+    let paths = expand_paths(&args.file, !args.no_ignore)?;
+    if !args.file.is_empty() && paths.is_empty() {
+        anyhow::bail!("No files matched the given patterns");
+    }
+    let files: Vec<Option<&Path>> = if paths.is_empty() {
+        vec![None]
+    } else {
+        paths.iter().map(|file| Some(file.as_path())).collect()
+    };
+
+    let mut failures = Vec::new();
+    let mut targets = Vec::new();
+    let mut skipped = 0;
+    for file in files {
+        // Refuse a file too big to send whole before reading any of it
+        let whole = args.lines.is_none() && args.function.is_none() && !args.api_only && !args.chunk;
+        if let Some((path, size)) = file
+            .filter(|_| whole && !args.force)
+            .and_then(|path| Some((path, context::oversized(path, config.commands.explain.max_file_bytes)?)))
+        {
+            failures.push((
+                path.display().to_string(),
+                anyhow::anyhow!(
+                    "the file is {} bytes, over the {} byte limit (commands.explain.max_file_bytes); \
+                     pass --lines START:END or --function NAME to explain part of it, or --force to send it all",
+                    size,
+                    config.commands.explain.max_file_bytes
+                ),
+            ));
+            continue;
+        }
+
+        // Read the code from the file, or from stdin when piped
+        let read = read_source(
+            file,
+            args.language.is_some(),
+            config.commands.detect_encoding,
+            io::stdin().is_terminal(),
+            io::stdin(),
+        )
+        .and_then(|(source, content, encoding)| {
+            // Guess the language from the whole file, before any of it is cut away
+            let detected = parse::detect_language_with_confidence(file, &content);
+            let (source, content) = match (&args.function, args.lines) {
+                (Some(name), _) => {
+                    let language = args.language.as_deref().unwrap_or(&detected.0);
+                    let (span, selected) = select_function(&content, language, name, config.commands.truncation_notices)
+                        .with_context(|| format!("In {}", source))?;
+                    (format!("{}:{}-{} ({})", source, span.start_line, span.end_line, name), selected)
+                }
+                (None, Some(range)) => (
+                    format!("{}:{}-{}", source, range.start, range.end),
+                    select_lines(&content, range, context_lines, config.commands.truncation_notices)?,
+                ),
+                (None, None) if args.api_only => {
+                    let language = args.language.as_deref().unwrap_or(&detected.0);
+                    api_surface(source, content, language)
+                }
+                (None, None) => (source, content),
+            };
+            let source = match encoding {
+                Some(encoding) => format!("{} (decoded from {})", source, encoding),
+                None => source,
+            };
+            Ok((source, content, detected))
+        });
+        let (source, content, detected) = match read {
+            Ok(read) => read,
+            // One binary file among many shouldn't fail the whole run
+            Err(e) if e.is::<parse::BinaryFile>() => {
+                if !quiet::enabled() {
+                    eprintln!("Warning: {:#}; skipped it", e);
+                }
+                skipped += 1;
+                continue;
+            }
+            Err(e) => {
+                let source = file.map_or_else(|| "<stdin>".to_string(), |f| f.display().to_string());
+                failures.push((source, e));
+                continue;
+            }
+        };
+
+        // Determine language
+        let (language, confidence) = match &args.language {
+            Some(language) => (language.clone(), None),
+            None => (detected.0, Some(detected.1)),
+        };
+
+        // Only what goes to the model is redacted; the source label stays as given
+        let content = context::redact_for(&content, &config);
+        let max_tokens = config.commands.max_context_tokens;
+        if args.chunk && history::estimate_tokens(&content) > max_tokens {
+            let chunks = parse::chunk_code(&content, &language, max_tokens * 4);
+            let parts = chunks.len();
+            for (i, chunk) in chunks.into_iter().enumerate() {
+                let mut content = chunk.text;
+                if config.commands.truncation_notices {
+                    content.push('\n');
+                    content.push_str(&truncation::partial_file_note(i + 1, parts, chunk.start_line, chunk.end_line));
+                }
+                targets.push(ExplainTarget {
+                    source: format!("{}:{}-{} (part {} of {})", source, chunk.start_line, chunk.end_line, i + 1, parts),
+                    content,
+                    language: language.clone(),
+                    confidence,
+                    chunk_of: Some(source.clone()),
+                });
+            }
+            continue;
+        }
+
+        targets.push(ExplainTarget {
+            source,
+            content,
+            language,
+            confidence,
+            chunk_of: None,
+        });
+    }
+
+    if targets.is_empty() && failures.is_empty() && skipped > 0 {
+        anyhow::bail!("Nothing to explain; every file looked binary");
+    }
+
+    // Let the user correct guessed languages before spending requests on them.
+    // The prompt goes to stderr, and never runs when the output is for a
+    // program rather than a person.
+    let guessed: Vec<(&str, &str, Confidence)> = targets
+        .iter()
+        .filter_map(|target| Some((target.source.as_str(), target.language.as_str(), target.confidence?)))
+        .collect();
+    let machine_output = matches!(args.format.as_str(), "json" | "ndjson");
+    if !guessed.is_empty() && !machine_output && io::stdin().is_terminal() && io::stderr().is_terminal() {
+        if let Some(correction) = confirm_language(&guessed, io::stdin().lock(), io::stderr())? {
+            for target in targets.iter_mut().filter(|target| target.confidence.is_some()) {
+                target.language = correction.clone();
+            }
+        }
+    }
+
+    if args.stream {
+        let printed = if targets.is_empty() {
+            0
+        } else {
+            stream_targets(&args, config, &targets, &mut failures).await?
+        };
+        return report_failures(&failures, printed, json_errors);
+    }
+
+    // Several files go to stdout as each is explained, unless asked to keep
+    // their order; a summary needs every part of its file first
+    if targets.len() > 1 && !args.ordered && !args.summary && args.output.is_none() {
+        let printed = print_as_completed(&args, config, &targets, &mut failures).await?;
+        return report_failures(&failures, printed, json_errors);
+    }
+
+    // Nothing readable means nothing to send
+    let summary_config = args.summary.then(|| config.clone());
+    let (explanations, timings): (Vec<_>, Vec<_>) = if targets.is_empty() {
+        Default::default()
+    } else {
+        explain_targets(&args, config, &targets).await?.into_iter().unzip()
+    };
+    let mut summaries = match summary_config {
+        Some(config) => summarize_chunks(&args, config, &targets, &explanations).await?,
+        None => Vec::new(),
+    };
+
+    // A section per file or part, with a chunked file's summary after its last part
+    let mut sections = Vec::new();
+    for (i, (target, explanation)) in targets.iter().zip(explanations).enumerate() {
+        sections.push((target.source.clone(), &target.language, explanation, Some(timings[i])));
+        if let (Some(summary), Some(file)) = (summaries.get_mut(i).and_then(Option::take), &target.chunk_of) {
+            sections.push((format!("{} (summary)", file), &target.language, summary, None));
+        }
+    }
+
+    // Format output, one delimited section per file
+    let mut output = String::new();
+    let mut printed = 0;
+    for (source, language, explanation, elapsed) in sections {
+        match explanation {
+            Ok(explanation) => {
+                output.push_str(&format_section(&args, &source, language, &explanation, elapsed, printed)?);
+                printed += 1;
+            }
+            Err(e) => failures.push((source, e)),
+        }
+    }
+
+    match &args.output {
+        Some(path) if printed > 0 => {
+            std::fs::write(path, &output)
+                .with_context(|| format!("Failed to write output file: {:?}", path))?;
+            if !quiet::enabled() {
+                eprintln!("Wrote {} explanation(s) to {}", printed, path.display());
+            }
+        }
+        Some(_) => {}
+        None => pager::print(&for_terminal(&output, &args.format)),
+    }
+
+    report_failures(&failures, printed, json_errors)
+}
 
-    // Read file content
-    let content = std::fs::read_to_string(&args.file)
-        .with_context(|| format!("Failed to read file: {:?}", args.file))?;
-
-    // Determine language
-    let language = args.language.unwrap_or_else(|| {
-        // Try to detect language from file extension
-        args.file
-            .extension()
-            .and_then(|ext| ext.to_str())
-            .unwrap_or("unknown")
-            .to_string()
+/// One file's explanation as printed: after a `---` unless it is the first,
+/// and followed by its `--timing` note, dimmed on a terminal.
+fn format_section(
+    args: &ExplainArgs,
+    source: &str,
+    language: &str,
+    explanation: &str,
+    elapsed: Option<Duration>,
+    printed: usize,
+) -> Result<String> {
+    let mut section = String::new();
+    // JSON and HTML sections delimit themselves
+    if printed > 0 && !matches!(args.format.as_str(), "json" | "html") {
+        section.push_str("\n---\n\n");
+    }
+    section.push_str(&format_explanation(source, language, explanation, &args.format)?);
+    if let (true, Some(elapsed), "markdown" | "plain") = (args.timing, elapsed, args.format.as_str()) {
+        let note = render::elapsed_note(elapsed);
+        match args.output {
+            None => section.push_str(&format!("\n{}\n", color::dim(&note))),
+            Some(_) => section.push_str(&format!("\n{}\n", note)),
+        }
+    }
+    Ok(section)
+}
+
+/// Readies output for stdout: markdown is rendered when color is on, and
+/// prose is wrapped to a terminal. Piped output is left as it is.
+fn for_terminal(output: &str, format: &str) -> String {
+    match (format, render::terminal_width()) {
+        ("markdown", width) if color::enabled() => highlight::highlight_code_blocks(output, "", width),
+        ("markdown" | "plain", Some(width)) => render::wrap_prose(output, width),
+        _ => output.to_string(),
+    }
+}
+
+/// Reports files that couldn't be explained: as JSON lines in JSON mode,
+/// otherwise on stderr, followed by an overall error.
+fn report_failures(failures: &[(String, anyhow::Error)], printed: usize, json_errors: bool) -> Result<()> {
+    if failures.is_empty() {
+        return Ok(());
+    }
+
+    let summary = format!(
+        "{} of {} files could not be explained",
+        failures.len(),
+        failures.len() + printed
+    );
+    if json_errors {
+        for (source, error) in failures {
+            println!("{}", error_json(error, Some(source)));
+        }
+        return Err(AlreadyReported(summary).into());
+    }
+
+    for (source, error) in failures {
+        eprintln!("Failed to explain {}: {:#}", source, error);
+    }
+    anyhow::bail!(summary)
+}
+
+/// Builds the service for an explain run, applying `--deterministic`.
+fn explain_service(args: &ExplainArgs, mut config: Config) -> Result<AIService> {
+    config.ai.apply(Overrides {
+        temperature: args.temperature,
+        max_tokens: args.max_tokens,
+        output_language: args.lang_out.clone(),
     });
+    if args.deterministic {
+        if let (Some(warning), false) = (config.ai.make_deterministic(), quiet::enabled()) {
+            eprintln!("Warning: {}", warning);
+        }
+    }
 
     // Create AI service
-    let ai_service = AIService::new(config.ai)?;
+    AIService::new(config.ai)
+}
+
+fn explain_options(args: &ExplainArgs, config: &Config) -> ExplainOptions {
+    ExplainOptions {
+        length: args.length,
+        detail: args.detail,
+        template: config.prompts.explain.clone(),
+    }
+}
+
+/// Sends every target to the model, a few at a time. The outer error is for
+/// setup failures; each target gets its own result, with how long its
+/// request took.
+async fn explain_targets(
+    args: &ExplainArgs,
+    config: Config,
+    targets: &[ExplainTarget],
+) -> Result<Vec<(Result<String>, Duration)>> {
+    let options = explain_options(args, &config);
+    let limit = config.commands.max_concurrency;
+    let ai_service = explain_service(args, config)?;
 
-    // Get explanation
     // info!("Getting explanation for {} code", language); // Commented out
-    let explanation = ai_service.explain(&content, &language).await?;
+    if args.output.is_some() && !quiet::enabled() {
+        // stdout isn't showing anything, so report progress on stderr
+        eprintln!("Explaining {} file(s)...", targets.len());
+    }
+    // Otherwise the spinner shows something is happening
+    let _spinner = args
+        .output
+        .is_none()
+        .then(|| Spinner::start(&format!("Explaining {} file(s)...", targets.len())));
+    let (ai_service, options) = (&ai_service, &options);
+    Ok(run_bounded(targets, limit, |target| async move {
+        let started = Instant::now();
+        let result = ai_service.explain(&target.content, &target.language, options).await;
+        (result, started.elapsed())
+    })
+    .await)
+}
+
+/// Sends every target to the model, up to `commands.max_concurrency` at a
+/// time, and prints each explanation as soon as it arrives. Failures are
+/// collected into `failures` without stopping the rest; returns the number
+/// of targets explained.
+async fn print_as_completed(
+    args: &ExplainArgs,
+    config: Config,
+    targets: &[ExplainTarget],
+    failures: &mut Vec<(String, anyhow::Error)>,
+) -> Result<usize> {
+    let options = explain_options(args, &config);
+    let limit = config.commands.max_concurrency;
+    let ai_service = explain_service(args, config)?;
+    let (ai_service, options) = (&ai_service, &options);
 
-    // Format and print output
-    match args.format.as_str() {
-        "markdown" => {
-            println!("# Code Explanation\n");
-            println!("## File: {}\n", args.file.display());
-            println!("## Language: {}\n", language);
-            println!("## Explanation\n");
-            println!("{}", explanation);
+    let mut spinner = Some(Spinner::start(&format!("Explaining {} files...", targets.len())));
+    let mut results = pin!(run_as_completed(targets, limit, |target| async move {
+        let started = Instant::now();
+        let result = ai_service.explain(&target.content, &target.language, options).await;
+        (result, started.elapsed())
+    }));
+    let mut out = io::stdout();
+    let mut printed = 0;
+    while let Some((i, (result, elapsed))) = results.next().await {
+        let target = &targets[i];
+        match result {
+            Ok(explanation) => {
+                // The spinner goes once there is something to show
+                spinner.take();
+                let section = format_section(args, &target.source, &target.language, &explanation, Some(elapsed), printed)?;
+                write!(out, "{}", for_terminal(&section, &args.format))?;
+                out.flush()?;
+                printed += 1;
+            }
+            Err(e) => failures.push((target.source.clone(), e)),
         }
-        "plain" => {
-            println!("File: {}", args.file.display());
-            println!("Language: {}", language);
-            println!("\nExplanation:\n");
-            println!("{}", explanation);
+    }
+    Ok(printed)
+}
+
+/// For `--summary`: asks the model to sum up each chunked file from the
+/// explanations of its parts. The summary for a file is returned at the index
+/// of its last part; every other entry is `None`.
+async fn summarize_chunks(
+    args: &ExplainArgs,
+    config: Config,
+    targets: &[ExplainTarget],
+    explanations: &[Result<String>],
+) -> Result<Vec<Option<Result<String>>>> {
+    let ai_service = explain_service(args, config)?;
+    let _spinner = args.output.is_none().then(|| Spinner::start("Summarizing..."));
+
+    let mut summaries: Vec<Option<Result<String>>> = targets.iter().map(|_| None).collect();
+    let mut start = 0;
+    while start < targets.len() {
+        let Some(file) = &targets[start].chunk_of else {
+            start += 1;
+            continue;
+        };
+        let end = targets[start..]
+            .iter()
+            .position(|target| target.chunk_of.as_ref() != Some(file))
+            .map_or(targets.len(), |offset| start + offset);
+
+        let parts: Vec<&str> = explanations[start..end]
+            .iter()
+            .filter_map(|explanation| explanation.as_deref().ok())
+            .collect();
+        if !parts.is_empty() {
+            let messages = [Message {
+                role: "user".to_string(),
+                content: summary_prompt(file, &parts),
+            }];
+            summaries[end - 1] = Some(ai_service.chat(&messages, None).await);
+        }
+        start = end;
+    }
+    Ok(summaries)
+}
+
+/// Asks for an overview of a file from the explanations of its parts, in order.
+fn summary_prompt(file: &str, parts: &[&str]) -> String {
+    let mut prompt = format!(
+        "The file {} was too large to explain at once, so it was explained in {} consecutive parts. \
+         Using the explanations below, write a short summary of what the whole file does and how its parts fit together.",
+        file,
+        parts.len()
+    );
+    for (i, part) in parts.iter().enumerate() {
+        prompt.push_str(&format!("\n\n## Part {}\n\n{}", i + 1, part));
+    }
+    prompt
+}
+
+/// Streams each target's explanation to stdout in turn, under its usual
+/// heading or as NDJSON. Failures are collected into `failures`; returns the
+/// number of targets explained.
+async fn stream_targets(
+    args: &ExplainArgs,
+    config: Config,
+    targets: &[ExplainTarget],
+    failures: &mut Vec<(String, anyhow::Error)>,
+) -> Result<usize> {
+    let options = explain_options(args, &config);
+    let ai_service = explain_service(args, config)?;
+    let ndjson = args.format == "ndjson";
+
+    let mut out = io::stdout();
+    let mut printed = 0;
+    for (i, target) in targets.iter().enumerate() {
+        if !ndjson {
+            if i > 0 {
+                write!(out, "\n---\n\n")?;
+            }
+            // The formatted section minus its closing newline is the heading to stream under
+            let heading = format_explanation(&target.source, &target.language, "", &args.format)?;
+            write!(out, "{}", heading.strip_suffix('\n').unwrap_or(&heading))?;
+        }
+
+        let started = Instant::now();
+        let result = ai_service
+            .explain_stream(
+                &target.content,
+                &target.language,
+                &options,
+                &mut stream::write_deltas(&mut out, ndjson),
+            )
+            .await;
+        let elapsed = started.elapsed();
+        match result {
+            Ok(completion) if ndjson => {
+                writeln!(out, "{}", stream::ndjson_done(completion.usage, Some(&target.source)))?;
+                printed += 1;
+            }
+            Ok(_) => {
+                writeln!(out)?;
+                if args.timing {
+                    writeln!(out, "\n{}", color::dim(&render::elapsed_note(elapsed)))?;
+                }
+                printed += 1;
+            }
+            Err(e) => {
+                if !ndjson {
+                    writeln!(out)?;
+                }
+                failures.push((target.source.clone(), e));
+            }
+        }
+    }
+
+    Ok(printed)
+}
+
+/// Rejects an unknown format, or one that doesn't fit the chosen mode.
+fn check_format(format: &str, stream: bool) -> Result<()> {
+    match (format, stream) {
+        ("ndjson", false) => Err(CommandError::InvalidArgument(
+            "--format ndjson streams its output; pass --stream as well".to_string(),
+        )
+        .into()),
+        ("ndjson" | "markdown" | "plain", true) => Ok(()),
+        (_, true) => Err(CommandError::InvalidArgument(format!(
+            "--stream supports markdown, plain, and ndjson output, not {}",
+            format
+        ))
+        .into()),
+        (_, false) => format_explanation("", "", "", format).map(|_| ()),
+    }
+}
+
+/// Cuts `range` out of `content`, with up to `context` lines on either side.
+/// Each part is labelled so the model knows which lines are only context, and
+/// with `notices` the lines left out entirely are called out too.
+fn select_lines(content: &str, range: LineRange, context: usize, notices: bool) -> Result<String> {
+    let lines: Vec<&str> = content.lines().collect();
+    if range.start > lines.len() {
+        anyhow::bail!(
+            "Line range {}:{} starts past the end of the file ({} lines)",
+            range.start,
+            range.end,
+            lines.len()
+        );
+    }
+
+    // Work in 0-based, end-exclusive indices from here on
+    let start = range.start - 1;
+    let end = range.end.min(lines.len());
+    let before = start.saturating_sub(context);
+    let after = (end + context).min(lines.len());
+
+    let mut selected = String::new();
+    if notices && before > 0 {
+        selected.push_str(&truncation::omitted_lines_note(1, before));
+        selected.push('\n');
+    }
+    let mut push_part = |label: &str, from: usize, to: usize| {
+        if from < to {
+            selected.push_str(&format!("[{}: lines {}-{}]\n", label, from + 1, to));
+            for line in &lines[from..to] {
+                selected.push_str(line);
+                selected.push('\n');
+            }
+        }
+    };
+    push_part("context only, do not explain", before, start);
+    push_part("explain", start, end);
+    push_part("context only, do not explain", end, after);
+    if notices && after < lines.len() {
+        selected.push_str(&truncation::omitted_lines_note(after + 1, lines.len()));
+        selected.push('\n');
+    }
+
+    Ok(selected)
+}
+
+/// Cuts the function (or, with syntax trees, any item) called `name` out of
+/// `content`. When it isn't there, the error lists the function-like names
+/// that were found instead.
+fn select_function(content: &str, language: &str, name: &str, notices: bool) -> Result<(parse::FunctionSpan, String)> {
+    let span = match parse::find_definition(content, language, name) {
+        Some(span) => span,
+        None => {
+            let found = parse::function_names(content);
+            if found.is_empty() {
+                anyhow::bail!("Function '{}' not found, and no function definitions were recognised", name);
+            }
+            anyhow::bail!("Function '{}' not found; found: {}", name, found.join(", "));
         }
-        _ => anyhow::bail!("Unsupported output format: {}", args.format),
+    };
+
+    let total = content.lines().count();
+    let mut selected = String::new();
+    if notices && span.start_line > 1 {
+        selected.push_str(&truncation::omitted_lines_note(1, span.start_line - 1));
+        selected.push('\n');
+    }
+    selected.push_str(&span.text);
+    selected.push('\n');
+    if notices && span.end_line < total {
+        selected.push_str(&truncation::omitted_lines_note(span.end_line + 1, total));
+        selected.push('\n');
     }
 
+    Ok((span, selected))
+}
+
+/// Refuses to overwrite an existing file without `force`, and warns when the
+/// file extension doesn't match the chosen format.
+fn check_output_path(path: &Path, format: &str, force: bool) -> Result<()> {
+    if path.exists() && !force {
+        anyhow::bail!("{} already exists; pass --force to overwrite it", path.display());
+    }
+
+    let expected: &[&str] = match format {
+        "markdown" => &["md", "markdown"],
+        "plain" => &["txt"],
+        "json" => &["json"],
+        "html" => &["html", "htm"],
+        _ => &[],
+    };
+    let extension = path.extension().and_then(|ext| ext.to_str()).unwrap_or("");
+    if let Some(suggested) = expected.first() {
+        if !expected.contains(&extension) {
+            eprintln!(
+                "Warning: writing {} output to {}; consider a .{} extension",
+                format,
+                path.display(),
+                suggested
+            );
+        }
+    }
     Ok(())
 }
 
+/// Expands arguments containing glob metacharacters into the files they match,
+/// and directories into the files under them (honoring ignore rules when
+/// `respect_ignore` is set). Other arguments are kept as literal paths.
+/// Patterns matching nothing are reported with a warning. Stdin (`-`) can only
+/// be read once, so it may only be given once.
+fn expand_paths(args: &[PathBuf], respect_ignore: bool) -> Result<Vec<PathBuf>> {
+    if args.iter().filter(|arg| arg.as_os_str() == "-").count() > 1 {
+        return Err(CommandError::InvalidArgument("`-` (stdin) can only be given once".to_string()).into());
+    }
+
+    let mut paths = Vec::new();
+    for arg in args {
+        if arg.is_dir() {
+            paths.extend(walk::files(arg, respect_ignore));
+            continue;
+        }
+        let pattern = arg.to_string_lossy();
+        if !pattern.contains(['*', '?', '[']) {
+            paths.push(arg.clone());
+            continue;
+        }
+
+        let before = paths.len();
+        for entry in glob::glob(&pattern).with_context(|| format!("Invalid glob pattern: {}", pattern))? {
+            let path = entry?;
+            if path.is_file() {
+                paths.push(path);
+            }
+        }
+        if paths.len() == before {
+            eprintln!("Warning: pattern '{}' did not match any files", pattern);
+        }
+    }
+    Ok(paths)
+}
+
+fn format_explanation(source: &str, language: &str, explanation: &str, format: &str) -> Result<String> {
+    match format {
+        "markdown" => Ok(format!(
+            "# Code Explanation\n\n## File: {}\n\n## Language: {}\n\n## Explanation\n\n{}\n",
+            source, language, explanation
+        )),
+        "plain" => Ok(format!(
+            "File: {}\nLanguage: {}\n\nExplanation:\n\n{}\n",
+            source, language, explanation
+        )),
+        "json" => Ok(format!(
+            "{}\n",
+            serde_json::json!({
+                "file": source,
+                "language": language,
+                "explanation": explanation,
+            })
+        )),
+        "html" => Ok(format!(
+            "<section class=\"monk-explanation\">\n<h2>{}</h2>\n<p class=\"language\">Language: {}</p>\n{}</section>\n",
+            render::escape_html(source),
+            render::escape_html(language),
+            render::markdown_to_html(explanation)
+        )),
+        _ => anyhow::bail!("Unsupported output format: {}", format),
+    }
+}
+
+/// Narrows Rust code to its public API for `--api-only`. Anything that can't
+/// be narrowed is explained whole, with a warning.
+fn api_surface(source: String, content: String, language: &str) -> (String, String) {
+    if language != "rust" {
+        eprintln!("Warning: --api-only needs Rust code; explaining all of {} ({})", source, language);
+        return (source, content);
+    }
+    match parse::public_api(&content) {
+        Ok(api) => (format!("{} (public API)", source), api),
+        Err(e) => {
+            eprintln!("Warning: {:#}; explaining all of {}", e, source);
+            (source, content)
+        }
+    }
+}
+
+/// Reads the code to explain and returns it with a display name for its source.
+/// Code comes from stdin when `file` is `-`, or when it is omitted and stdin is
+/// not a terminal; stdin has no extension, so a language must be given then.
+/// Files that aren't UTF-8 are transcoded when `detect_encoding` is set, and
+/// the encoding they were read as is returned alongside. Binary files are
+/// refused with a `BinaryFile` error.
+fn read_source(
+    file: Option<&Path>,
+    language_given: bool,
+    detect_encoding: bool,
+    stdin_is_terminal: bool,
+    mut stdin: impl Read,
+) -> Result<(String, String, Option<&'static str>)> {
+    let from_stdin = match file {
+        Some(path) => path.as_os_str() == "-",
+        None => !stdin_is_terminal,
+    };
+
+    if from_stdin {
+        if !language_given {
+            return Err(CommandError::MissingArgument(
+                "--language is required when reading code from stdin".to_string(),
+            )
+            .into());
+        }
+        let mut content = String::new();
+        stdin
+            .read_to_string(&mut content)
+            .context("Failed to read code from stdin")?;
+        return Ok(("<stdin>".to_string(), content, None));
+    }
+
+    let file = file.ok_or_else(|| anyhow::anyhow!("No file given; pass a path or pipe code on stdin"))?;
+    let bytes = std::fs::read(file).with_context(|| format!("Failed to read file: {:?}", file))?;
+    if parse::is_probably_binary(&bytes) {
+        return Err(parse::BinaryFile(file.display().to_string()).into());
+    }
+    let (content, encoding) =
+        parse::decode_source(bytes, detect_encoding).with_context(|| format!("Failed to read file: {:?}", file))?;
+    Ok((file.display().to_string(), content, encoding))
+}
+
+/// Shows the detected languages, with how sure each guess is, and reads an
+/// optional correction that applies to every guessed file. An empty line
+/// accepts the guesses.
+fn confirm_language(
+    detected: &[(&str, &str, Confidence)],
+    mut input: impl BufRead,
+    mut output: impl Write,
+) -> Result<Option<String>> {
+    match detected {
+        [(_, language, confidence)] => write!(
+            output,
+            "Detected language: {} ({} confidence; press Enter to accept or type a correction): ",
+            language, confidence
+        )?,
+        _ => {
+            writeln!(output, "Detected languages:")?;
+            for (source, language, confidence) in detected {
+                writeln!(output, "  {}: {} ({} confidence)", source, language, confidence)?;
+            }
+            write!(output, "Press Enter to accept, or type a language to use for all of them: ")?;
+        }
+    }
+    output.flush()?;
+
+    let mut line = String::new();
+    input.read_line(&mut line)?;
+
+    let correction = line.trim();
+    Ok((!correction.is_empty()).then(|| correction.to_string()))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -86,10 +944,28 @@ mod tests {
         std::fs::write(&temp_file, "fn main() { println!(\"Hello, world!\"); }").unwrap();
 
         let args = ExplainArgs {
-            file: temp_file.path().to_path_buf(),
+            file: vec![temp_file.path().to_path_buf()],
             language: Some("rust".to_string()),
+            lines: None,
             context_lines: None,
+            function: None,
+            api_only: false,
             format: "markdown".to_string(),
+            stream: false,
+            deterministic: false,
+            temperature: None,
+            max_tokens: None,
+            lang_out: None,
+            timing: false,
+            ordered: false,
+            length: None,
+            detail: DetailLevel::Medium,
+            json_errors: false,
+            output: None,
+            force: false,
+            chunk: false,
+            summary: false,
+            no_ignore: false,
         };
 
         // This test will fail if the AI service is not properly configured
@@ -97,24 +973,317 @@ mod tests {
         assert!(result.is_err());
     }
 
+    #[test]
+    fn test_read_source_from_stdin() {
+        let code = "print('hi')\n";
+        let (source, content, _) = read_source(Some(Path::new("-")), true, true, true, code.as_bytes()).unwrap();
+        assert_eq!(source, "<stdin>");
+        assert_eq!(content, code);
+
+        // Omitting the file reads piped stdin too
+        let (_, content, _) = read_source(None, true, true, false, code.as_bytes()).unwrap();
+        assert_eq!(content, code);
+
+        // Without a language there is nothing to go on
+        assert!(read_source(Some(Path::new("-")), false, true, false, code.as_bytes()).is_err());
+        // Nothing given at an interactive terminal
+        assert!(read_source(None, true, true, true, code.as_bytes()).is_err());
+    }
+
+    #[test]
+    fn test_api_surface() {
+        let code = "/// Adds.\npub fn add(a: i32, b: i32) -> i32 {\n    helper(a) + b\n}\n\nfn helper(a: i32) -> i32 {\n    a\n}\n";
+
+        let (source, api) = api_surface("lib.rs".to_string(), code.to_string(), "rust");
+        assert_eq!(source, "lib.rs (public API)");
+        assert_eq!(api, "/// Adds.\npub fn add(a: i32, b: i32) -> i32;\n");
+
+        // Other languages, and Rust that doesn't parse, go through whole
+        let (source, content) = api_surface("add.py".to_string(), "def add(): pass\n".to_string(), "python");
+        assert_eq!((source.as_str(), content.as_str()), ("add.py", "def add(): pass\n"));
+        let (_, content) = api_surface("broken.rs".to_string(), "pub fn {".to_string(), "rust");
+        assert_eq!(content, "pub fn {");
+    }
+
+    #[test]
+    fn test_summary_prompt_keeps_parts_in_order() {
+        let prompt = summary_prompt("src/big.rs", &["Sets up logging.", "Parses arguments."]);
+        assert!(prompt.starts_with("The file src/big.rs was too large to explain at once, so it was explained in 2 consecutive parts."));
+        assert!(prompt.ends_with("## Part 1\n\nSets up logging.\n\n## Part 2\n\nParses arguments."));
+    }
+
+    #[test]
+    fn test_read_latin1_file() {
+        let temp_file = NamedTempFile::new().unwrap();
+        let (latin1, _, _) = encoding_rs::WINDOWS_1252.encode("# Prix: 5€ — déjà payé, reçu envoyé à l'élève\n");
+        std::fs::write(&temp_file, &latin1).unwrap();
+
+        let (_, content, encoding) = read_source(Some(temp_file.path()), false, true, true, io::empty()).unwrap();
+        assert_eq!(content, "# Prix: 5€ — déjà payé, reçu envoyé à l'élève\n");
+        assert_eq!(encoding, Some("windows-1252"));
+
+        assert!(read_source(Some(temp_file.path()), false, false, true, io::empty()).is_err());
+
+        std::fs::write(&temp_file, [0x7f, b'E', b'L', b'F', 2, 1, 1, 0, 0, 0]).unwrap();
+        let error = read_source(Some(temp_file.path()), false, true, true, io::empty()).unwrap_err();
+        assert!(error.is::<parse::BinaryFile>());
+    }
+
+    #[test]
+    fn test_read_failure_as_json_error() {
+        let missing = Path::new("/no/such/dir/missing.rs");
+        let error = read_source(Some(missing), false, true, true, io::empty()).unwrap_err();
+
+        let json = error_json(&error, Some("/no/such/dir/missing.rs"));
+        let value: serde_json::Value = serde_json::from_str(&json.to_string()).unwrap();
+        assert_eq!(value["error"]["kind"], "io");
+        assert_eq!(value["error"]["file"], "/no/such/dir/missing.rs");
+        assert!(value["error"]["message"]
+            .as_str()
+            .unwrap()
+            .contains("Failed to read file"));
+    }
+
+    #[test]
+    fn test_json_failures_are_reported_once() {
+        let failures = vec![("a.rs".to_string(), anyhow::anyhow!("unreadable"))];
+        let error = report_failures(&failures, 1, true).unwrap_err();
+        assert!(error.is::<AlreadyReported>());
+        assert_eq!(error.to_string(), "1 of 2 files could not be explained");
+
+        assert!(!report_failures(&failures, 1, false).unwrap_err().is::<AlreadyReported>());
+        assert!(report_failures(&[], 1, true).is_ok());
+    }
+
+    #[test]
+    fn test_check_output_path_requires_force() {
+        let existing = NamedTempFile::with_suffix(".md").unwrap();
+        assert!(check_output_path(existing.path(), "markdown", false).is_err());
+        assert!(check_output_path(existing.path(), "markdown", true).is_ok());
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        // Mismatched extensions only warn
+        assert!(check_output_path(&temp_dir.path().join("out.md"), "json", false).is_ok());
+    }
+
+    #[test]
+    fn test_line_range_parsing() {
+        assert_eq!("10:20".parse::<LineRange>(), Ok(LineRange { start: 10, end: 20 }));
+        assert!("0:5".parse::<LineRange>().is_err());
+        assert!("9:3".parse::<LineRange>().is_err());
+        assert!("12".parse::<LineRange>().is_err());
+    }
+
+    #[test]
+    fn test_select_lines_with_context() {
+        let content = (1..=10).map(|n| format!("line {}", n)).collect::<Vec<_>>().join("\n");
+
+        let selected = select_lines(&content, LineRange { start: 4, end: 5 }, 2, false).unwrap();
+        assert_eq!(
+            selected,
+            "[context only, do not explain: lines 2-3]\nline 2\nline 3\n\
+             [explain: lines 4-5]\nline 4\nline 5\n\
+             [context only, do not explain: lines 6-7]\nline 6\nline 7\n"
+        );
+
+        // No context, and an end past the file is clamped
+        let selected = select_lines(&content, LineRange { start: 9, end: 40 }, 0, false).unwrap();
+        assert_eq!(selected, "[explain: lines 9-10]\nline 9\nline 10\n");
+
+        assert!(select_lines(&content, LineRange { start: 11, end: 12 }, 0, false).is_err());
+    }
+
+    #[test]
+    fn test_select_lines_notes_omitted_lines() {
+        let content = (1..=10).map(|n| format!("line {}", n)).collect::<Vec<_>>().join("\n");
+
+        let selected = select_lines(&content, LineRange { start: 5, end: 5 }, 1, true).unwrap();
+        assert!(selected.starts_with(&truncation::omitted_lines_note(1, 3)));
+        assert!(selected.trim_end().ends_with(&truncation::omitted_lines_note(7, 10)));
+
+        // The whole file was shown, so there is nothing to note
+        let selected = select_lines(&content, LineRange { start: 1, end: 10 }, 0, true).unwrap();
+        assert!(!selected.contains("[Note:"));
+    }
+
+    #[test]
+    fn test_select_function() {
+        let content = "use std::io;\n\nfn helper() {\n    1\n}\n\nfn main() {\n    helper();\n}\n";
+
+        let (span, selected) = select_function(content, "rust", "helper", true).unwrap();
+        assert_eq!((span.start_line, span.end_line), (3, 5));
+        assert_eq!(
+            selected,
+            format!(
+                "{}\nfn helper() {{\n    1\n}}\n{}\n",
+                truncation::omitted_lines_note(1, 2),
+                truncation::omitted_lines_note(6, 9)
+            )
+        );
+
+        let error = select_function(content, "rust", "missing", false).unwrap_err();
+        assert_eq!(error.to_string(), "Function 'missing' not found; found: helper, main");
+    }
+
+    #[test]
+    fn test_confirm_language() {
+        let mut output = Vec::new();
+        let one = [("a.rs", "rust", Confidence::High)];
+        let accepted = confirm_language(&one, "\n".as_bytes(), &mut output).unwrap();
+        assert_eq!(accepted, None);
+        assert!(String::from_utf8(output)
+            .unwrap()
+            .starts_with("Detected language: rust (high confidence;"));
+
+        let corrected = confirm_language(&one, "  python \n".as_bytes(), io::sink()).unwrap();
+        assert_eq!(corrected.as_deref(), Some("python"));
+
+        // Several files are confirmed with a single prompt
+        let mut output = Vec::new();
+        let several = [("a.rs", "rust", Confidence::High), ("tool", "sh", Confidence::Medium)];
+        confirm_language(&several, "\n".as_bytes(), &mut output).unwrap();
+        let shown = String::from_utf8(output).unwrap();
+        assert!(shown.contains("  tool: sh (medium confidence)\n"));
+        assert_eq!(shown.matches("Press Enter").count(), 1);
+    }
+
+    #[test]
+    fn test_expand_paths() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let nested = temp_dir.path().join("src").join("cli");
+        std::fs::create_dir_all(&nested).unwrap();
+        std::fs::write(temp_dir.path().join("src").join("main.rs"), "").unwrap();
+        std::fs::write(nested.join("mod.rs"), "").unwrap();
+        std::fs::write(nested.join("notes.txt"), "").unwrap();
+
+        let pattern = temp_dir.path().join("src").join("**").join("*.rs");
+        let literal = PathBuf::from("does/not/exist.rs");
+        let paths = expand_paths(&[pattern, literal.clone()], true).unwrap();
+
+        assert_eq!(
+            paths,
+            vec![
+                nested.join("mod.rs"),
+                temp_dir.path().join("src").join("main.rs"),
+                literal,
+            ]
+        );
+
+        let empty = temp_dir.path().join("*.py");
+        assert!(expand_paths(&[empty], true).unwrap().is_empty());
+
+        let directory = expand_paths(&[temp_dir.path().join("src")], true).unwrap();
+        assert_eq!(
+            directory,
+            vec![
+                nested.join("mod.rs"),
+                nested.join("notes.txt"),
+                temp_dir.path().join("src").join("main.rs"),
+            ]
+        );
+
+        let stdin = PathBuf::from("-");
+        let other = PathBuf::from("other.rs");
+        assert!(expand_paths(&[stdin.clone(), other.clone()], true).is_ok());
+        assert!(expand_paths(&[stdin.clone(), other, stdin], true).is_err());
+    }
+
+    #[test]
+    fn test_format_explanation_sections() {
+        let markdown = format_explanation("src/a.rs", "rs", "It adds.", "markdown").unwrap();
+        assert!(markdown.starts_with("# Code Explanation"));
+        assert!(markdown.contains("## File: src/a.rs"));
+
+        let plain = format_explanation("src/a.rs", "rs", "It adds.", "plain").unwrap();
+        assert!(plain.starts_with("File: src/a.rs\nLanguage: rs"));
+
+        assert!(format_explanation("src/a.rs", "rs", "It adds.", "yaml").is_err());
+    }
+
+    #[test]
+    fn test_check_format_for_streaming() {
+        assert!(check_format("ndjson", true).is_ok());
+        assert!(check_format("ndjson", false).is_err());
+        assert!(check_format("html", true).is_err());
+        assert!(check_format("html", false).is_ok());
+        assert!(check_format("yaml", false).is_err());
+    }
+
+    #[test]
+    fn test_format_explanation_html() {
+        let html = format_explanation("src/<a>.rs", "rust", "Calls `foo`.\n\n```rust\nfoo();\n```", "html").unwrap();
+        assert!(html.starts_with("<section class=\"monk-explanation\">"));
+        assert!(html.contains("<h2>src/&lt;a&gt;.rs</h2>"));
+        assert!(html.contains("<code>foo</code>"));
+        assert!(html.contains("<pre><code class=\"language-rust\">foo();"));
+        assert!(html.trim_end().ends_with("</section>"));
+    }
+
+    #[test]
+    fn test_format_explanation_json() {
+        let explanation = "Uses \"quotes\" and\na ```rust\nfence\n```";
+        let json = format_explanation("src/a.rs", "rust", explanation, "json").unwrap();
+        assert_eq!(json.lines().count(), 1);
+
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(value["file"], "src/a.rs");
+        assert_eq!(value["language"], "rust");
+        assert_eq!(value["explanation"], explanation);
+    }
+
     #[test]
     fn test_language_detection() {
         let args = ExplainArgs {
-            file: PathBuf::from("test.rs"),
+            file: vec![PathBuf::from("test.rs")],
             language: None,
+            lines: None,
             context_lines: None,
+            function: None,
+            api_only: false,
             format: "markdown".to_string(),
+            stream: false,
+            deterministic: false,
+            temperature: None,
+            max_tokens: None,
+            lang_out: None,
+            timing: false,
+            ordered: false,
+            length: None,
+            detail: DetailLevel::Medium,
+            json_errors: false,
+            output: None,
+            force: false,
+            chunk: false,
+            summary: false,
+            no_ignore: false,
         };
 
         assert_eq!(
-            args.language.unwrap_or_else(|| {
-                args.file
-                    .extension()
-                    .and_then(|ext| ext.to_str())
-                    .unwrap_or("unknown")
-                    .to_string()
-            }),
-            "rs"
+            args.language
+                .unwrap_or_else(|| parse::detect_language(Some(&args.file[0]), "")),
+            "rust"
         );
     }
+
+    #[test]
+    fn test_format_section() {
+        use clap::Parser;
+        let cli = super::super::Cli::parse_from(["monk", "explain", "a.rs", "b.rs", "--timing", "--format", "plain", "-o", "out.txt"]);
+        let Some(super::super::Commands::Explain(mut args)) = cli.command else {
+            panic!("Expected Explain command");
+        };
+        let elapsed = Some(Duration::from_millis(1500));
+
+        let first = format_section(&args, "a.rs", "rust", "Adds numbers.", elapsed, 0).unwrap();
+        assert_eq!(first, format!("{}\n(1.5s)\n", format_explanation("a.rs", "rust", "Adds numbers.", "plain").unwrap()));
+        // Later sections are set apart, whichever file finished first
+        let second = format_section(&args, "b.rs", "rust", "Parses input.", None, 1).unwrap();
+        assert!(second.starts_with("\n---\n\n"), "{}", second);
+        assert!(!second.contains("(1.5s)"));
+
+        // JSON sections delimit themselves and carry no timing note
+        args.format = "json".to_string();
+        let json = format_section(&args, "b.rs", "rust", "Parses input.", elapsed, 1).unwrap();
+        assert!(!json.starts_with("\n---") && !json.contains("(1.5s)"), "{}", json);
+    }
 } 
\ No newline at end of file
@@ -0,0 +1,227 @@
+use std::sync::OnceLock;
+use syntect::easy::HighlightLines;
+use syntect::highlighting::ThemeSet;
+use syntect::parsing::SyntaxSet;
+use syntect::util::{as_24_bit_terminal_escaped, LinesWithEndings};
+
+use super::render::TerminalMarkdown;
+
+const RESET: &str = "\x1B[0m";
+const THEME: &str = "base16-ocean.dark";
+
+fn syntax_set() -> &'static SyntaxSet {
+    static SYNTAXES: OnceLock<SyntaxSet> = OnceLock::new();
+    SYNTAXES.get_or_init(SyntaxSet::load_defaults_newlines)
+}
+
+fn theme_set() -> &'static ThemeSet {
+    static THEMES: OnceLock<ThemeSet> = OnceLock::new();
+    THEMES.get_or_init(ThemeSet::load_defaults)
+}
+
+/// Colors the contents of fenced code blocks in `text` for the terminal, using
+/// the language named on the opening fence, and renders the markdown around
+/// them. Fences and code in an unrecognised language are wrapped in
+/// `prose_style` (if non-empty) as they are. Prose is wrapped to `width`
+/// when given; code never is.
+pub fn highlight_code_blocks(text: &str, prose_style: &str, width: Option<usize>) -> String {
+    let syntaxes = syntax_set();
+    let theme = &theme_set().themes[THEME];
+
+    let mut output = String::with_capacity(text.len());
+    let mut in_code = false;
+    let mut highlighter: Option<HighlightLines> = None;
+
+    for line in LinesWithEndings::from(text) {
+        let (body, newline) = match line.strip_suffix('\n') {
+            Some(body) => (body, "\n"),
+            None => (line, ""),
+        };
+
+        if body.trim_start().starts_with("```") {
+            if in_code {
+                highlighter = None;
+            } else {
+                let language = body.trim_start().trim_start_matches('`').trim();
+                highlighter = syntaxes
+                    .find_syntax_by_token(language)
+                    .map(|syntax| HighlightLines::new(syntax, theme));
+            }
+            in_code = !in_code;
+        } else if let Some(highlighter) = highlighter.as_mut() {
+            if let Ok(ranges) = highlighter.highlight_line(line, syntaxes) {
+                let escaped = as_24_bit_terminal_escaped(&ranges, false);
+                output.push_str(escaped.strip_suffix('\n').unwrap_or(&escaped));
+                output.push_str(RESET);
+                output.push_str(newline);
+                continue;
+            }
+        }
+
+        if in_code || body.trim_start().starts_with("```") {
+            output.push_str(&styled(line, prose_style));
+        } else {
+            let mut markdown = TerminalMarkdown::new(prose_style).wrapped_to(width);
+            output.push_str(&markdown.push(body));
+            output.push_str(&markdown.end_line());
+            output.push_str(newline);
+        }
+    }
+
+    output
+}
+
+/// Wraps `text`, minus any trailing newline, in `style`.
+fn styled(text: &str, style: &str) -> String {
+    let (body, newline) = match text.strip_suffix('\n') {
+        Some(body) => (body, "\n"),
+        None => (text, ""),
+    };
+    if style.is_empty() || body.is_empty() {
+        return text.to_string();
+    }
+    format!("{}{}{}{}", style, body, RESET, newline)
+}
+
+/// Highlights a response while it streams in. Prose is rendered and passed
+/// on as soon as it arrives; a fenced code block is held back until its
+/// closing fence and then comes out highlighted in one piece.
+pub struct StreamHighlighter {
+    prose_style: String,
+    /// Columns to wrap prose at, if any
+    width: Option<usize>,
+    markdown: TerminalMarkdown,
+    /// The current, unfinished line
+    line: String,
+    /// How much of `line` has already been passed on
+    shown: usize,
+    /// An open code block, from its opening fence
+    block: Option<String>,
+}
+
+impl StreamHighlighter {
+    pub fn new(prose_style: &str) -> Self {
+        Self {
+            prose_style: prose_style.to_string(),
+            width: None,
+            markdown: TerminalMarkdown::new(prose_style),
+            line: String::new(),
+            shown: 0,
+            block: None,
+        }
+    }
+
+    /// Wraps prose to `width` columns; `None` leaves it unwrapped.
+    pub fn wrapped_to(mut self, width: Option<usize>) -> Self {
+        self.width = width;
+        self.markdown = TerminalMarkdown::new(&self.prose_style).wrapped_to(width);
+        self
+    }
+
+    /// Takes the next piece of the response and returns what can be printed now.
+    pub fn push(&mut self, delta: &str) -> String {
+        let mut output = String::new();
+        for piece in delta.split_inclusive('\n') {
+            self.line.push_str(piece);
+            if self.line.ends_with('\n') {
+                output.push_str(&self.end_line());
+            }
+        }
+
+        // Hold back the start of a line until it's clear it doesn't open a fence
+        let start = self.line.trim_start();
+        let may_be_fence = "```".starts_with(start) || start.starts_with("```");
+        if self.block.is_none() && !may_be_fence {
+            output.push_str(&self.markdown.push(&self.line[self.shown..]));
+            self.shown = self.line.len();
+        }
+        output
+    }
+
+    /// Returns whatever was still held back once the response is complete.
+    pub fn finish(&mut self) -> String {
+        let line = std::mem::take(&mut self.line);
+        let shown = std::mem::take(&mut self.shown);
+        match self.block.take() {
+            Some(block) => highlight_code_blocks(&(block + &line), &self.prose_style, self.width),
+            None => self.markdown.push(&line[shown..]) + &self.markdown.end_line(),
+        }
+    }
+
+    fn end_line(&mut self) -> String {
+        let line = std::mem::take(&mut self.line);
+        let shown = std::mem::take(&mut self.shown);
+        let is_fence = line.trim_start().starts_with("```");
+        match self.block.as_mut() {
+            Some(block) => {
+                block.push_str(&line);
+                if !is_fence {
+                    return String::new();
+                }
+                let block = self.block.take().unwrap_or_default();
+                highlight_code_blocks(&block, &self.prose_style, self.width)
+            }
+            None if is_fence => {
+                self.block = Some(line);
+                String::new()
+            }
+            None => {
+                let body = line[shown..].strip_suffix('\n').unwrap_or(&line[shown..]);
+                self.markdown.push(body) + &self.markdown.end_line() + "\n"
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_highlight_only_colors_known_code() {
+        let text = "Call it like this:\n\n```rust\nfn main() {}\n```\n\n```nosuchlang\nplain\n```\nDone.";
+        let highlighted = highlight_code_blocks(text, "", None);
+
+        let lines: Vec<&str> = highlighted.lines().collect();
+        assert_eq!(lines[0], "Call it like this:");
+        assert_eq!(lines[2], "```rust");
+        assert!(lines[3].contains("\x1B[38;2;"));
+        assert!(lines[3].ends_with(RESET));
+        assert_eq!(lines[7], "plain");
+        assert_eq!(lines[9], "Done.");
+    }
+
+    #[test]
+    fn test_stream_highlighter_holds_back_code_blocks() {
+        let mut highlighter = StreamHighlighter::new("");
+        assert_eq!(highlighter.push("Here"), "Here");
+        assert_eq!(highlighter.push(" it is:\n`"), " it is:\n");
+        assert_eq!(highlighter.push("``rust\nfn main"), "");
+        assert_eq!(highlighter.push("() {}\n"), "");
+
+        let block = highlighter.push("```\nDone");
+        assert!(block.starts_with("```rust\n"));
+        assert!(block.contains("\x1B[38;2;"));
+        assert!(block.ends_with("```\nDone"));
+        assert_eq!(highlighter.finish(), "");
+
+        // Inline code isn't mistaken for a fence
+        let mut highlighter = StreamHighlighter::new("");
+        assert_eq!(highlighter.push("`x` is"), "\x1B[36mx\x1B[39m is");
+        assert_eq!(highlighter.finish(), "");
+    }
+
+    #[test]
+    fn test_prose_style_wraps_other_lines() {
+        let highlighted = highlight_code_blocks("Hello\n\nWorld\n", "\x1B[32m", None);
+        assert_eq!(highlighted, "\x1B[32mHello\x1B[0m\n\n\x1B[32mWorld\x1B[0m\n");
+
+        // Markdown around the code is rendered, but not inside it
+        let highlighted = highlight_code_blocks("## Usage\n```text\n**as is**\n```\n", "", None);
+        assert_eq!(highlighted, "\x1B[1mUsage\x1B[0m\n```text\n**as is**\n```\n");
+
+        // Prose is wrapped and code isn't
+        let highlighted = highlight_code_blocks("Some words to wrap\n```\nlet long_line = 1;\n```\n", "", Some(10));
+        assert_eq!(highlighted, "Some words\nto wrap\n```\nlet long_line = 1;\n```\n");
+    }
+}
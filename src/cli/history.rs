@@ -0,0 +1,785 @@
+use anyhow::{Context, Result};
+use std::collections::BTreeMap;
+use std::io::{BufRead, Write};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use super::truncation;
+use crate::{ai::Message, config::RoleRepair};
+
+/// Most messages kept in the saved conversation; older ones are dropped on save.
+const MAX_SAVED_MESSAGES: usize = 200;
+
+/// Named in-memory snapshots of a conversation, for `/checkpoint` and
+/// `/restore`. They live only as long as the session.
+#[derive(Debug, Default)]
+pub struct Checkpoints {
+    snapshots: BTreeMap<String, Vec<Message>>,
+}
+
+impl Checkpoints {
+    /// Stores a copy of `history` under `name`, replacing any earlier snapshot.
+    pub fn save(&mut self, name: &str, history: &[Message]) {
+        self.snapshots.insert(name.to_string(), history.to_vec());
+    }
+
+    /// Returns a copy of the history saved under `name`.
+    pub fn restore(&self, name: &str) -> Option<Vec<Message>> {
+        self.snapshots.get(name).cloned()
+    }
+
+    /// Checkpoint names with their message counts, in name order.
+    pub fn list(&self) -> Vec<(&str, usize)> {
+        self.snapshots
+            .iter()
+            .map(|(name, history)| (name.as_str(), history.len()))
+            .collect()
+    }
+}
+
+/// Name of the tab every interactive session starts in.
+pub const MAIN_TAB: &str = "main";
+
+/// Independent conversations held side by side in one interactive session,
+/// for `/new <name>`, `/tab` and `/tabs`. Exactly one is active at a time.
+#[derive(Debug)]
+pub struct Tabs {
+    active: String,
+    conversations: BTreeMap<String, Vec<Message>>,
+}
+
+impl Tabs {
+    /// Starts with a single main tab holding `history`.
+    pub fn new(history: Vec<Message>) -> Self {
+        Self {
+            active: MAIN_TAB.to_string(),
+            conversations: BTreeMap::from([(MAIN_TAB.to_string(), history)]),
+        }
+    }
+
+    /// Name of the active tab.
+    pub fn active_name(&self) -> &str {
+        &self.active
+    }
+
+    /// The active tab's conversation.
+    pub fn active(&mut self) -> &mut Vec<Message> {
+        self.conversations.entry(self.active.clone()).or_default()
+    }
+
+    /// Creates an empty tab called `name` and switches to it. Returns false,
+    /// leaving everything as it was, if the tab already exists.
+    pub fn open(&mut self, name: &str) -> bool {
+        if self.conversations.contains_key(name) {
+            return false;
+        }
+        self.conversations.insert(name.to_string(), Vec::new());
+        self.active = name.to_string();
+        true
+    }
+
+    /// Makes `name` the active tab. Returns false if there is no such tab.
+    pub fn switch(&mut self, name: &str) -> bool {
+        if !self.conversations.contains_key(name) {
+            return false;
+        }
+        self.active = name.to_string();
+        true
+    }
+
+    /// Adds a tab restored from disk without switching to it.
+    pub fn insert(&mut self, name: &str, history: Vec<Message>) {
+        self.conversations.insert(name.to_string(), history);
+    }
+
+    /// Number of open tabs.
+    pub fn count(&self) -> usize {
+        self.conversations.len()
+    }
+
+    /// Tab names with their message counts, in name order.
+    pub fn list(&self) -> Vec<(&str, usize)> {
+        self.conversations
+            .iter()
+            .map(|(name, history)| (name.as_str(), history.len()))
+            .collect()
+    }
+
+    /// Every tab except the main one, for saving alongside it.
+    pub fn others(&self) -> BTreeMap<&str, &[Message]> {
+        self.conversations
+            .iter()
+            .filter(|(name, _)| name.as_str() != MAIN_TAB)
+            .map(|(name, history)| (name.as_str(), history.as_slice()))
+            .collect()
+    }
+}
+
+/// Total bytes of message content held in a conversation.
+pub fn history_bytes(history: &[Message]) -> usize {
+    history.iter().map(|message| message.content.len()).sum()
+}
+
+/// Keeps the retained conversation under `max_bytes` by evicting the oldest
+/// messages. The most recent message is never evicted; if it alone exceeds the
+/// cap its content is cut down. With `notices` set, evictions and cuts are
+/// both called out with a truncation note (notes don't count against the cap).
+/// Returns the number of messages evicted.
+pub fn enforce_byte_cap(history: &mut Vec<Message>, max_bytes: usize, notices: bool) -> usize {
+    let mut evicted = 0;
+    // Messages an earlier eviction left out, as noted on a message evicted now
+    let mut noted = 0;
+    let mut evict = |history: &mut Vec<Message>| {
+        noted += truncation::split_omitted_messages_note(&history.remove(0).content).0;
+        evicted += 1;
+    };
+    while history.len() > 1 && history_bytes(history) > max_bytes {
+        evict(history);
+
+        // A conversation has to start with a user turn
+        while history.len() > 1 && history[0].role != "user" {
+            evict(history);
+        }
+    }
+
+    if let Some(last) = history.last_mut() {
+        if last.content.len() > max_bytes {
+            let removed = last.content.len() - max_bytes;
+            let mut cut = max_bytes;
+            while !last.content.is_char_boundary(cut) {
+                cut -= 1;
+            }
+            last.content.truncate(cut);
+            if notices {
+                last.content.push('\n');
+                last.content.push_str(&truncation::omitted_bytes_note(removed));
+            }
+        }
+    }
+
+    if let (true, true, Some(first)) = (notices, evicted > 0, history.first_mut()) {
+        first.content = truncation::prepend_omitted_messages_note(&first.content, evicted + noted);
+    }
+
+    evicted
+}
+
+/// Drops the latest answer so its question can be asked again. Returns false,
+/// leaving the history alone, if the last message isn't an answer.
+pub fn pop_last_answer(history: &mut Vec<Message>) -> bool {
+    if history.last().is_none_or(|message| message.role != "assistant") {
+        return false;
+    }
+    history.pop();
+    true
+}
+
+/// Removes the last exchange: the latest user message and the answer to it,
+/// if there is one. Returns how many messages were removed; a history with
+/// fewer than two messages is left alone.
+pub fn undo_last_exchange(history: &mut Vec<Message>) -> usize {
+    if history.len() < 2 {
+        return 0;
+    }
+    match history.iter().rposition(|message| message.role == "user") {
+        Some(index) => {
+            let removed = history.len() - index;
+            history.truncate(index);
+            removed
+        }
+        None => 0,
+    }
+}
+
+/// Rough token count of `text`, at about four characters per token.
+pub fn estimate_tokens(text: &str) -> usize {
+    text.chars().count().div_ceil(4)
+}
+
+/// Rough token count of every message in `history`.
+pub fn history_tokens(history: &[Message]) -> usize {
+    history.iter().map(|message| estimate_tokens(&message.content)).sum()
+}
+
+/// Index of the oldest message to send so the conversation, plus `reserved`
+/// tokens for the system and project context, stays within `max_tokens`.
+/// The newest messages are kept first, the slice always starts with a user
+/// turn, and the latest message is sent even if it alone is over budget.
+pub fn token_budget_start(history: &[Message], reserved: usize, max_tokens: usize) -> usize {
+    let mut used = reserved;
+    let mut start = history.len();
+    while start > 0 {
+        let cost = estimate_tokens(&history[start - 1].content);
+        if start < history.len() && used + cost > max_tokens {
+            break;
+        }
+        used += cost;
+        start -= 1;
+    }
+
+    // Don't open with an assistant turn
+    while start < history.len().saturating_sub(1) && history[start].role != "user" {
+        start += 1;
+    }
+    start
+}
+
+/// Makes user and assistant turns alternate again, as the API requires, after
+/// edits or failed requests leave two messages with the same role side by side.
+/// A leading assistant turn has nothing to merge into, so either strategy puts
+/// a placeholder user turn in front of it. Meant for the copy of the history
+/// sent with a request; the stored conversation is left as it was typed.
+/// Returns the number of repairs made.
+pub fn repair_alternation(history: &mut Vec<Message>, strategy: RoleRepair) -> usize {
+    let mut repairs = 0;
+    if history.first().is_some_and(|message| message.role == "assistant") {
+        history.insert(
+            0,
+            Message {
+                role: "user".to_string(),
+                content: "(continue)".to_string(),
+            },
+        );
+        repairs += 1;
+    }
+
+    let mut i = 1;
+    while i < history.len() {
+        if history[i].role != history[i - 1].role {
+            i += 1;
+            continue;
+        }
+
+        match strategy {
+            RoleRepair::Merge => {
+                let message = history.remove(i);
+                let previous = &mut history[i - 1].content;
+                previous.push_str("\n\n");
+                previous.push_str(&message.content);
+            }
+            RoleRepair::Bridge => {
+                let (role, content) = if history[i].role == "user" {
+                    ("assistant", "(no response)")
+                } else {
+                    ("user", "(continue)")
+                };
+                history.insert(
+                    i,
+                    Message {
+                        role: role.to_string(),
+                        content: content.to_string(),
+                    },
+                );
+                i += 2;
+            }
+        }
+        repairs += 1;
+    }
+
+    repairs
+}
+
+/// Where the conversation is kept between interactive sessions.
+pub fn saved_history_path() -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| dir.join("monk-manager").join("history.json"))
+}
+
+/// Where tabs other than the main one are kept between interactive sessions.
+pub fn saved_tabs_path() -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| dir.join("monk-manager").join("tabs.json"))
+}
+
+/// The most recent `MAX_SAVED_MESSAGES` messages of `history`, within `max_bytes`.
+fn saved_tail(history: &[Message], max_bytes: usize) -> Vec<Message> {
+    let mut kept = history[history.len().saturating_sub(MAX_SAVED_MESSAGES)..].to_vec();
+    while kept.len() > 1 && kept[0].role != "user" {
+        kept.remove(0);
+    }
+    enforce_byte_cap(&mut kept, max_bytes, false);
+    kept
+}
+
+fn write_json(path: &Path, value: &impl serde::Serialize) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create directory: {:?}", parent))?;
+    }
+    let json = serde_json::to_string_pretty(value)?;
+    std::fs::write(path, json).with_context(|| format!("Failed to save conversation: {:?}", path))
+}
+
+/// Writes the conversation to `path`, keeping only the most recent
+/// `MAX_SAVED_MESSAGES` messages within `max_bytes` so the file stays small
+/// however long it is used.
+pub fn save_history(path: &Path, history: &[Message], max_bytes: usize) -> Result<()> {
+    write_json(path, &saved_tail(history, max_bytes))
+}
+
+/// Writes every tab but the main one to `path`, each trimmed like
+/// `save_history`. With no other tabs open the file is removed.
+pub fn save_tabs(path: &Path, tabs: &Tabs, max_bytes: usize) -> Result<()> {
+    let others: BTreeMap<&str, Vec<Message>> = tabs
+        .others()
+        .into_iter()
+        .map(|(name, history)| (name, saved_tail(history, max_bytes)))
+        .collect();
+    if others.is_empty() {
+        if path.exists() {
+            std::fs::remove_file(path).with_context(|| format!("Failed to remove {:?}", path))?;
+        }
+        return Ok(());
+    }
+    write_json(path, &others)
+}
+
+/// Reads tabs saved by `save_tabs`. A missing file means no extra tabs.
+pub fn load_tabs(path: &Path) -> Result<BTreeMap<String, Vec<Message>>> {
+    if !path.exists() {
+        return Ok(BTreeMap::new());
+    }
+    let json = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read saved tabs: {:?}", path))?;
+    serde_json::from_str(&json).with_context(|| format!("Saved tabs are corrupt: {:?}", path))
+}
+
+/// Reads a conversation saved by `save_history`. A missing file is an empty
+/// conversation.
+pub fn load_history(path: &Path) -> Result<Vec<Message>> {
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let json = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read saved conversation: {:?}", path))?;
+    serde_json::from_str(&json).with_context(|| format!("Saved conversation is corrupt: {:?}", path))
+}
+
+/// Where conversations saved with `/save` are kept.
+pub fn sessions_dir() -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| dir.join("monk-manager").join("sessions"))
+}
+
+/// The file for a named session. Names are limited to letters, digits, `-`
+/// and `_` so they can't point outside `dir`.
+fn session_path(dir: &Path, name: &str) -> Result<PathBuf> {
+    if name.is_empty() || !name.chars().all(|c| c.is_alphanumeric() || matches!(c, '-' | '_')) {
+        anyhow::bail!("Invalid session name '{}': use letters, digits, '-' and '_'", name);
+    }
+    Ok(dir.join(format!("{}.json", name)))
+}
+
+/// Saves the whole conversation under `name`, replacing any earlier save.
+pub fn save_session(dir: &Path, name: &str, history: &[Message]) -> Result<PathBuf> {
+    let path = session_path(dir, name)?;
+    std::fs::create_dir_all(dir).with_context(|| format!("Failed to create directory: {:?}", dir))?;
+    let json = serde_json::to_string_pretty(history)?;
+    std::fs::write(&path, json).with_context(|| format!("Failed to save session: {:?}", path))?;
+    Ok(path)
+}
+
+/// Reads the session saved under `name`, or `None` if there isn't one.
+pub fn load_session(dir: &Path, name: &str) -> Result<Option<Vec<Message>>> {
+    let path = session_path(dir, name)?;
+    if !path.exists() {
+        return Ok(None);
+    }
+    load_history(&path).map(Some)
+}
+
+/// Names of the saved sessions, sorted.
+pub fn list_sessions(dir: &Path) -> Result<Vec<String>> {
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+    let mut names = Vec::new();
+    for entry in std::fs::read_dir(dir).with_context(|| format!("Failed to list sessions in {:?}", dir))? {
+        let path = entry?.path();
+        if path.extension().is_some_and(|ext| ext == "json") {
+            if let Some(name) = path.file_stem().and_then(|stem| stem.to_str()) {
+                names.push(name.to_string());
+            }
+        }
+    }
+    names.sort();
+    Ok(names)
+}
+
+/// A saved session as `monk-manager sessions list` shows it.
+#[derive(Debug)]
+pub struct SessionInfo {
+    pub name: String,
+    /// `None` when the file can't be read as a conversation
+    pub turns: Option<usize>,
+    pub modified: Option<SystemTime>,
+}
+
+/// Every saved session with its turn count and when it was last saved.
+pub fn session_details(dir: &Path) -> Result<Vec<SessionInfo>> {
+    list_sessions(dir)?
+        .into_iter()
+        .map(|name| {
+            let path = session_path(dir, &name)?;
+            Ok(SessionInfo {
+                turns: load_history(&path).ok().map(|history| turn_count(&history)),
+                modified: std::fs::metadata(&path).and_then(|metadata| metadata.modified()).ok(),
+                name,
+            })
+        })
+        .collect()
+}
+
+/// Deletes the session saved under `name`. Returns false if there isn't one.
+pub fn delete_session(dir: &Path, name: &str) -> Result<bool> {
+    let path = session_path(dir, name)?;
+    if !path.exists() {
+        return Ok(false);
+    }
+    std::fs::remove_file(&path).with_context(|| format!("Failed to delete session: {:?}", path))?;
+    Ok(true)
+}
+
+/// Number of user turns in a conversation.
+pub fn turn_count(history: &[Message]) -> usize {
+    history.iter().filter(|message| message.role == "user").count()
+}
+
+/// The conversation split into turns: each user message with the answers
+/// that follow it. Anything before the first user message is a turn of its own.
+pub fn turns(history: &[Message]) -> Vec<&[Message]> {
+    let mut turns = Vec::new();
+    let mut start = 0;
+    for (i, message) in history.iter().enumerate() {
+        if message.role == "user" && i > start {
+            turns.push(&history[start..i]);
+            start = i;
+        }
+    }
+    if start < history.len() {
+        turns.push(&history[start..]);
+    }
+    turns
+}
+
+/// Asks whether to pick up the saved conversation. An empty answer resumes.
+pub fn confirm_resume(messages: usize, mut input: impl BufRead, mut output: impl Write) -> Result<bool> {
+    write!(
+        output,
+        "Resume your previous conversation ({} messages)? [Y/n] ",
+        messages
+    )?;
+    output.flush()?;
+
+    let mut line = String::new();
+    input.read_line(&mut line)?;
+    Ok(!matches!(line.trim().to_ascii_lowercase().as_str(), "n" | "no"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(role: &str, content: &str) -> Message {
+        Message {
+            role: role.to_string(),
+            content: content.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_restore_checkpoint_after_more_turns() {
+        let mut history = vec![message("user", "first"), message("assistant", "one")];
+        let mut checkpoints = Checkpoints::default();
+        checkpoints.save("before-refactor", &history);
+
+        history.push(message("user", "second"));
+        history.push(message("assistant", "two"));
+
+        history = checkpoints.restore("before-refactor").unwrap();
+        assert_eq!(history.len(), 2);
+        assert_eq!(history[1].content, "one");
+        assert!(checkpoints.restore("missing").is_none());
+        assert_eq!(checkpoints.list(), vec![("before-refactor", 2)]);
+    }
+
+    #[test]
+    fn test_byte_cap_evicts_oldest_messages() {
+        let mut history = vec![
+            message("user", &"a".repeat(40)),
+            message("assistant", &"b".repeat(40)),
+            message("user", &"c".repeat(40)),
+            message("assistant", &"d".repeat(40)),
+        ];
+
+        let evicted = enforce_byte_cap(&mut history, 100, false);
+
+        assert_eq!(evicted, 2);
+        assert_eq!(history.len(), 2);
+        assert_eq!(history[0].role, "user");
+        assert!(history[0].content.starts_with('c'));
+        assert!(history_bytes(&history) <= 100);
+    }
+
+    #[test]
+    fn test_byte_cap_notes_evicted_messages() {
+        let mut history = vec![
+            message("user", &"a".repeat(40)),
+            message("assistant", &"b".repeat(40)),
+            message("user", &"c".repeat(40)),
+            message("assistant", &"d".repeat(40)),
+        ];
+
+        enforce_byte_cap(&mut history, 100, true);
+        let note = truncation::omitted_messages_note(2);
+        assert_eq!(history[0].content, format!("{}\n\n{}", note, "c".repeat(40)));
+
+        // Evicting again adds to the existing note instead of stacking another
+        history.push(message("user", &"e".repeat(80)));
+        enforce_byte_cap(&mut history, 100, true);
+        assert_eq!(history.len(), 1);
+        assert!(history[0].content.starts_with(&truncation::omitted_messages_note(4)));
+        assert_eq!(history[0].content.matches("[Note:").count(), 1);
+    }
+
+    #[test]
+    fn test_byte_cap_truncates_oversized_last_message() {
+        let mut history = vec![message("user", "short"), message("user", &"x".repeat(500))];
+
+        enforce_byte_cap(&mut history, 100, true);
+
+        assert_eq!(history.len(), 1);
+        let (evicted, rest) = truncation::split_omitted_messages_note(&history[0].content);
+        assert_eq!(evicted, 1);
+        assert!(rest.starts_with(&"x".repeat(100)));
+        assert!(rest.ends_with(&truncation::omitted_bytes_note(400)));
+
+        let mut quiet = vec![message("user", &"x".repeat(500))];
+        enforce_byte_cap(&mut quiet, 100, false);
+        assert_eq!(quiet[0].content, "x".repeat(100));
+    }
+
+    #[test]
+    fn test_pop_last_answer() {
+        let mut history = vec![message("user", "explain this"), message("assistant", "a poor answer")];
+        assert!(pop_last_answer(&mut history));
+        assert_eq!(history.len(), 1);
+        assert_eq!(history[0].content, "explain this");
+
+        // The question is still there, but there's no answer to drop
+        assert!(!pop_last_answer(&mut history));
+        assert!(!pop_last_answer(&mut Vec::new()));
+    }
+
+    #[test]
+    fn test_turns() {
+        let history = vec![
+            message("user", "first"),
+            message("assistant", "answer"),
+            message("user", "unanswered"),
+            message("user", "second"),
+            message("assistant", "another"),
+        ];
+        let turns = turns(&history);
+        assert_eq!(turns.iter().map(|turn| turn.len()).collect::<Vec<_>>(), vec![2, 1, 2]);
+        assert_eq!(turns[2][0].content, "second");
+        assert!(super::turns(&[]).is_empty());
+    }
+
+    #[test]
+    fn test_undo_last_exchange() {
+        let mut history = vec![
+            message("user", "on topic"),
+            message("assistant", "useful"),
+            message("user", "off topic"),
+            message("assistant", "tangent"),
+        ];
+        assert_eq!(undo_last_exchange(&mut history), 2);
+        assert_eq!(history.last().unwrap().content, "useful");
+
+        // An unanswered question is undone on its own
+        history.push(message("user", "failed request"));
+        assert_eq!(undo_last_exchange(&mut history), 1);
+        assert_eq!(history.len(), 2);
+
+        let mut single = vec![message("user", "hi")];
+        assert_eq!(undo_last_exchange(&mut single), 0);
+        assert_eq!(single.len(), 1);
+        assert_eq!(undo_last_exchange(&mut Vec::new()), 0);
+    }
+
+    #[test]
+    fn test_token_budget_keeps_recent_turns() {
+        // 40 characters, so 10 tokens each
+        let history: Vec<Message> = (0..6)
+            .map(|turn| {
+                let role = if turn % 2 == 0 { "user" } else { "assistant" };
+                message(role, &format!("{:<40}", turn))
+            })
+            .collect();
+
+        assert_eq!(estimate_tokens(&history[0].content), 10);
+        assert_eq!(history_tokens(&history), 60);
+        assert_eq!(token_budget_start(&history, 0, 1_000), 0);
+
+        // Room for 3 messages after the context, but the slice must start with a user turn
+        assert_eq!(token_budget_start(&history, 20, 50), 4);
+        assert_eq!(history[4].role, "user");
+
+        // The latest message goes out even when the context fills the budget
+        assert_eq!(token_budget_start(&history, 100, 50), 5);
+    }
+
+    #[test]
+    fn test_repair_alternation() {
+        let malformed = vec![
+            message("user", "first"),
+            message("user", "again"),
+            message("assistant", "one"),
+            message("assistant", "two"),
+            message("user", "next"),
+        ];
+        let alternates = |history: &[Message]| history.windows(2).all(|pair| pair[0].role != pair[1].role);
+
+        let mut merged = malformed.clone();
+        assert_eq!(repair_alternation(&mut merged, RoleRepair::Merge), 2);
+        assert!(alternates(&merged));
+        assert_eq!(merged.len(), 3);
+        assert_eq!(merged[0].content, "first\n\nagain");
+        assert_eq!(merged[1].content, "one\n\ntwo");
+
+        let mut bridged = malformed;
+        assert_eq!(repair_alternation(&mut bridged, RoleRepair::Bridge), 2);
+        assert!(alternates(&bridged));
+        assert_eq!(bridged.len(), 7);
+        assert_eq!(bridged[1].role, "assistant");
+
+        let mut valid = vec![message("user", "hi"), message("assistant", "hello")];
+        assert_eq!(repair_alternation(&mut valid, RoleRepair::Merge), 0);
+
+        // The API needs a user turn first
+        let mut leading = vec![message("assistant", "hello"), message("user", "hi")];
+        assert_eq!(repair_alternation(&mut leading, RoleRepair::Merge), 1);
+        assert_eq!(leading[0].role, "user");
+        assert!(alternates(&leading));
+    }
+
+    #[test]
+    fn test_save_and_load_history() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("monk-manager").join("history.json");
+        assert!(load_history(&path).unwrap().is_empty());
+
+        let mut history = Vec::new();
+        for turn in 0..150 {
+            history.push(message("user", &format!("question {}", turn)));
+            history.push(message("assistant", &format!("answer {}", turn)));
+        }
+        save_history(&path, &history, 1_000_000).unwrap();
+
+        let loaded = load_history(&path).unwrap();
+        assert_eq!(loaded.len(), MAX_SAVED_MESSAGES);
+        assert_eq!(loaded[0].content, "question 50");
+        assert_eq!(loaded.last().unwrap().content, "answer 149");
+
+        // The byte cap applies on top of the message cap
+        save_history(&path, &history, 100).unwrap();
+        assert!(history_bytes(&load_history(&path).unwrap()) <= 100);
+    }
+
+    #[test]
+    fn test_save_and_load_named_sessions() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let dir = temp_dir.path().join("sessions");
+        assert!(list_sessions(&dir).unwrap().is_empty());
+
+        let history = vec![
+            message("user", "why is this slow?"),
+            message("assistant", "the loop allocates"),
+            message("user", "fix it"),
+        ];
+        save_session(&dir, "perf-review", &history).unwrap();
+        save_session(&dir, "alpha", &history[..1]).unwrap();
+
+        let loaded = load_session(&dir, "perf-review").unwrap().unwrap();
+        assert_eq!(loaded.len(), 3);
+        assert_eq!(turn_count(&loaded), 2);
+        assert!(load_session(&dir, "missing").unwrap().is_none());
+        assert_eq!(list_sessions(&dir).unwrap(), vec!["alpha", "perf-review"]);
+
+        assert!(save_session(&dir, "../escape", &history).is_err());
+    }
+
+    #[test]
+    fn test_session_details_and_delete() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let dir = temp_dir.path();
+        save_session(dir, "feature-a", &[message("user", "plan it"), message("assistant", "ok")]).unwrap();
+        std::fs::write(dir.join("broken.json"), "not json").unwrap();
+
+        let details = session_details(dir).unwrap();
+        let found: Vec<(&str, Option<usize>)> = details.iter().map(|info| (info.name.as_str(), info.turns)).collect();
+        assert_eq!(found, vec![("broken", None), ("feature-a", Some(1))]);
+        assert!(details[1].modified.is_some());
+
+        assert!(delete_session(dir, "feature-a").unwrap());
+        assert!(!delete_session(dir, "feature-a").unwrap());
+        assert!(delete_session(dir, "../config").is_err());
+        assert_eq!(list_sessions(dir).unwrap(), vec!["broken"]);
+    }
+
+    #[test]
+    fn test_tabs_keep_histories_apart() {
+        let mut tabs = Tabs::new(vec![message("user", "main question")]);
+        assert_eq!(tabs.active_name(), MAIN_TAB);
+
+        assert!(tabs.open("bugfix"));
+        assert_eq!(tabs.active_name(), "bugfix");
+        assert!(tabs.active().is_empty());
+        tabs.active().push(message("user", "why does it crash?"));
+        tabs.active().push(message("assistant", "null pointer"));
+
+        assert!(tabs.switch(MAIN_TAB));
+        assert_eq!(tabs.active().len(), 1);
+        assert_eq!(tabs.active()[0].content, "main question");
+        tabs.active().clear();
+
+        assert!(tabs.switch("bugfix"));
+        assert_eq!(tabs.active().len(), 2);
+        assert!(!tabs.open("bugfix"));
+        assert!(!tabs.switch("missing"));
+        assert_eq!(tabs.active_name(), "bugfix");
+        assert_eq!(tabs.list(), vec![("bugfix", 2), (MAIN_TAB, 0)]);
+    }
+
+    #[test]
+    fn test_save_and_load_tabs() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("tabs.json");
+
+        let mut tabs = Tabs::new(vec![message("user", "main")]);
+        tabs.open("docs");
+        tabs.active().push(message("user", "write the README"));
+        save_tabs(&path, &tabs, 1_000_000).unwrap();
+
+        let loaded = load_tabs(&path).unwrap();
+        assert_eq!(loaded.keys().collect::<Vec<_>>(), vec!["docs"]);
+        assert_eq!(loaded["docs"][0].content, "write the README");
+
+        // Only the main tab left: nothing to keep
+        save_tabs(&path, &Tabs::new(Vec::new()), 1_000_000).unwrap();
+        assert!(!path.exists());
+        assert!(load_tabs(&path).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_confirm_resume() {
+        assert!(confirm_resume(4, "\n".as_bytes(), std::io::sink()).unwrap());
+        assert!(confirm_resume(4, "y\n".as_bytes(), std::io::sink()).unwrap());
+        assert!(!confirm_resume(4, " No \n".as_bytes(), std::io::sink()).unwrap());
+    }
+
+    #[test]
+    fn test_byte_cap_leaves_small_history_alone() {
+        let mut history = vec![message("user", "hi"), message("assistant", "hello")];
+        assert_eq!(enforce_byte_cap(&mut history, 1_000, true), 0);
+        assert_eq!(history.len(), 2);
+    }
+}
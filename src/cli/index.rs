@@ -0,0 +1,76 @@
+use anyhow::{Context, Result};
+use clap::Args;
+use std::path::PathBuf;
+
+use super::{context, quiet, spinner::Spinner, walk};
+use crate::{
+    ai::{EmbeddingConfig, EmbeddingService},
+    config::Config,
+    error::ConfigError,
+    indexer::{self, Index},
+};
+
+#[derive(Args, Debug)]
+pub struct IndexArgs {
+    /// Directory to index (defaults to the project root)
+    pub dir: Option<PathBuf>,
+
+    /// Include files that .gitignore, hidden-file and build-output rules would leave out
+    #[arg(long)]
+    pub no_ignore: bool,
+}
+
+/// The embeddings settings, or an error naming what `command` needs when
+/// there are none.
+pub fn embedding_config(config: &Config, command: &str) -> Result<EmbeddingConfig, ConfigError> {
+    config.embeddings.clone().ok_or_else(|| {
+        ConfigError::Missing(format!(
+            "embeddings; set embeddings.provider, embeddings.model and embeddings.api_key to use {}",
+            command
+        ))
+    })
+}
+
+pub async fn execute(args: IndexArgs) -> Result<()> {
+    let config = Config::load()?;
+    let embeddings = embedding_config(&config, "index")?;
+    let root = match args.dir {
+        Some(dir) => dir,
+        None => config.project_root()?,
+    };
+    let root = std::path::absolute(&root).with_context(|| format!("Failed to resolve {:?}", root))?;
+    if !root.is_dir() {
+        anyhow::bail!("{} is not a directory", root.display());
+    }
+
+    let mut files = Vec::new();
+    for path in walk::files(&root, !args.no_ignore) {
+        let relative = path.strip_prefix(&root).unwrap_or(&path).display().to_string();
+        match context::read_text(&path, &config) {
+            Ok(content) => files.push((relative, content)),
+            Err(e) if !quiet::enabled() => eprintln!("Warning: skipping {}: {:#}", relative, e),
+            Err(_) => {}
+        }
+    }
+
+    let index_path = indexer::index_path(&root).context("Could not determine the config directory")?;
+    let mut index = Index::load(&index_path)?;
+    let service = EmbeddingService::new(embeddings)?;
+    let result = {
+        let _spinner = Spinner::start(&format!("Indexing {} files...", files.len()));
+        index.update(&service, files).await
+    };
+    // Keep what was embedded before a failure, so the next run picks up from there
+    index.save(&index_path)?;
+    let stats = result?;
+
+    println!(
+        "Embedded {} changed file(s) ({} chunks); {} unchanged, {} removed. Index: {}",
+        stats.embedded,
+        stats.chunks,
+        stats.unchanged,
+        stats.removed,
+        index_path.display()
+    );
+    Ok(())
+}
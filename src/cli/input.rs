@@ -0,0 +1,268 @@
+use anyhow::{Context, Result};
+use rustyline::error::ReadlineError;
+use rustyline::DefaultEditor;
+use std::io::{self, BufRead, Write};
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
+
+/// What the user did at the prompt.
+pub enum Input {
+    /// A line was entered
+    Line(String),
+    /// Ctrl-C: the line being typed was abandoned
+    Interrupted,
+    /// Ctrl-D or end of piped input
+    Eof,
+}
+
+/// Where lines typed at the interactive prompt are remembered between sessions.
+fn input_history_path() -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| dir.join("monk-manager").join("input_history.txt"))
+}
+
+/// An editable prompt with arrow-key history and the usual readline
+/// shortcuts (Ctrl-A, Ctrl-E, Ctrl-R, ...). History persists across sessions.
+pub struct LineEditor {
+    editor: DefaultEditor,
+    history_path: Option<PathBuf>,
+}
+
+impl LineEditor {
+    pub fn new() -> Result<Self> {
+        let mut editor = DefaultEditor::new().context("Failed to set up the input prompt")?;
+        let history_path = input_history_path();
+        if let Some(path) = history_path.as_ref().filter(|path| path.exists()) {
+            // A damaged history file only costs the old entries
+            let _ = editor.load_history(path);
+        }
+        Ok(Self { editor, history_path })
+    }
+
+    /// Shows `prompt` and reads one line, adding non-empty lines to history.
+    pub fn read(&mut self, prompt: &str) -> Result<Input> {
+        match self.editor.readline(prompt) {
+            Ok(line) => {
+                if !line.trim().is_empty() {
+                    let _ = self.editor.add_history_entry(line.as_str());
+                }
+                Ok(Input::Line(line))
+            }
+            Err(ReadlineError::Interrupted) => Ok(Input::Interrupted),
+            Err(ReadlineError::Eof) => Ok(Input::Eof),
+            Err(e) => Err(e).context("Failed to read input"),
+        }
+    }
+
+    /// Writes the input history for the next session.
+    pub fn save_history(&mut self) -> Result<()> {
+        let Some(path) = &self.history_path else {
+            return Ok(());
+        };
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create directory: {:?}", parent))?;
+        }
+        self.editor
+            .save_history(path)
+            .with_context(|| format!("Failed to save input history: {:?}", path))
+    }
+}
+
+/// Line that ends a `/paste` message.
+const PASTE_SENTINEL: &str = "EOF";
+
+/// Collects the lines of one multi-line message: everything between `/paste`
+/// and `EOF`, or a message that opens a code fence, up to the line closing it.
+pub struct MultiLine {
+    lines: Vec<String>,
+    paste: bool,
+}
+
+impl MultiLine {
+    /// Starts a multi-line message if `first` calls for one.
+    pub fn start(first: &str) -> Option<Self> {
+        let first = first.trim_end_matches(['\r', '\n']);
+        if first.trim() == "/paste" {
+            return Some(Self::paste());
+        }
+        let lines = vec![first.to_string()];
+        has_open_fence(&lines).then_some(Self { lines, paste: false })
+    }
+
+    /// A message pasted after `/paste`, which ends at a line holding only EOF.
+    pub fn paste() -> Self {
+        Self {
+            lines: Vec::new(),
+            paste: true,
+        }
+    }
+
+    /// Whether this message was started with `/paste`.
+    pub fn is_paste(&self) -> bool {
+        self.paste
+    }
+
+    /// Adds the next line, returning the whole message once it is complete.
+    pub fn push(&mut self, line: &str) -> Option<String> {
+        let line = line.trim_end_matches(['\r', '\n']);
+        if self.paste && line.trim() == PASTE_SENTINEL {
+            return Some(self.lines.join("\n"));
+        }
+        self.lines.push(line.to_string());
+        if !self.paste && !has_open_fence(&self.lines) {
+            return Some(self.lines.join("\n"));
+        }
+        None
+    }
+
+    /// The message so far, for when input ends before it is complete.
+    pub fn finish(self) -> String {
+        self.lines.join("\n")
+    }
+}
+
+/// Whether the lines leave a ``` code fence open.
+fn has_open_fence(lines: &[String]) -> bool {
+    lines.iter().map(|line| line.matches("```").count()).sum::<usize>() % 2 == 1
+}
+
+/// Reads lines from stdin without any editing support. Used when the prompt
+/// must be abandonable, since a line editor can't be interrupted without
+/// leaving the terminal in raw mode. A background thread does the blocking
+/// reads, so a prompt given up on doesn't lose the line typed next.
+pub struct PlainInput {
+    lines: mpsc::UnboundedReceiver<io::Result<String>>,
+}
+
+impl PlainInput {
+    pub fn new() -> Self {
+        Self::from_reader(io::BufReader::new(io::stdin()))
+    }
+
+    fn from_reader(mut reader: impl BufRead + Send + 'static) -> Self {
+        let (sender, lines) = mpsc::unbounded_channel();
+        // The thread is left behind rather than joined, so it can't keep the
+        // process alive
+        std::thread::spawn(move || loop {
+            let mut line = String::new();
+            let read = match reader.read_line(&mut line) {
+                Ok(0) => break,
+                Ok(_) => Ok(line),
+                Err(e) => Err(e),
+            };
+            let failed = read.is_err();
+            if sender.send(read).is_err() || failed {
+                break;
+            }
+        });
+        Self { lines }
+    }
+
+    /// Shows `prompt` and waits for a line, Ctrl-C or end of input. Returns
+    /// `None` if `idle` passes first.
+    pub async fn read(&mut self, prompt: &str, idle: Option<Duration>) -> Result<Option<Input>> {
+        print!("{}", prompt);
+        io::stdout().flush()?;
+
+        let idle_timer = async {
+            match idle {
+                Some(idle) => tokio::time::sleep(idle).await,
+                None => std::future::pending().await,
+            }
+        };
+        tokio::select! {
+            line = self.lines.recv() => match line {
+                Some(line) => Ok(Some(Input::Line(line.context("Failed to read input")?))),
+                None => Ok(Some(Input::Eof)),
+            },
+            _ = tokio::signal::ctrl_c() => {
+                println!();
+                Ok(Some(Input::Interrupted))
+            }
+            _ = idle_timer => Ok(None),
+        }
+    }
+}
+
+/// How soon a second Ctrl-C has to follow the first to end the session.
+const EXIT_WINDOW: Duration = Duration::from_secs(2);
+
+/// Tells a lone Ctrl-C from two in quick succession.
+#[derive(Default)]
+pub struct Interrupts {
+    last: Option<Instant>,
+}
+
+impl Interrupts {
+    /// Records a Ctrl-C at `now`, returning whether it closely follows the
+    /// previous one.
+    pub fn press(&mut self, now: Instant) -> bool {
+        let quick = self.last.is_some_and(|last| now.duration_since(last) <= EXIT_WINDOW);
+        self.last = Some(now);
+        quick
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_multi_line_paste() {
+        let mut paste = MultiLine::start("/paste\n").unwrap();
+        assert!(paste.push("fn main() {\n").is_none());
+        assert!(paste.push("    println!(\"hi\");").is_none());
+        assert!(paste.push("}").is_none());
+        assert_eq!(paste.push("EOF\n").as_deref(), Some("fn main() {\n    println!(\"hi\");\n}"));
+
+        assert!(MultiLine::start("what does this do?").is_none());
+    }
+
+    #[test]
+    fn test_multi_line_open_fence() {
+        let mut fenced = MultiLine::start("explain this: ```python").unwrap();
+        assert!(fenced.push("def f():").is_none());
+        assert!(fenced.push("    return 1").is_none());
+        assert_eq!(
+            fenced.push("```").as_deref(),
+            Some("explain this: ```python\ndef f():\n    return 1\n```")
+        );
+
+        // Input ending early keeps what was typed
+        let mut cut_short = MultiLine::start("```").unwrap();
+        cut_short.push("x = 1");
+        assert_eq!(cut_short.finish(), "```\nx = 1");
+    }
+
+    #[tokio::test]
+    async fn test_idle_timeout_gives_up_without_input() {
+        // A reader that never gets any input
+        struct Silent;
+        impl io::Read for Silent {
+            fn read(&mut self, _: &mut [u8]) -> io::Result<usize> {
+                loop {
+                    std::thread::park();
+                }
+            }
+        }
+        let mut silent = PlainInput::from_reader(io::BufReader::new(Silent));
+        let started = Instant::now();
+        assert!(silent.read("", Some(Duration::from_millis(50))).await.unwrap().is_none());
+        assert!(started.elapsed() >= Duration::from_millis(50));
+
+        let mut piped = PlainInput::from_reader(io::Cursor::new("exit\n"));
+        let read = piped.read("", Some(Duration::from_secs(5))).await.unwrap();
+        assert!(matches!(read, Some(Input::Line(line)) if line == "exit\n"));
+        assert!(matches!(piped.read("", None).await.unwrap(), Some(Input::Eof)));
+    }
+
+    #[test]
+    fn test_second_quick_interrupt() {
+        let mut interrupts = Interrupts::default();
+        let start = Instant::now();
+        assert!(!interrupts.press(start));
+        assert!(interrupts.press(start + Duration::from_secs(1)));
+        assert!(!interrupts.press(start + Duration::from_secs(10)));
+    }
+}
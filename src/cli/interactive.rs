@@ -1,37 +1,114 @@
 use anyhow::Result;
-use std::io::{self, Write};
-use std::path::PathBuf;
-use crate::ai::{AIService, Message, ModelConfig};
+use std::io::{self, IsTerminal, Write};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tracing::debug;
+
+use crate::ai::{self, AIService, Message};
+use crate::cli::color::{self, cprint, cprintln};
+use crate::cli::commands::{self, Outcome, Session, SYSTEM_PROMPT_TOKENS};
+use crate::cli::{context, highlight, quiet, spinner::Spinner, history, render, truncation, input::{Input, Interrupts, LineEditor, MultiLine, PlainInput}};
 use crate::config::Config;
 
 /// Runs the interactive CLI session.
 /// This is the primary interaction mode for monk-manager.
-pub async fn run_interactive_session() -> Result<()> {
-    // Get the current directory as the project root
-    let project_root = std::env::current_dir()?;
-    
+/// Unless `fresh` is set, offers to resume the conversation saved when the
+/// last session ended. With `timing`, each answer is followed by how long
+/// its request took.
+pub async fn run_interactive_session(fresh: bool, timing: bool) -> Result<()> {
     // Load configuration
-    let config = Config::load()?;
-    
-    // Initialize AI service
-    let ai_service = initialize_ai_service(&config)?;
-    
+    let mut session = start_session(Config::load()?)?;
+
     // Display welcome message with project path
-    println!("\x1B[32mWelcome to monk-manager interactive mode!\x1B[0m");
-    println!("\x1B[32mProject directory: {}\x1B[0m", project_root.display());
-    println!("\x1B[32mType your message and press Enter to send.\x1B[0m");
-    println!("\x1B[32mType '/help' for assistance or '/exit' to quit.\x1B[0m\n");
+    if !quiet::enabled() {
+        cprintln!("\x1B[32mWelcome to monk-manager interactive mode!\x1B[0m");
+        cprintln!("\x1B[32mProject directory: {}\x1B[0m", session.project_root.display());
+        cprintln!("\x1B[32mType your message and press Enter to send.\x1B[0m");
+        cprintln!("\x1B[32mType '/help' for assistance or '/exit' to quit.\x1B[0m\n");
+    }
+
+    // Pick up where the last session left off, unless asked not to
+    let history_path = history::saved_history_path();
+    let tabs_path = history::saved_tabs_path();
+    let tabs = &mut session.tabs;
+    if let (Some(path), false) = (&history_path, fresh) {
+        let saved = history::load_history(path).and_then(|main| {
+            let others = match &tabs_path {
+                Some(tabs_path) => history::load_tabs(tabs_path)?,
+                None => Default::default(),
+            };
+            Ok((main, others))
+        });
+        match saved {
+            Ok((main, others)) => {
+                let messages = main.len() + others.values().map(Vec::len).sum::<usize>();
+                if messages > 0 && history::confirm_resume(messages, io::stdin().lock(), io::stdout())? {
+                    *tabs = history::Tabs::new(main);
+                    for (name, conversation) in others {
+                        tabs.insert(&name, conversation);
+                    }
+                    cprintln!("\x1B[32mResumed {} messages.\x1B[0m\n", messages);
+                }
+            }
+            Err(e) => cprintln!("\x1B[33mCould not load the saved conversation: {:#}\x1B[0m\n", e),
+        }
+    }
 
     // Main interaction loop
-    let mut conversation_history = Vec::new();
+    // A line editor can't be abandoned mid-read, so idle timeouts use plain input
+    let idle_timeout = Some(Duration::from_secs(session.config.commands.idle_timeout_secs)).filter(|idle| !idle.is_zero());
+    let mut reader = match idle_timeout {
+        Some(_) => Reader::Plain(PlainInput::new()),
+        None => Reader::Editor(Box::new(LineEditor::new()?)),
+    };
+    let mut interrupts = Interrupts::default();
+    let thinking_style = color::named(&session.config.commands.thinking_color).unwrap_or_else(|| {
+        cprintln!("\x1B[33mUnknown thinking_color '{}'; using yellow.\x1B[0m", session.config.commands.thinking_color);
+        "\x1B[33m"
+    });
+    let idle_secs = session.config.commands.idle_timeout_secs;
     
     loop {
-        print!(">> ");
-        io::stdout().flush()?;
+        // Name the active tab once there is more than one
+        let tabs = &session.tabs;
+        let prompt = if tabs.count() > 1 {
+            format!("[{}] >> ", tabs.active_name())
+        } else {
+            ">> ".to_string()
+        };
         
-        // Read user input
-        let mut input = String::new();
-        io::stdin().read_line(&mut input)?;
+        // Read user input, treating end of input like /exit
+        let Some(read) = reader.read(idle_timeout, prompt).await? else {
+            cprintln!("\n\x1B[33mNo input for {} seconds; ending the session.\x1B[0m", idle_secs);
+            break;
+        };
+        let mut input = match read {
+            Input::Line(line) => line,
+            Input::Interrupted => {
+                if interrupts.press(Instant::now()) {
+                    cprintln!("\x1B[32mExiting monk-manager.\x1B[0m");
+                    break;
+                }
+                cprintln!("\x1B[33m(Type /exit to leave, or press Ctrl-C again)\x1B[0m");
+                continue;
+            }
+            Input::Eof => {
+                cprintln!("\n\x1B[32mExiting monk-manager.\x1B[0m");
+                break;
+            }
+        };
+
+        // An unclosed code fence keeps reading until the message is complete
+        if let Some(multi_line) = MultiLine::start(&input).filter(|multi_line| !multi_line.is_paste()) {
+            input = match read_rest(&mut reader, idle_timeout, multi_line).await? {
+                Rest::Message(text) => text,
+                Rest::Interrupted => continue,
+                Rest::TimedOut => {
+                    cprintln!("\n\x1B[33mNo input for {} seconds; ending the session.\x1B[0m", idle_secs);
+                    break;
+                },
+            };
+        }
         
         let input = input.trim();
         
@@ -40,90 +117,291 @@ pub async fn run_interactive_session() -> Result<()> {
             continue;
         }
         
-        // Handle special commands
-        match input {
-            "/exit" | "/quit" => {
-                println!("\n\x1B[32mExiting monk-manager.\x1B[0m");
-                break;
-            },
-            "/help" => {
-                display_help();
-                continue;
+        // Slash commands run from the registry; anything else goes to the model
+        let (name, argument) = match input.split_once(char::is_whitespace) {
+            Some((name, argument)) => (name, argument.trim()),
+            None => (input, ""),
+        };
+        // What to go back to if the request this input makes is cancelled
+        let before_request = session.tabs.active().clone();
+        let outcome = match commands::find(name) {
+            Some(command) => command.run(&mut session, argument).await?,
+            None => Outcome::Ask(input.to_string()),
+        };
+        // What goes to the model as the user's message, unless the last one is being asked again
+        let message = match outcome {
+            Outcome::Done => continue,
+            Outcome::Exit => break,
+            Outcome::Retry => None,
+            Outcome::Ask(message) => Some(message),
+            Outcome::Paste => {
+                cprintln!("\x1B[32mPaste your text, then type EOF on a line of its own.\x1B[0m");
+                match read_rest(&mut reader, idle_timeout, MultiLine::paste()).await? {
+                    Rest::Message(text) if text.trim().is_empty() => continue,
+                    Rest::Message(text) => Some(text.trim().to_string()),
+                    Rest::Interrupted => continue,
+                    Rest::TimedOut => {
+                        cprintln!("\n\x1B[33mNo input for {} seconds; ending the session.\x1B[0m", idle_secs);
+                        break;
+                    },
+                }
             },
-            _ => {}
-        }
+        };
+        let Session { config, project_context, ai_service, tabs, .. } = &mut session;
+        let conversation_history = tabs.active();
         
-        // Add user message to history
-        conversation_history.push(Message {
-            role: "user".to_string(),
-            content: input.to_string(),
-        });
+        // Add user message to history, unless the last one is being asked again
+        if let Some(message) = message {
+            conversation_history.push(Message {
+                role: "user".to_string(),
+                content: message,
+            });
+            history::enforce_byte_cap(
+                conversation_history,
+                config.commands.max_history_bytes,
+                config.commands.truncation_notices,
+            );
+        }
         
-        // Display "thinking" indicator
-        print!("\x1B[33mThinking...\x1B[0m");
-        io::stdout().flush()?;
+        // Leave out the oldest turns that don't fit the token budget; the
+        // system prompt and project context always go
+        let reserved = SYSTEM_PROMPT_TOKENS + history::estimate_tokens(project_context);
+        let start = history::token_budget_start(conversation_history, reserved, config.commands.max_context_tokens);
+        if start > 0 {
+            cprintln!("\x1B[33m({} older messages left out to stay within max_context_tokens)\x1B[0m", start);
+        }
+
+        // Turns must alternate for the API; fix up a copy so the stored
+        // conversation keeps what was actually said
+        let mut request = conversation_history[start..].to_vec();
+        history::repair_alternation(&mut request, config.commands.role_repair);
+        context::redact_messages(&mut request, config);
+        // Tell the model, too, that it isn't seeing the whole conversation
+        if let (true, true, Some(first)) = (config.commands.truncation_notices, start > 0, request.first_mut()) {
+            // Including any the history byte cap had already noted as evicted
+            let noted: usize = conversation_history[..start]
+                .iter()
+                .map(|message| truncation::split_omitted_messages_note(&message.content).0)
+                .sum();
+            first.content = truncation::prepend_omitted_messages_note(&first.content, start + noted);
+        }
         
-        // Get project context
-        let project_context = format!("Current directory: {}", project_root.display());
+        // Animate a "thinking" indicator until the answer starts arriving
+        let mut spinner = Spinner::start_styled(&config.commands.thinking_message, thinking_style);
         
-        // Get AI response
-        match ai_service.chat(&conversation_history, Some(&project_context)).await {
-            Ok(response) => {
-                // Clear the "thinking" indicator
-                print!("\r\x1B[K");
-                
-                // Display AI response
-                println!("\x1B[32m{}\x1B[0m\n", response);
+        // Stream the AI response, highlighting any code it contains; providers
+        // without streaming hand it over in one piece. Ctrl-C abandons it.
+        let color = color::enabled();
+        let mut highlighter = highlight::StreamHighlighter::new("\x1B[32m").wrapped_to(render::terminal_width());
+        let mut started = false;
+        // Printing and highlighting happen between deltas; that time isn't the request's
+        let mut rendering = Duration::ZERO;
+        let request_started = Instant::now();
+        let result = {
+            let mut on_delta = |delta: &str| {
+                let rendering_started = Instant::now();
+                if !started {
+                    spinner.stop();
+                    started = true;
+                }
+                if color {
+                    print!("{}", highlighter.push(delta));
+                } else {
+                    print!("{}", delta);
+                }
+                let _ = io::stdout().flush();
+                rendering += rendering_started.elapsed();
+            };
+            tokio::select! {
+                result = ai_service.chat_stream(&request, Some(project_context.as_str()), &mut on_delta) => Some(result),
+                _ = tokio::signal::ctrl_c() => None,
+            }
+        };
+        let elapsed = request_started.elapsed().saturating_sub(rendering);
+        if started {
+            println!("{}", highlighter.finish());
+        }
+
+        // Leave no unanswered question behind to be merged into the next one
+        spinner.stop();
+        let Some(result) = result else {
+            interrupts.press(Instant::now());
+            *conversation_history = before_request;
+            cprintln!("\x1B[33mRequest cancelled; the conversation is as it was before you sent it.\x1B[0m\n");
+            continue;
+        };
+
+        match result {
+            Ok(completion) => {
+                if timing {
+                    println!("{}", color::dim(&render::elapsed_note(elapsed)));
+                }
+                println!();
                 
                 // Add AI response to history
                 conversation_history.push(Message {
                     role: "assistant".to_string(),
-                    content: response,
+                    content: completion.text,
                 });
             },
             Err(e) => {
-                // Clear the "thinking" indicator
-                print!("\r\x1B[K");
-                
-                println!("\x1B[31mError getting AI response: {}\x1B[0m", e);
-                println!("\x1B[31mPlease check your API key and internet connection.\x1B[0m");
-                println!("\x1B[31mYou can continue chatting, but responses may not work.\x1B[0m\n");
+                // A refusal isn't a fault with the setup, so don't suggest one
+                if matches!(e.downcast_ref::<ai::AIError>(), Some(ai::AIError::Refused)) {
+                    cprintln!("\x1B[33m{}. You can rephrase it or ask something else.\x1B[0m\n", e);
+                } else {
+                    cprintln!("\x1B[31mError getting AI response: {}\x1B[0m", e);
+                    cprintln!("\x1B[31mPlease check your API key and internet connection.\x1B[0m");
+                    cprintln!("\x1B[31mYou can continue chatting, but responses may not work.\x1B[0m\n");
+                }
             }
         }
     }
+
+    if let Reader::Editor(editor) = &mut reader {
+        if let Err(e) = editor.save_history() {
+            cprintln!("\x1B[33mCould not save input history: {:#}\x1B[0m", e);
+        }
+    }
+
+    // Keep the conversation, and any other tabs, for next time
+    let Session { config, mut tabs, .. } = session;
+    if config.commands.autosave {
+        let max_bytes = config.commands.max_history_bytes;
+        tabs.switch(history::MAIN_TAB);
+        let saved = history_path
+            .iter()
+            .try_for_each(|path| history::save_history(path, tabs.active(), max_bytes))
+            .and_then(|_| tabs_path.iter().try_for_each(|path| history::save_tabs(path, &tabs, max_bytes)));
+        if let Err(e) = saved {
+            cprintln!("\x1B[33mCould not save the conversation: {:#}\x1B[0m", e);
+        }
+    }
     
     Ok(())
 }
 
-// Display help information
-fn display_help() {
-    println!("\n\x1B[32mAvailable commands:\x1B[0m");
-    println!("  \x1B[32m/help\x1B[0m - Display this help message");
-    println!("  \x1B[32m/exit\x1B[0m or \x1B[32m/quit\x1B[0m - Exit the session\n");
+/// Sets up a session on `config`: the project directory and its
+/// description, and the model client.
+pub fn start_session(config: Config) -> Result<Session> {
+    // Anchor context and relative paths at repository_home, or else the current directory
+    let project_root = config.project_root()?;
+
+    // Describe the project once; it goes with every request
+    let project_context = context::project_context(&project_root, &config);
+
+    let ai_service = initialize_ai_service(&config)?;
+    Ok(Session {
+        config,
+        project_root,
+        project_context,
+        ai_service,
+        tabs: history::Tabs::new(Vec::new()),
+        checkpoints: history::Checkpoints::default(),
+    })
 }
 
-// Initialize the AI service from config
-fn initialize_ai_service(_config: &Config) -> Result<AIService> {
-    // Use API key from environment variable
-    let api_key = std::env::var("ANTHROPIC_API_KEY").unwrap_or_else(|_| {
-        println!("\x1B[33mWARNING: ANTHROPIC_API_KEY environment variable not found, using demo key\x1B[0m");
-        "demo-api-key".to_string()
-    });
-    
-    if api_key == "demo-api-key" {
-        println!("\x1B[31mWARNING: Using demo API key. This won't work for real requests.\x1B[0m");
-        println!("\x1B[31mPlease set the ANTHROPIC_API_KEY environment variable to use the service.\x1B[0m");
+/// Where interactive input comes from.
+enum Reader {
+    Editor(Box<LineEditor>),
+    /// Plain input, for when an idle timeout has to be able to cut a read short
+    Plain(PlainInput),
+}
+
+impl Reader {
+    /// Reads one line. Returns `None` if the idle timeout ran out first.
+    async fn read(&mut self, idle: Option<Duration>, prompt: String) -> Result<Option<Input>> {
+        match self {
+            Reader::Editor(editor) => editor.read(&prompt).map(Some),
+            Reader::Plain(plain) => plain.read(&prompt, idle).await,
+        }
     }
-    
-    // Default to Claude model if no configuration exists
-    let model_config = ModelConfig {
-        provider: "anthropic".to_string(),
-        model_name: "claude-3-haiku-20240307".to_string(),
-        api_key,
-        temperature: 0.7,
-        max_tokens: 4000,
-        api_base_url: None,
-    };
-    
-    AIService::new(model_config)
-} 
\ No newline at end of file
+}
+
+/// How reading the rest of a multi-line message ended.
+enum Rest {
+    Message(String),
+    /// Ctrl-C; the message is dropped
+    Interrupted,
+    /// The idle timeout ran out
+    TimedOut,
+}
+
+/// Reads lines into `multi_line` until the message is complete. End of
+/// input completes it with what there is.
+async fn read_rest(reader: &mut Reader, idle: Option<Duration>, mut multi_line: MultiLine) -> Result<Rest> {
+    loop {
+        let Some(read) = reader.read(idle, ".. ".to_string()).await? else {
+            return Ok(Rest::TimedOut);
+        };
+        match read {
+            Input::Line(line) => {
+                if let Some(text) = multi_line.push(&line) {
+                    return Ok(Rest::Message(text));
+                }
+            }
+            Input::Interrupted => return Ok(Rest::Interrupted),
+            Input::Eof => return Ok(Rest::Message(multi_line.finish())),
+        }
+    }
+}
+
+/// Retries failed requests up to `commands.max_retries` times, telling the
+/// user about each attempt when they're watching a terminal.
+pub fn retry_policy(config: &Config) -> ai::RetryPolicy {
+    let show = io::stdout().is_terminal() && !quiet::enabled();
+    ai::RetryPolicy {
+        max_retries: config.commands.max_retries,
+        on_retry: Some(Arc::new(move |notice: &ai::RetryNotice| {
+            if show {
+                // The spinner redraws itself on the next line
+                cprint!("\r\x1B[K\x1B[33m{}\x1B[0m\n", notice);
+                let _ = io::stdout().flush();
+            } else {
+                debug!("{}", notice);
+            }
+        })),
+    }
+}
+
+/// Builds the model client from `ai.*`, as it was loaded with environment
+/// overrides applied.
+fn initialize_ai_service(config: &Config) -> Result<AIService> {
+    if config.ai.api_key.is_empty() && ai::needs_api_key(&config.ai.provider) && !quiet::enabled() {
+        cprintln!("\x1B[31mWARNING: No API key is set, so requests will be refused.\x1B[0m");
+        cprintln!("\x1B[31mSet ai.api_key in the config or the ANTHROPIC_API_KEY environment variable.\x1B[0m");
+    }
+    Ok(AIService::new(config.ai.clone())?.with_retries(retry_policy(config)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_session_runs_on_the_configured_provider() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let config: Config = serde_yaml::from_str(&format!(
+            "ai:\n  provider: mock\n  model_name: mock\n  api_key: ''\n  temperature: 0.2\n  max_tokens: 100\n\
+             logging:\n  level: info\n  format: pretty\n  output: stderr\n\
+             commands:\n  default_language: rust\n  default_format: markdown\n  timeout: 30\n  \
+             explain:\n    max_context_lines: 10\n    language_detection: true\n\
+             security:\n  secrets_file: null\nrepository_home: {:?}\n",
+            temp_dir.path().display().to_string()
+        ))
+        .unwrap();
+
+        let session = start_session(config).unwrap();
+        assert_eq!(session.project_root, temp_dir.path());
+        let model = session.ai_service.config();
+        assert_eq!((model.provider.as_str(), model.model_name.as_str()), ("mock", "mock"));
+        assert_eq!(model.temperature, 0.2);
+
+        // A message goes to the mock, not over the network
+        let messages = [Message {
+            role: "user".to_string(),
+            content: "hi".to_string(),
+        }];
+        let answer = session.ai_service.chat(&messages, Some(&session.project_context)).await.unwrap();
+        assert!(!answer.is_empty());
+    }
+}
@@ -1,29 +1,185 @@
 use anyhow::Result;
-use clap::{Parser, Subcommand};
+use clap::{ArgAction, Parser, Subcommand};
+use std::path::PathBuf;
 
+use crate::{ai, config::Config};
+
+pub mod ask;
+pub mod color;
+pub mod commands;
+pub mod completions;
+pub mod context;
+pub mod diff;
+pub mod doctor;
 pub mod explain;
+pub mod highlight;
+pub mod history;
+pub mod index;
+pub mod input;
 pub mod interactive;
+pub mod pager;
+pub mod quiet;
+pub mod raw;
+pub mod render;
+pub mod search;
+pub mod sessions;
+pub mod spinner;
+pub mod stream;
+pub mod summarize;
+pub mod transcript;
+pub mod truncation;
+pub mod update;
+pub mod version;
+pub mod walk;
 
+pub use ask::AskArgs;
+pub use color::ColorChoice;
+pub use completions::CompletionsArgs;
+pub use diff::DiffArgs;
+pub use doctor::DoctorArgs;
 pub use explain::ExplainArgs;
+pub use index::IndexArgs;
+pub use raw::RawArgs;
+pub use search::SearchArgs;
+pub use sessions::SessionsArgs;
+pub use summarize::SummarizeArgs;
+pub use update::UpdateArgs;
 
 #[derive(Parser)]
 #[command(author, version, about, long_about = None)]
 pub struct Cli {
     #[command(subcommand)]
     pub command: Option<Commands>,
+
+    /// Print version and build information as JSON
+    #[arg(long, hide = true)]
+    pub version_json: bool,
+
+    /// Start interactive mode without offering to resume the last conversation
+    #[arg(long)]
+    pub fresh: bool,
+
+    /// In interactive mode, show how long each answer took
+    #[arg(long)]
+    pub timing: bool,
+
+    /// When to color output
+    #[arg(long, global = true, value_enum, default_value_t = ColorChoice::Auto)]
+    pub color: ColorChoice,
+
+    /// Log more: -v for debug, -vv for trace
+    #[arg(short, long, global = true, action = ArgAction::Count)]
+    pub verbose: u8,
+
+    /// Print only answers and errors: no banners, progress or advisory warnings
+    #[arg(short, long, global = true)]
+    pub quiet: bool,
+
+    /// Read configuration from this file instead of searching for one
+    #[arg(long, global = true, value_name = "PATH")]
+    pub config: Option<PathBuf>,
+
+    /// Never color output; the same as --color never
+    #[arg(long, global = true)]
+    pub no_color: bool,
+
+    /// Refuse to use any provider that sends requests over the network
+    #[arg(long, global = true)]
+    pub offline: bool,
+
+    /// Print long output straight to the terminal instead of through $PAGER
+    #[arg(long, global = true)]
+    pub no_pager: bool,
+}
+
+impl Cli {
+    /// Applies the options that hold for the whole run: color, quiet mode,
+    /// the pager, offline mode and the config file.
+    pub fn apply_global_options(&self) {
+        color::set_choice(self.color_choice());
+        quiet::set(self.quiet);
+        pager::set_disabled(self.no_pager);
+        ai::set_offline(self.offline);
+        if let Some(path) = self.config.clone() {
+            Config::use_path(path);
+        }
+    }
+
+    /// The color choice the flags add up to; `--no-color` wins.
+    pub fn color_choice(&self) -> ColorChoice {
+        if self.no_color {
+            ColorChoice::Never
+        } else {
+            self.color
+        }
+    }
 }
 
 #[derive(Subcommand)]
 pub enum Commands {
-    /// [Legacy] Explain code using AI - now redirects to interactive mode
+    /// Explain code using AI
     Explain(ExplainArgs),
+    /// Ask a single question and print the answer
+    Ask(AskArgs),
+    /// Explain the changes in a git diff
+    Diff(DiffArgs),
+    /// Summarize every file in a directory into an overview of the codebase
+    Summarize(SummarizeArgs),
+    /// Embed the project's source files into a local index for semantic search
+    Index(IndexArgs),
+    /// Find the indexed code closest in meaning to a query
+    Search(SearchArgs),
+    /// Print a shell completion script to stdout
+    Completions(CompletionsArgs),
+    /// List or delete the sessions saved from interactive mode
+    Sessions(SessionsArgs),
+    /// Print the version, config file, provider and model, for bug reports
+    Doctor(DoctorArgs),
+    /// Replace this binary with the latest release, if there is a newer one
+    Update(UpdateArgs),
+    /// Send a hand-written JSON request body to the provider and print the raw response
+    Raw(RawArgs),
+}
+
+/// Parses a `--temperature` value, which the API only accepts from 0.0 to 1.0.
+fn parse_temperature(value: &str) -> Result<f32, String> {
+    let temperature: f32 = value.parse().map_err(|_| format!("'{}' is not a number", value))?;
+    if !(0.0..=1.0).contains(&temperature) {
+        return Err(format!("temperature must be between 0.0 and 1.0, got {}", temperature));
+    }
+    Ok(temperature)
+}
+
+/// Parses a `--max-tokens` value, which has to leave room for some answer.
+fn parse_max_tokens(value: &str) -> Result<usize, String> {
+    match value.parse() {
+        Ok(0) => Err("max_tokens must be greater than 0".to_string()),
+        Ok(max_tokens) => Ok(max_tokens),
+        Err(_) => Err(format!("'{}' is not a whole number", value)),
+    }
 }
 
-// This function is no longer used since we always start interactive mode
-#[deprecated(note = "All commands now redirect to interactive mode")]
+/// Dispatches the parsed command line, starting interactive mode when no
+/// subcommand is given.
 pub async fn execute(cli: Cli) -> Result<()> {
-    // Always use interactive mode now
-    interactive::run_interactive_session().await
+    if cli.version_json {
+        return version::print_json();
+    }
+
+    match cli.command {
+        Some(Commands::Explain(args)) => explain::execute(args).await,
+        Some(Commands::Ask(args)) => ask::execute(args).await,
+        Some(Commands::Diff(args)) => diff::execute(args).await,
+        Some(Commands::Summarize(args)) => summarize::execute(args).await,
+        Some(Commands::Index(args)) => index::execute(args).await,
+        Some(Commands::Search(args)) => search::execute(args).await,
+        Some(Commands::Completions(args)) => completions::execute(args),
+        Some(Commands::Sessions(args)) => sessions::execute(args),
+        Some(Commands::Doctor(args)) => doctor::execute(args).await,
+        Some(Commands::Update(args)) => update::execute(args).await,
+        Some(Commands::Raw(args)) => raw::execute(args).await,
+        None => interactive::run_interactive_session(cli.fresh, cli.timing).await,
+    }
 }
 
 #[cfg(test)]
@@ -48,10 +204,110 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_cli_parse_ask_compare_models() {
+        let args = vec!["monk", "ask", "what is a closure?", "--compare-models", "a,b"];
+        let cli = Cli::parse_from(args);
+        if let Some(Commands::Ask(args)) = cli.command {
+            assert_eq!(args.prompt, "what is a closure?");
+            assert_eq!(args.compare_models, vec!["a".to_string(), "b".to_string()]);
+        } else {
+            panic!("Expected Ask command");
+        }
+    }
+
+    #[test]
+    fn test_cli_parse_detail() {
+        let cli = Cli::parse_from(["monk", "explain", "src/main.rs", "--detail", "detailed"]);
+        match cli.command {
+            Some(Commands::Explain(args)) => assert_eq!(args.detail, crate::ai::DetailLevel::Detailed),
+            _ => panic!("Expected Explain command"),
+        }
+
+        let error = Cli::try_parse_from(["monk", "ask", "hi", "--detail", "verbose"])
+            .err()
+            .unwrap()
+            .to_string();
+        assert!(error.contains("basic, medium, detailed"));
+    }
+
+    #[test]
+    fn test_cli_parse_temperature() {
+        match Cli::parse_from(["monk", "ask", "hi", "--temperature", "0.2"]).command {
+            Some(Commands::Ask(args)) => assert_eq!(args.temperature, Some(0.2)),
+            _ => panic!("Expected Ask command"),
+        }
+        assert!(Cli::try_parse_from(["monk", "diff", "--temperature", "1"]).is_ok());
+
+        let error = Cli::try_parse_from(["monk", "explain", "src/main.rs", "--temperature", "1.5"])
+            .err()
+            .unwrap()
+            .to_string();
+        assert!(error.contains("temperature must be between 0.0 and 1.0, got 1.5"), "{}", error);
+        assert!(Cli::try_parse_from(["monk", "ask", "hi", "--temperature", "warm"]).is_err());
+        assert!(Cli::try_parse_from(["monk", "ask", "hi", "--temperature", "0", "--deterministic"]).is_err());
+    }
+
+    #[test]
+    fn test_cli_parse_max_tokens() {
+        match Cli::parse_from(["monk", "explain", "src/main.rs", "--max-tokens", "200"]).command {
+            Some(Commands::Explain(args)) => assert_eq!(args.max_tokens, Some(200)),
+            _ => panic!("Expected Explain command"),
+        }
+
+        let error = Cli::try_parse_from(["monk", "ask", "hi", "--max-tokens", "0"])
+            .err()
+            .unwrap()
+            .to_string();
+        assert!(error.contains("max_tokens must be greater than 0"), "{}", error);
+        assert!(Cli::try_parse_from(["monk", "diff", "--max-tokens", "-5"]).is_err());
+    }
+
+    #[test]
+    fn test_cli_color_flags() {
+        assert_eq!(Cli::parse_from(["monk"]).color_choice(), ColorChoice::Auto);
+        assert_eq!(Cli::parse_from(["monk", "--color", "always"]).color_choice(), ColorChoice::Always);
+        assert_eq!(Cli::parse_from(["monk", "ask", "hi", "--no-color"]).color_choice(), ColorChoice::Never);
+        assert_eq!(Cli::parse_from(["monk", "--color", "always", "--no-color"]).color_choice(), ColorChoice::Never);
+    }
+
+    #[test]
+    fn test_cli_verbose_count() {
+        assert_eq!(Cli::parse_from(["monk"]).verbose, 0);
+        assert_eq!(Cli::parse_from(["monk", "-v", "ask", "hi"]).verbose, 1);
+        assert_eq!(Cli::parse_from(["monk", "ask", "hi", "-vv"]).verbose, 2);
+        assert!(Cli::parse_from(["monk", "--quiet", "ask", "hi"]).quiet);
+        assert!(Cli::parse_from(["monk", "explain", "big.rs", "--no-pager"]).no_pager);
+    }
+
+    #[test]
+    fn test_cli_parse_search_top_k() {
+        match Cli::parse_from(["monk", "search", "where is the config validated"]).command {
+            Some(Commands::Search(args)) => assert_eq!(args.top_k, crate::search::DEFAULT_TOP_K),
+            _ => panic!("Expected Search command"),
+        }
+        match Cli::parse_from(["monk", "search", "config", "-k", "3"]).command {
+            Some(Commands::Search(args)) => assert_eq!(args.top_k, 3),
+            _ => panic!("Expected Search command"),
+        }
+    }
+
+    #[test]
+    fn test_cli_parse_sessions() {
+        match Cli::parse_from(["monk", "sessions", "rm", "feature-a"]).command {
+            Some(Commands::Sessions(args)) => {
+                assert!(matches!(args.command, sessions::SessionsCommand::Rm { name } if name == "feature-a"))
+            }
+            _ => panic!("Expected Sessions command"),
+        }
+        assert!(Cli::try_parse_from(["monk", "sessions", "list"]).is_ok());
+        assert!(Cli::try_parse_from(["monk", "sessions", "rm"]).is_err());
+    }
+
     #[test]
     fn test_cli_no_command() {
         let args = vec!["monk"];
         let cli = Cli::parse_from(args);
-        assert!(matches!(cli.command, None));
+        assert!(cli.command.is_none());
     }
 } 
\ No newline at end of file
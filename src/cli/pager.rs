@@ -0,0 +1,92 @@
+use std::io::{self, IsTerminal, Write};
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use super::{color::strip_escapes, quiet};
+
+/// Pager used when `PAGER` isn't set; `-R` keeps the colors.
+const DEFAULT_PAGER: &str = "less -R";
+
+static DISABLED: AtomicBool = AtomicBool::new(false);
+
+/// Turns the pager off for the rest of the run, as set by `--no-pager`.
+pub fn set_disabled(disabled: bool) {
+    DISABLED.store(disabled, Ordering::Relaxed);
+}
+
+/// Prints `text` to stdout, through the pager when it won't fit on one
+/// screen of a terminal. If the pager can't be started, the text is printed
+/// as it is.
+pub fn print(text: &str) {
+    let size = terminal_size::terminal_size()
+        .map(|(terminal_size::Width(width), terminal_size::Height(height))| (width as usize, height as usize));
+    let paged = match size {
+        Some((width, height)) if enabled() && io::stdout().is_terminal() && overflows(text, width, height) => {
+            page(text).is_ok()
+        }
+        _ => false,
+    };
+    if !paged {
+        print!("{}", text);
+    }
+}
+
+/// Whether paging is allowed at all: not with `--no-pager` or `--quiet`.
+fn enabled() -> bool {
+    !DISABLED.load(Ordering::Relaxed) && !quiet::enabled()
+}
+
+/// Whether `text` takes more rows than a `width` by `height` screen has,
+/// counting lines the terminal wraps and leaving a row for the prompt.
+fn overflows(text: &str, width: usize, height: usize) -> bool {
+    let width = width.max(1);
+    let rows: usize = text
+        .lines()
+        .map(|line| strip_escapes(line, true).chars().count().div_ceil(width).max(1))
+        .sum();
+    rows >= height
+}
+
+/// The pager program and its arguments, from `PAGER` or the default.
+fn pager_command(configured: Option<&str>) -> Vec<String> {
+    let command = configured.filter(|command| !command.trim().is_empty()).unwrap_or(DEFAULT_PAGER);
+    command.split_whitespace().map(str::to_string).collect()
+}
+
+/// Runs the pager with `text` on its stdin and waits for the user to leave it.
+fn page(text: &str) -> io::Result<()> {
+    let configured = std::env::var("PAGER").ok();
+    let command = pager_command(configured.as_deref());
+    let mut child = Command::new(&command[0]).args(&command[1..]).stdin(Stdio::piped()).spawn()?;
+    if let Some(mut stdin) = child.stdin.take() {
+        // Quitting the pager before the end closes the pipe; that's not an error
+        match stdin.write_all(text.as_bytes()) {
+            Err(e) if e.kind() != io::ErrorKind::BrokenPipe => return Err(e),
+            _ => {}
+        }
+    }
+    child.wait()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_overflows_counts_wrapped_rows() {
+        assert!(!overflows("one\ntwo\n", 80, 24));
+        assert!(overflows(&"line\n".repeat(30), 80, 24));
+        // Two lines, but the long one wraps onto three rows
+        assert!(overflows(&format!("{}\nshort\n", "x".repeat(25)), 10, 4));
+        // Colors take no room
+        assert!(!overflows("\x1B[32mgreen\x1B[0m\n", 5, 2));
+    }
+
+    #[test]
+    fn test_pager_command() {
+        assert_eq!(pager_command(None), vec!["less", "-R"]);
+        assert_eq!(pager_command(Some(" ")), vec!["less", "-R"]);
+        assert_eq!(pager_command(Some("more -d")), vec!["more", "-d"]);
+    }
+}
@@ -0,0 +1,14 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static QUIET: AtomicBool = AtomicBool::new(false);
+
+/// Turns quiet mode on or off for the rest of the run, as set by `--quiet`.
+pub fn set(quiet: bool) {
+    QUIET.store(quiet, Ordering::Relaxed);
+}
+
+/// Whether to leave out everything but answers and errors: banners,
+/// progress and "thinking" indicators, and advisory warnings.
+pub fn enabled() -> bool {
+    QUIET.load(Ordering::Relaxed)
+}
@@ -0,0 +1,78 @@
+use anyhow::{Context, Result};
+use clap::Args;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use crate::{
+    ai::{redact_secret, AIService},
+    config::Config,
+};
+
+#[derive(Args, Debug)]
+pub struct RawArgs {
+    /// JSON file holding the request body, or `-` to read it from stdin
+    #[arg(long, value_name = "FILE")]
+    pub body: PathBuf,
+
+    /// Echo the request body to stderr before sending it
+    #[arg(long)]
+    pub show_request: bool,
+}
+
+pub async fn execute(args: RawArgs) -> Result<()> {
+    let body = read_body(&args.body, std::io::stdin())?;
+
+    // Load configuration
+    let config = Config::load()?;
+    if args.show_request {
+        eprintln!("{}", echo_request(&body, &config.ai.api_key)?);
+    }
+
+    let ai_service = AIService::new(config.ai)?;
+    let response = ai_service.raw(&body).await?;
+    println!("{}", response);
+
+    Ok(())
+}
+
+/// Reads and validates the request body, so malformed JSON fails locally.
+fn read_body(path: &Path, mut stdin: impl Read) -> Result<serde_json::Value> {
+    let text = if path.as_os_str() == "-" {
+        let mut text = String::new();
+        stdin
+            .read_to_string(&mut text)
+            .context("Failed to read request body from stdin")?;
+        text
+    } else {
+        std::fs::read_to_string(path).with_context(|| format!("Failed to read request body: {:?}", path))?
+    };
+
+    serde_json::from_str(&text).context("Request body is not valid JSON")
+}
+
+/// Pretty-prints the request body with the configured API key redacted.
+fn echo_request(body: &serde_json::Value, api_key: &str) -> Result<String> {
+    Ok(redact_secret(&serde_json::to_string_pretty(body)?, api_key))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_body() {
+        let body = read_body(Path::new("-"), r#"{"max_tokens": 5}"#.as_bytes()).unwrap();
+        assert_eq!(body["max_tokens"], 5);
+
+        assert!(read_body(Path::new("-"), "not json".as_bytes()).is_err());
+        assert!(read_body(Path::new("/no/such/request.json"), std::io::empty()).is_err());
+    }
+
+    #[test]
+    fn test_echo_request_redacts_api_key() {
+        let body = serde_json::json!({ "metadata": { "key": "sk-secret" } });
+        let echoed = echo_request(&body, "sk-secret").unwrap();
+        assert!(!echoed.contains("sk-secret"));
+        assert!(echoed.contains("[REDACTED]"));
+    }
+}
@@ -0,0 +1,456 @@
+use pulldown_cmark::{html, Event, Options, Parser};
+use std::time::Duration;
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+
+/// Renders model markdown to an HTML fragment. Fenced code blocks become
+/// `<pre><code>`; raw HTML in the markdown is escaped rather than passed through.
+pub fn markdown_to_html(markdown: &str) -> String {
+    let parser = Parser::new_ext(markdown, Options::ENABLE_TABLES | Options::ENABLE_STRIKETHROUGH)
+        .map(|event| match event {
+            Event::Html(raw) | Event::InlineHtml(raw) => Event::Text(raw),
+            other => other,
+        });
+
+    let mut output = String::new();
+    html::push_html(&mut output, parser);
+    output
+}
+
+/// How long a request took, as shown by `--timing`: `(2.3s)`.
+pub fn elapsed_note(elapsed: Duration) -> String {
+    format!("({:.1}s)", elapsed.as_secs_f64())
+}
+
+/// A point in time to the second, in UTC: `2026-10-17T14:03:05Z`.
+pub fn timestamp(at: impl Into<OffsetDateTime>) -> String {
+    let at = at.into();
+    let at = at.replace_nanosecond(0).unwrap_or(at);
+    at.format(&Rfc3339).unwrap_or_default()
+}
+
+/// Columns in the terminal stdout goes to, or `None` when it isn't one.
+pub fn terminal_width() -> Option<usize> {
+    terminal_size::terminal_size().map(|(terminal_size::Width(width), _)| width as usize)
+}
+
+/// Wraps each line of `text` at spaces to fit `width` columns, keeping its
+/// indentation on the lines it is broken into. A word longer than `width`
+/// gets a line of its own rather than being split.
+pub fn wrap(text: &str, width: usize) -> String {
+    let mut wrapped = Vec::new();
+    for line in text.lines() {
+        let indent = &line[..line.len() - line.trim_start().len()];
+        let mut current = indent.to_string();
+        for word in line.split_whitespace() {
+            let used = current.chars().count();
+            if used > indent.len() && used + 1 + word.chars().count() > width {
+                wrapped.push(std::mem::replace(&mut current, indent.to_string()));
+            }
+            if current.len() > indent.len() {
+                current.push(' ');
+            }
+            current.push_str(word);
+        }
+        wrapped.push(current.trim_end().to_string());
+    }
+    wrapped.join("\n")
+}
+
+/// The start of `text` on one line, cut to `max_chars` with an ellipsis.
+pub fn preview(text: &str, max_chars: usize) -> String {
+    let flat = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    if flat.chars().count() <= max_chars {
+        return flat;
+    }
+    let cut: String = flat.chars().take(max_chars.saturating_sub(1)).collect();
+    format!("{}…", cut.trim_end())
+}
+
+/// Escapes text for use in HTML element content and attribute values.
+pub fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+const RESET: &str = "\x1B[0m";
+const BOLD_ON: &str = "\x1B[1m";
+const BOLD_OFF: &str = "\x1B[22m";
+const ITALIC_ON: &str = "\x1B[3m";
+const ITALIC_OFF: &str = "\x1B[23m";
+const CODE_ON: &str = "\x1B[36m";
+const CODE_OFF: &str = "\x1B[39m";
+
+/// Renders markdown prose for the terminal a line at a time, as it streams in:
+/// headings become bold, list bullets become `•`, quotes get a bar, and
+/// `**bold**`, `*italic*` and `` `code` `` are styled with their markers
+/// dropped. Code blocks are left to the highlighter. Markers whose meaning
+/// depends on what follows are held back until it arrives. Given a width,
+/// prose is wrapped at spaces to fit it, so each word is held back until the
+/// space after it shows whether it still fits on the line.
+#[derive(Debug, Default)]
+pub struct TerminalMarkdown {
+    /// Style every line starts in, such as a color (may be empty)
+    style: String,
+    /// Text of the current line not yet rendered
+    held: String,
+    /// Whether the start of the current line has been rendered
+    started: bool,
+    heading: bool,
+    bold: bool,
+    italic: bool,
+    code: bool,
+    /// The last character rendered on this line
+    last: Option<char>,
+    /// Columns to wrap prose at; `None` leaves lines as long as they are
+    width: Option<usize>,
+    /// Columns used so far on the current output line
+    column: usize,
+    /// What a wrapped line starts with, to line up under the text above
+    margin: String,
+    /// The word being held back, with any styling inside it
+    word: String,
+    /// Columns the held word takes up
+    word_len: usize,
+    /// Spaces before the held word, dropped if it goes on a new line
+    spaces: String,
+}
+
+impl TerminalMarkdown {
+    pub fn new(style: &str) -> Self {
+        Self {
+            style: style.to_string(),
+            ..Default::default()
+        }
+    }
+
+    /// Wraps prose to `width` columns; `None` leaves it unwrapped.
+    pub fn wrapped_to(mut self, width: Option<usize>) -> Self {
+        self.width = width;
+        self
+    }
+
+    /// Takes more of the current line, without its newline, and returns what
+    /// can be shown now.
+    pub fn push(&mut self, text: &str) -> String {
+        self.held.push_str(text);
+        let mut output = self.start_line(false);
+        if self.started {
+            output.push_str(&self.inline(false));
+        }
+        output
+    }
+
+    /// Ends the current line, returning the rest of it with any styling closed.
+    pub fn end_line(&mut self) -> String {
+        if !self.started && self.held.trim().is_empty() {
+            return std::mem::take(&mut self.held);
+        }
+        let mut output = self.start_line(true);
+        output.push_str(&self.inline(true));
+        if !self.style.is_empty() || self.heading || self.bold || self.italic || self.code {
+            output.push_str(RESET);
+        }
+        *self = Self::new(&self.style).wrapped_to(self.width);
+        output
+    }
+
+    /// Renders the line's block marker once enough of it is known.
+    fn start_line(&mut self, done: bool) -> String {
+        if self.started {
+            return String::new();
+        }
+        let rest = self.held.trim_start();
+        let indent = &self.held[..self.held.len() - rest.len()];
+        let hashes = rest.chars().take_while(|&c| c == '#').count();
+        let undecided = rest.is_empty() || (hashes == rest.len() && hashes <= 6) || matches!(rest, "-" | "*" | "+");
+        if undecided && !done {
+            return String::new();
+        }
+
+        let mut output = self.style.clone();
+        let remaining = if (1..=6).contains(&hashes) && rest[hashes..].starts_with(' ') {
+            self.heading = true;
+            output.push_str(BOLD_ON);
+            rest[hashes..].trim_start()
+        } else if let Some(item) = ["- ", "* ", "+ "].iter().find_map(|bullet| rest.strip_prefix(bullet)) {
+            output.push_str(indent);
+            output.push_str("• ");
+            // Wrapped lines of an item hang under its text
+            self.margin = " ".repeat(indent.chars().count() + 2);
+            self.column = self.margin.len();
+            item
+        } else if let Some(quote) = rest.strip_prefix('>') {
+            output.push_str(indent);
+            output.push_str("│ ");
+            self.margin = format!("{}│ ", indent);
+            self.column = indent.chars().count() + 2;
+            quote.trim_start_matches(' ')
+        } else {
+            self.margin = indent.to_string();
+            &self.held
+        };
+        self.held = remaining.to_string();
+        self.started = true;
+        output
+    }
+
+    /// Renders inline markup, stopping before a `*` whose meaning depends on
+    /// the next character unless the line is `done`.
+    fn inline(&mut self, done: bool) -> String {
+        let chars: Vec<char> = self.held.chars().collect();
+        let mut output = String::new();
+        let mut i = 0;
+        while i < chars.len() {
+            let c = chars[i];
+            let next = chars.get(i + 1).copied();
+            if self.code {
+                if c == '`' {
+                    self.code = false;
+                    self.put_style(CODE_OFF, &mut output);
+                    let style = self.style.clone();
+                    self.put_style(&style, &mut output);
+                } else {
+                    self.put(c, &mut output);
+                }
+            } else if c == '`' {
+                self.code = true;
+                self.put_style(CODE_ON, &mut output);
+            } else if c == '*' {
+                if next.is_none() && !done {
+                    break;
+                }
+                if next == Some('*') {
+                    self.bold = !self.bold;
+                    let marker = match (self.bold, self.heading) {
+                        (true, _) => BOLD_ON,
+                        (false, false) => BOLD_OFF,
+                        // A heading stays bold throughout
+                        (false, true) => "",
+                    };
+                    self.put_style(marker, &mut output);
+                    self.last = Some(c);
+                    i += 2;
+                    continue;
+                }
+                // A lone `*` only marks italics when it hugs the text, so `a * b` stays as it is
+                let opens = !self.italic
+                    && next.is_some_and(|n| !n.is_whitespace())
+                    && self.last.is_none_or(|l| l.is_whitespace() || "([{\"'".contains(l));
+                let closes = self.italic && self.last.is_some_and(|l| !l.is_whitespace());
+                if opens || closes {
+                    self.italic = !self.italic;
+                    self.put_style(if self.italic { ITALIC_ON } else { ITALIC_OFF }, &mut output);
+                } else {
+                    self.put(c, &mut output);
+                }
+            } else {
+                self.put(c, &mut output);
+            }
+            self.last = Some(c);
+            i += 1;
+        }
+        self.held = chars[i..].iter().collect();
+        if done {
+            self.flush_word(&mut output);
+            output.push_str(&std::mem::take(&mut self.spaces));
+        }
+        output
+    }
+
+    /// Adds a character of text, holding words back when wrapping.
+    fn put(&mut self, c: char, output: &mut String) {
+        if self.width.is_none() {
+            output.push(c);
+        } else if c.is_whitespace() {
+            self.flush_word(output);
+            self.spaces.push(c);
+        } else {
+            self.word.push(c);
+            self.word_len += 1;
+        }
+    }
+
+    /// Adds styling, which takes up no columns.
+    fn put_style(&mut self, style: &str, output: &mut String) {
+        if self.width.is_none() {
+            output.push_str(style);
+        } else {
+            self.word.push_str(style);
+        }
+    }
+
+    /// Passes on the held word, first starting a new line if it won't fit
+    /// on this one.
+    fn flush_word(&mut self, output: &mut String) {
+        let Some(width) = self.width else { return };
+        if self.word_len == 0 {
+            output.push_str(&std::mem::take(&mut self.word));
+            return;
+        }
+        let spaces = std::mem::take(&mut self.spaces);
+        let margin_len = self.margin.chars().count();
+        if self.column > margin_len && self.column + spaces.chars().count() + self.word_len > width {
+            output.push('\n');
+            output.push_str(&self.margin);
+            self.column = margin_len;
+        } else {
+            output.push_str(&spaces);
+            self.column += spaces.chars().count();
+        }
+        output.push_str(&std::mem::take(&mut self.word));
+        self.column += std::mem::take(&mut self.word_len);
+    }
+}
+
+/// Wraps the lines of `text` outside fenced code blocks to `width` columns;
+/// code is left as it is, since breaking it would change its meaning.
+pub fn wrap_prose(text: &str, width: usize) -> String {
+    let mut output = String::with_capacity(text.len());
+    let mut in_code = false;
+    for line in text.split_inclusive('\n') {
+        let (body, newline) = match line.strip_suffix('\n') {
+            Some(body) => (body, "\n"),
+            None => (line, ""),
+        };
+        if body.trim_start().starts_with("```") {
+            in_code = !in_code;
+            output.push_str(line);
+        } else if in_code {
+            output.push_str(line);
+        } else {
+            output.push_str(&wrap(body, width));
+            output.push_str(newline);
+        }
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn markdown_to_terminal(text: &str, style: &str) -> String {
+        let mut markdown = TerminalMarkdown::new(style);
+        let mut output = String::new();
+        for line in text.split_inclusive('\n') {
+            let body = line.strip_suffix('\n');
+            output.push_str(&markdown.push(body.unwrap_or(line)));
+            if body.is_some() {
+                output.push_str(&markdown.end_line());
+                output.push('\n');
+            }
+        }
+        output.push_str(&markdown.end_line());
+        output
+    }
+
+    #[test]
+    fn test_elapsed_note() {
+        assert_eq!(elapsed_note(Duration::from_millis(2345)), "(2.3s)");
+        assert_eq!(elapsed_note(Duration::from_millis(40)), "(0.0s)");
+    }
+
+    #[test]
+    fn test_wrap() {
+        assert_eq!(wrap("one two three four", 9), "one two\nthree\nfour");
+        assert_eq!(wrap("  - indented item here", 12), "  - indented\n  item here");
+        assert_eq!(wrap("short\n\nsupercalifragilistic word", 8), "short\n\nsupercalifragilistic\nword");
+    }
+
+    #[test]
+    fn test_preview() {
+        assert_eq!(preview("How do I\nparse   this?", 40), "How do I parse this?");
+        assert_eq!(preview("The quick brown fox jumps", 12), "The quick b…");
+    }
+
+    #[test]
+    fn test_timestamp() {
+        let at = OffsetDateTime::from_unix_timestamp_nanos(1_700_000_000_900_000_000).unwrap();
+        assert_eq!(timestamp(at), "2023-11-14T22:13:20Z");
+    }
+
+    #[test]
+    fn test_markdown_to_html_code_fence() {
+        let html = markdown_to_html("Some **bold** text.\n\n```rust\nfn main() {}\n```\n");
+        assert!(html.contains("<strong>bold</strong>"));
+        assert!(html.contains("<pre><code class=\"language-rust\">fn main() {}"));
+    }
+
+    #[test]
+    fn test_raw_html_is_escaped() {
+        let html = markdown_to_html("<script>alert(1)</script>\n");
+        assert!(!html.contains("<script>"));
+        assert!(html.contains("&lt;script&gt;"));
+        assert_eq!(escape_html("a<b & \"c\""), "a&lt;b &amp; &quot;c&quot;");
+    }
+
+    #[test]
+    fn test_markdown_to_terminal() {
+        let rendered = markdown_to_terminal("# Title\n\n- one **two**\n  * `three`\n> quoted\n2 * 3 is *six*\n", "");
+        let lines: Vec<&str> = rendered.lines().collect();
+        assert_eq!(lines[0], "\x1B[1mTitle\x1B[0m");
+        assert_eq!(lines[1], "");
+        assert_eq!(lines[2], "• one \x1B[1mtwo\x1B[22m");
+        assert_eq!(lines[3], "  • \x1B[36mthree\x1B[39m");
+        assert_eq!(lines[4], "│ quoted");
+        assert_eq!(lines[5], "2 * 3 is \x1B[3msix\x1B[23m");
+
+        // The base style is restored after inline code and closed at the end of the line
+        assert_eq!(
+            markdown_to_terminal("Run `ls` now", "\x1B[32m"),
+            "\x1B[32mRun \x1B[36mls\x1B[39m\x1B[32m now\x1B[0m"
+        );
+    }
+
+    #[test]
+    fn test_markdown_wraps_to_width() {
+        let render = |text: &str| {
+            let mut markdown = TerminalMarkdown::new("").wrapped_to(Some(16));
+            markdown.push(text) + &markdown.end_line()
+        };
+        assert_eq!(render("The quick brown fox jumps over"), "The quick brown\nfox jumps over");
+        assert_eq!(render("- a list item that wraps"), "• a list item\n  that wraps");
+        assert_eq!(render("> quoted text that wraps"), "│ quoted text\n│ that wraps");
+        assert_eq!(render("Use **bold words** here"), "Use \x1B[1mbold words\x1B[22m\nhere");
+
+        // Each word waits for the space after it
+        let mut markdown = TerminalMarkdown::new("").wrapped_to(Some(10));
+        assert_eq!(markdown.push("one tw"), "one");
+        assert_eq!(markdown.push("o three"), " two");
+        assert_eq!(markdown.end_line(), "\nthree");
+    }
+
+    #[test]
+    fn test_wrap_prose_leaves_code_alone() {
+        let text = "A sentence that is long.\n```\nlet x = some_long_call(a, b);\n```\nShort.";
+        assert_eq!(
+            wrap_prose(text, 12),
+            "A sentence\nthat is\nlong.\n```\nlet x = some_long_call(a, b);\n```\nShort."
+        );
+    }
+
+    #[test]
+    fn test_markdown_streams_in_pieces() {
+        let mut markdown = TerminalMarkdown::new("");
+        assert_eq!(markdown.push("#"), "");
+        assert_eq!(markdown.push("# Plan"), "\x1B[1mPlan");
+        assert_eq!(markdown.end_line(), "\x1B[0m");
+
+        assert_eq!(markdown.push("-"), "");
+        assert_eq!(markdown.push(" a *"), "• a ");
+        assert_eq!(markdown.push("*b"), "\x1B[1mb");
+        assert_eq!(markdown.push("*"), "");
+        assert_eq!(markdown.push("*"), "\x1B[22m");
+        assert_eq!(markdown.end_line(), "");
+    }
+}
@@ -0,0 +1,107 @@
+use anyhow::Result;
+use clap::Args;
+use std::path::{Path, PathBuf};
+
+use super::{index::embedding_config, quiet, spinner::Spinner};
+use crate::{
+    ai::EmbeddingService,
+    config::Config,
+    search::{self, Hit, DEFAULT_TOP_K},
+};
+
+/// Lines of each hit shown under its location.
+const SNIPPET_LINES: usize = 8;
+
+#[derive(Args, Debug)]
+pub struct SearchArgs {
+    /// What to look for, in plain words
+    pub query: String,
+
+    /// How many results to show
+    #[arg(short = 'k', long, default_value_t = DEFAULT_TOP_K)]
+    pub top_k: usize,
+
+    /// Directory whose index to search (defaults to the project root)
+    #[arg(long)]
+    pub dir: Option<PathBuf>,
+}
+
+pub async fn execute(args: SearchArgs) -> Result<()> {
+    let config = Config::load()?;
+    let embeddings = embedding_config(&config, "search")?;
+    let root = match args.dir {
+        Some(dir) => dir,
+        None => config.project_root()?,
+    };
+
+    let service = EmbeddingService::new(embeddings)?;
+    let hits = {
+        let _spinner = Spinner::start("Searching...");
+        search::search(&root, &service, &args.query, args.top_k).await?
+    };
+    if hits.is_empty() {
+        println!("No matches.");
+        return Ok(());
+    }
+
+    for hit in &hits {
+        println!("{}:{}-{} ({:.3})", hit.path, hit.start_line, hit.end_line, hit.score);
+        match search::snippet(&root, hit) {
+            Ok(snippet) => {
+                let lines: Vec<&str> = snippet.lines().take(SNIPPET_LINES).collect();
+                println!("```\n{}\n```\n", lines.join("\n"));
+            }
+            // The file changed or went away since it was indexed
+            Err(e) if !quiet::enabled() => eprintln!("Warning: {:#}\n", e),
+            Err(_) => {}
+        }
+    }
+    Ok(())
+}
+
+/// A question for the model with the code search found for it in front, so
+/// it can answer from the relevant parts of the project. Hits whose files
+/// can no longer be read are left out.
+pub fn question_with_context(root: &Path, question: &str, hits: &[Hit]) -> String {
+    let mut message = String::from("Code from the project that may be relevant:\n");
+    for hit in hits {
+        if let Ok(snippet) = search::snippet(root, hit) {
+            message.push_str(&format!(
+                "\n{}:{}-{}\n```\n{}\n```\n",
+                hit.path, hit.start_line, hit.end_line, snippet
+            ));
+        }
+    }
+    message.push_str(&format!("\n{}", question));
+    message
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_question_with_context() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        std::fs::write(temp_dir.path().join("config.rs"), "fn load() {}\nfn validate() {}\n").unwrap();
+        let hits = vec![
+            Hit {
+                path: "config.rs".to_string(),
+                start_line: 2,
+                end_line: 2,
+                score: 0.9,
+            },
+            Hit {
+                path: "deleted.rs".to_string(),
+                start_line: 1,
+                end_line: 3,
+                score: 0.5,
+            },
+        ];
+
+        let message = question_with_context(temp_dir.path(), "Where is the config validated?", &hits);
+        assert!(message.contains("config.rs:2-2\n```\nfn validate() {}\n```"));
+        assert!(!message.contains("deleted.rs"));
+        assert!(message.ends_with("\nWhere is the config validated?"));
+    }
+}
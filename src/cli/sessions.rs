@@ -0,0 +1,47 @@
+use anyhow::{Context, Result};
+use clap::{Args, Subcommand};
+
+use super::{history, render};
+
+#[derive(Args, Debug)]
+pub struct SessionsArgs {
+    #[command(subcommand)]
+    pub command: SessionsCommand,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum SessionsCommand {
+    /// List saved sessions with their turn counts and when they were saved
+    List,
+    /// Delete a saved session
+    Rm {
+        /// Name the session was saved under
+        name: String,
+    },
+}
+
+/// Manages the sessions `/save` writes, without starting interactive mode.
+/// Like `completions`, this doesn't need a configuration.
+pub fn execute(args: SessionsArgs) -> Result<()> {
+    let dir = history::sessions_dir().context("Could not determine the config directory")?;
+    match args.command {
+        SessionsCommand::List => {
+            let sessions = history::session_details(&dir)?;
+            if sessions.is_empty() {
+                println!("No saved sessions in {}.", dir.display());
+            }
+            for session in sessions {
+                let turns = session.turns.map_or("unreadable".to_string(), |turns| format!("{} turns", turns));
+                let modified = session.modified.map_or("-".to_string(), render::timestamp);
+                println!("{:<24} {:>12}  {}", session.name, turns, modified);
+            }
+        }
+        SessionsCommand::Rm { name } => {
+            if !history::delete_session(&dir, &name)? {
+                anyhow::bail!("No session named '{}'; `monk-manager sessions list` shows the saved ones", name);
+            }
+            println!("Deleted session '{}'.", name);
+        }
+    }
+    Ok(())
+}
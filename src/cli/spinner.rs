@@ -0,0 +1,89 @@
+use std::io::{IsTerminal, Write};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::task::JoinHandle;
+
+use super::{color, quiet};
+
+const DEFAULT_STYLE: &str = "\x1B[33m";
+const FRAMES: [&str; 10] = ["⠋", "⠙", "⠹", "⠸", "⠼", "⠴", "⠦", "⠧", "⠇", "⠏"];
+const TICK: Duration = Duration::from_millis(100);
+
+/// An animated "working on it" indicator on stderr, for while a request is in
+/// flight. It stays off when stderr isn't a terminal, color is off, or
+/// `--quiet` is set, and clears its line when stopped or dropped.
+pub struct Spinner {
+    /// Set once stopped; held while drawing so a frame never lands after the clear
+    stopped: Arc<Mutex<bool>>,
+    task: Option<JoinHandle<()>>,
+}
+
+impl Spinner {
+    /// Starts animating `message` in yellow. Must be called within a Tokio runtime.
+    pub fn start(message: &str) -> Self {
+        Self::start_styled(message, DEFAULT_STYLE)
+    }
+
+    /// Starts animating `message`, which may be empty, in `style`.
+    pub fn start_styled(message: &str, style: &str) -> Self {
+        let stopped = Arc::new(Mutex::new(false));
+        let enabled = std::io::stderr().is_terminal() && color::enabled() && !quiet::enabled();
+        let task = enabled.then(|| {
+            let stopped = stopped.clone();
+            let message = message.to_string();
+            let style = style.to_string();
+            tokio::spawn(async move {
+                let mut interval = tokio::time::interval(TICK);
+                for tick in 0.. {
+                    interval.tick().await;
+                    let stopped = stopped.lock().unwrap_or_else(|e| e.into_inner());
+                    if *stopped {
+                        break;
+                    }
+                    let mut stderr = std::io::stderr().lock();
+                    let _ = write!(stderr, "\r\x1B[K{}", frame(tick, &message, &style));
+                    let _ = stderr.flush();
+                }
+            })
+        });
+        Self { stopped, task }
+    }
+
+    /// Stops the animation and clears its line. Safe to call more than once.
+    pub fn stop(&mut self) {
+        let Some(task) = self.task.take() else {
+            return;
+        };
+        let mut stopped = self.stopped.lock().unwrap_or_else(|e| e.into_inner());
+        *stopped = true;
+        task.abort();
+        let mut stderr = std::io::stderr().lock();
+        let _ = write!(stderr, "\r\x1B[K");
+        let _ = stderr.flush();
+    }
+}
+
+impl Drop for Spinner {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+/// The spinner line for the `tick`th frame.
+fn frame(tick: usize, message: &str, style: &str) -> String {
+    let separator = if message.is_empty() { "" } else { " " };
+    format!("{}{}{}{}\x1B[0m", style, FRAMES[tick % FRAMES.len()], separator, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_frames_cycle() {
+        assert_eq!(frame(0, "Thinking...", DEFAULT_STYLE), "\x1B[33m⠋ Thinking...\x1B[0m");
+        assert_eq!(frame(FRAMES.len() + 1, "x", ""), frame(1, "x", ""));
+        // With no message, just the spinner
+        assert_eq!(frame(0, "", "\x1B[36m"), "\x1B[36m⠋\x1B[0m");
+    }
+}
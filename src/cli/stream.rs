@@ -0,0 +1,64 @@
+use serde_json::json;
+use std::io::Write;
+
+use crate::ai::Usage;
+
+/// One streamed piece of text as an NDJSON line.
+pub fn ndjson_delta(delta: &str) -> String {
+    json!({ "delta": delta }).to_string()
+}
+
+/// The closing NDJSON line for a stream. `usage` is null when the provider
+/// didn't report it, and `file` names the source when several are streamed.
+pub fn ndjson_done(usage: Option<Usage>, file: Option<&str>) -> String {
+    let mut done = json!({ "done": true, "usage": usage });
+    if let Some(file) = file {
+        done["file"] = json!(file);
+    }
+    done.to_string()
+}
+
+/// Returns a delta callback that writes each piece of text to `out` straight
+/// away, either as-is or as an NDJSON line, so readers see it immediately.
+/// Write errors (such as a closed pipe) are ignored; the request still completes.
+pub fn write_deltas<'a>(out: &'a mut (dyn Write + Send), ndjson: bool) -> impl FnMut(&str) + Send + 'a {
+    move |delta| {
+        let _ = if ndjson {
+            writeln!(out, "{}", ndjson_delta(delta))
+        } else {
+            write!(out, "{}", delta)
+        };
+        let _ = out.flush();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ndjson_lines() {
+        let mut out = Vec::new();
+        let mut on_delta = write_deltas(&mut out, true);
+        on_delta("Hel\"lo");
+        on_delta("\nworld");
+        drop(on_delta);
+        let usage = Usage {
+            input_tokens: 3,
+            output_tokens: 2,
+        };
+        writeln!(out, "{}", ndjson_done(Some(usage), Some("a.rs"))).unwrap();
+
+        let text = String::from_utf8(out).unwrap();
+        let lines: Vec<serde_json::Value> = text
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0]["delta"], "Hel\"lo");
+        assert_eq!(lines[1]["delta"], "\nworld");
+        assert_eq!(lines[2]["done"], true);
+        assert_eq!(lines[2]["usage"]["output_tokens"], 2);
+        assert_eq!(lines[2]["file"], "a.rs");
+    }
+}
@@ -0,0 +1,207 @@
+use anyhow::Result;
+use clap::Args;
+use std::path::PathBuf;
+use tracing::warn;
+
+use super::{context, history, quiet, spinner::Spinner, truncation, walk};
+use crate::{
+    ai::{AIService, Message},
+    concurrency::{run_bounded, DEFAULT_MAX_CONCURRENCY},
+    config::Config,
+};
+
+#[derive(Args, Debug)]
+pub struct SummarizeArgs {
+    /// Directory to summarize (defaults to the project root)
+    pub dir: Option<PathBuf>,
+
+    /// Include files that .gitignore, hidden-file and build-output rules would leave out
+    #[arg(long)]
+    pub no_ignore: bool,
+}
+
+pub async fn execute(args: SummarizeArgs) -> Result<()> {
+    let config = Config::load()?;
+    let dir = match args.dir {
+        Some(dir) => dir,
+        None => config.project_root()?,
+    };
+    if !dir.is_dir() {
+        anyhow::bail!("{} is not a directory", dir.display());
+    }
+
+    let mut files = Vec::new();
+    for path in walk::files(&dir, !args.no_ignore) {
+        match context::read_text(&path, &config) {
+            Ok(content) => files.push((path, content)),
+            Err(e) if !quiet::enabled() => eprintln!("Warning: skipping {}: {:#}", path.display(), e),
+            Err(_) => {}
+        }
+    }
+    if files.is_empty() {
+        anyhow::bail!("No text files to summarize in {}", dir.display());
+    }
+
+    let max_tokens = config.commands.max_context_tokens;
+    let ai_service = AIService::new(config.ai)?;
+    let overview = {
+        let _spinner = Spinner::start(&format!("Summarizing {} files...", files.len()));
+        map_reduce(&ai_service, files, max_tokens).await?
+    };
+    println!("# Summary of {}\n\n{}", dir.display(), overview);
+    Ok(())
+}
+
+/// Summarizes many files without any one request going over `max_tokens`:
+/// each file is summarized on its own (the map), then the summaries are
+/// combined in batches that fit the budget, level by level, until a single
+/// overview is left (the reduce). Files that can't be summarized are left
+/// out with a warning; it is an error only if none can be.
+pub async fn map_reduce(service: &AIService, files: Vec<(PathBuf, String)>, max_tokens: usize) -> Result<String> {
+    let max_chars = max_tokens.saturating_mul(4);
+    let mapped = run_bounded(files, DEFAULT_MAX_CONCURRENCY, |(path, content)| async move {
+        let result = service.chat(&[user(file_prompt(&path, &content, max_chars))], None).await;
+        (path, result)
+    })
+    .await;
+
+    let mut summaries = Vec::new();
+    for (path, result) in mapped {
+        match result {
+            Ok(summary) => summaries.push(format!("### {}\n\n{}", path.display(), summary.trim())),
+            Err(e) => warn!("Could not summarize {}: {:#}", path.display(), e),
+        }
+    }
+    if summaries.is_empty() {
+        anyhow::bail!("None of the files could be summarized");
+    }
+
+    loop {
+        let batches = batch(summaries, max_tokens);
+        if let [only] = batches.as_slice() {
+            return service.chat(&[user(reduce_prompt(only, true))], None).await;
+        }
+        let reduced = run_bounded(&batches, DEFAULT_MAX_CONCURRENCY, |batch| async move {
+            service.chat(&[user(reduce_prompt(batch, false))], None).await
+        })
+        .await;
+        summaries = reduced.into_iter().collect::<Result<Vec<_>>>()?;
+    }
+}
+
+fn user(content: String) -> Message {
+    Message {
+        role: "user".to_string(),
+        content,
+    }
+}
+
+/// The map step's request for one file, cut to `max_chars` characters.
+fn file_prompt(path: &std::path::Path, content: &str, max_chars: usize) -> String {
+    let mut content = content.to_string();
+    if content.chars().count() > max_chars {
+        let cut = content.char_indices().nth(max_chars).map_or(content.len(), |(index, _)| index);
+        let omitted = content.len() - cut;
+        content.truncate(cut);
+        content.push('\n');
+        content.push_str(&truncation::omitted_bytes_note(omitted));
+    }
+    format!(
+        "Summarize the file {} in a few sentences: what it is for and what its main parts do.\n\n```\n{}\n```",
+        path.display(),
+        content
+    )
+}
+
+/// The reduce step's request: the final overview, or a combined summary of one batch.
+fn reduce_prompt(summaries: &[String], last: bool) -> String {
+    let task = if last {
+        "Using these summaries of its files, write a cohesive overview of the codebase: \
+         its purpose, how it is organized, and how the main parts fit together."
+    } else {
+        "Combine these summaries of related files into one shorter summary, keeping the file names \
+         and the most important details."
+    };
+    format!("{}\n\n{}", task, summaries.join("\n\n"))
+}
+
+/// Groups `summaries` in order into batches of about `max_tokens` each. A
+/// batch has at least two summaries whenever two are left, so every level
+/// of the reduce makes progress.
+fn batch(summaries: Vec<String>, max_tokens: usize) -> Vec<Vec<String>> {
+    let mut batches: Vec<Vec<String>> = Vec::new();
+    let mut tokens = 0;
+    for summary in summaries {
+        let size = history::estimate_tokens(&summary);
+        match batches.last_mut() {
+            Some(last) if last.len() < 2 || tokens + size <= max_tokens => {
+                tokens += size;
+                last.push(summary);
+            }
+            _ => {
+                tokens = size;
+                batches.push(vec![summary]);
+            }
+        }
+    }
+    batches
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::{AIClient, ExplainOptions, ModelConfig};
+    use async_trait::async_trait;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    /// Answers every chat with a numbered reply, counting the requests.
+    struct CountingClient(Arc<AtomicUsize>);
+
+    #[async_trait]
+    impl AIClient for CountingClient {
+        async fn explain(&self, _code: &str, _language: &str, _options: &ExplainOptions) -> Result<String> {
+            unreachable!("map_reduce only chats")
+        }
+
+        async fn chat(&self, messages: &[Message], _project_context: Option<&str>) -> Result<String> {
+            let call = self.0.fetch_add(1, Ordering::SeqCst) + 1;
+            let overview = messages[0].content.starts_with("Using these summaries");
+            Ok(if overview { "overview".to_string() } else { format!("summary {}", call) })
+        }
+
+        async fn raw(&self, _body: &serde_json::Value) -> Result<String> {
+            unreachable!("map_reduce only chats")
+        }
+    }
+
+    #[test]
+    fn test_batches_fit_the_budget() {
+        let summaries: Vec<String> = (0..5).map(|_| "x".repeat(40)).collect();
+        let batches = batch(summaries, 25);
+        assert_eq!(batches.iter().map(Vec::len).collect::<Vec<_>>(), vec![2, 2, 1]);
+
+        // Even summaries over the budget are paired up
+        let batches = batch(vec!["x".repeat(400), "y".repeat(400)], 25);
+        assert_eq!(batches.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_map_reduce_reduces_in_levels() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let config = ModelConfig {
+            model_name: "claude-3-haiku".to_string(),
+            ..crate::ai::test_model_config()
+        };
+        let service = AIService::from_client(Box::new(CountingClient(calls.clone())), config);
+        let files: Vec<(PathBuf, String)> = (0..6)
+            .map(|i| (PathBuf::from(format!("src/file{}.rs", i)), "fn main() {}".to_string()))
+            .collect();
+
+        // Each summary is ~7 tokens, so a budget of 16 allows pairs only
+        let overview = map_reduce(&service, files, 16).await.unwrap();
+        assert_eq!(overview, "overview");
+        // 6 files, 3 pair summaries, then 2 more pairings of those, then the overview
+        assert!(calls.load(Ordering::SeqCst) > 6 + 1);
+    }
+}
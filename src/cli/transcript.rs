@@ -0,0 +1,174 @@
+//! Conversations as markdown, for `/export` and `/import`: a readable
+//! transcript to share, unlike the JSON `/save` writes for resuming.
+
+use anyhow::{bail, Context, Result};
+use std::io::{BufRead, Write};
+use std::path::Path;
+use time::OffsetDateTime;
+
+use super::render;
+use crate::ai::Message;
+
+/// Marks the start of a message from the user.
+pub const USER_HEADER: &str = "**You:**";
+/// Marks the start of a message from the model.
+pub const ASSISTANT_HEADER: &str = "**Assistant:**";
+
+/// The conversation as markdown: a title and the time of the export, then
+/// each message under a header naming who sent it.
+pub fn to_markdown(history: &[Message], exported_at: OffsetDateTime) -> String {
+    let mut markdown = format!("# monk-manager conversation\n\n_Exported {}_\n", render::timestamp(exported_at));
+    for message in history {
+        let header = if message.role == "assistant" { ASSISTANT_HEADER } else { USER_HEADER };
+        markdown.push_str(&format!("\n{}\n\n{}\n", header, message.content.trim_end()));
+    }
+    markdown
+}
+
+/// Writes the conversation to `path` as markdown, stamped with the current time.
+pub fn export(path: &Path, history: &[Message]) -> Result<()> {
+    let markdown = to_markdown(history, OffsetDateTime::now_utc());
+    std::fs::write(path, markdown).with_context(|| format!("Failed to write {:?}", path))
+}
+
+/// Reads a conversation back from markdown written by `to_markdown`. Blank
+/// lines and spacing around headers don't matter, but anything else that
+/// isn't in a message, or a message with nothing in it, is an error: nothing
+/// is loaded from a file that isn't a transcript.
+pub fn from_markdown(markdown: &str) -> Result<Vec<Message>> {
+    let mut history: Vec<Message> = Vec::new();
+    let mut body = String::new();
+    for (number, line) in markdown.lines().enumerate() {
+        let role = match line.trim() {
+            header if header.eq_ignore_ascii_case(USER_HEADER) => "user",
+            header if header.eq_ignore_ascii_case(ASSISTANT_HEADER) => "assistant",
+            text => {
+                if !history.is_empty() {
+                    body.push_str(line);
+                    body.push('\n');
+                } else if !(text.is_empty() || text.starts_with('#') || text.starts_with("_Exported")) {
+                    bail!(
+                        "Line {} isn't part of a message; expected a {} or {} line before any text",
+                        number + 1,
+                        USER_HEADER,
+                        ASSISTANT_HEADER
+                    );
+                }
+                continue;
+            }
+        };
+        finish_message(&mut history, &mut body)?;
+        history.push(Message {
+            role: role.to_string(),
+            content: String::new(),
+        });
+    }
+    finish_message(&mut history, &mut body)?;
+    if history.is_empty() {
+        bail!(
+            "No messages found; expected each one to start with a {} or {} line, as /export writes them",
+            USER_HEADER,
+            ASSISTANT_HEADER
+        );
+    }
+    Ok(history)
+}
+
+/// Moves the text read so far into the last message, which mustn't be empty.
+fn finish_message(history: &mut [Message], body: &mut String) -> Result<()> {
+    let Some(message) = history.last_mut() else { return Ok(()) };
+    let content = body.trim_matches('\n').trim_end();
+    if content.trim().is_empty() {
+        let header = if message.role == "assistant" { ASSISTANT_HEADER } else { USER_HEADER };
+        bail!("Message {} ({}) has no text under it", history.len(), header);
+    }
+    message.content = content.to_string();
+    body.clear();
+    Ok(())
+}
+
+/// Reads a transcript written by `/export`.
+pub fn import(path: &Path) -> Result<Vec<Message>> {
+    let markdown = std::fs::read_to_string(path).with_context(|| format!("Failed to read {:?}", path))?;
+    from_markdown(&markdown).with_context(|| format!("{} is not an exported conversation", path.display()))
+}
+
+/// Asks whether to replace the file at `path`. Anything but yes keeps it.
+pub fn confirm_overwrite(path: &Path, mut input: impl BufRead, mut output: impl Write) -> Result<bool> {
+    write!(output, "{} already exists. Overwrite it? [y/N] ", path.display())?;
+    output.flush()?;
+
+    let mut line = String::new();
+    input.read_line(&mut line)?;
+    Ok(matches!(line.trim().to_ascii_lowercase().as_str(), "y" | "yes"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_to_markdown() {
+        let history = vec![
+            Message {
+                role: "user".to_string(),
+                content: "What is a closure?".to_string(),
+            },
+            Message {
+                role: "assistant".to_string(),
+                content: "A function that captures its environment.\n\n```rust\nlet add = |x| x + n;\n```\n".to_string(),
+            },
+        ];
+        let exported_at = OffsetDateTime::from_unix_timestamp(1_700_000_000).unwrap();
+
+        assert_eq!(
+            to_markdown(&history, exported_at),
+            "# monk-manager conversation\n\n_Exported 2023-11-14T22:13:20Z_\n\
+             \n**You:**\n\nWhat is a closure?\n\
+             \n**Assistant:**\n\nA function that captures its environment.\n\n```rust\nlet add = |x| x + n;\n```\n"
+        );
+    }
+
+    #[test]
+    fn test_from_markdown_reads_an_export_back() {
+        let history = vec![
+            Message {
+                role: "user".to_string(),
+                content: "Explain this:\n\n    indented code".to_string(),
+            },
+            Message {
+                role: "assistant".to_string(),
+                content: "It prints **You:** inline.".to_string(),
+            },
+        ];
+        let exported_at = OffsetDateTime::from_unix_timestamp(0).unwrap();
+        let read = from_markdown(&to_markdown(&history, exported_at)).unwrap();
+        assert_eq!(read.len(), 2);
+        assert_eq!(read[0].content, history[0].content);
+        assert_eq!(read[1].content, history[1].content);
+
+        // Hand-edited spacing is fine
+        let read = from_markdown("\n  **you:**  \nHi\n\n\n**Assistant:**\n\n\nHello\n\n").unwrap();
+        assert_eq!((read[0].content.as_str(), read[1].content.as_str()), ("Hi", "Hello"));
+    }
+
+    #[test]
+    fn test_from_markdown_rejects_other_files() {
+        let error = from_markdown("# Notes\n\nSome text\n**You:**\nHi\n").unwrap_err();
+        assert!(error.to_string().starts_with("Line 3 isn't part of a message"), "{}", error);
+
+        let error = from_markdown("# Notes\n").unwrap_err();
+        assert!(error.to_string().starts_with("No messages found"), "{}", error);
+
+        let error = from_markdown("**You:**\nHi\n**Assistant:**\n\n").unwrap_err();
+        assert_eq!(error.to_string(), "Message 2 (**Assistant:**) has no text under it");
+    }
+
+    #[test]
+    fn test_confirm_overwrite_defaults_to_no() {
+        let path = Path::new("notes.md");
+        assert!(confirm_overwrite(path, "y\n".as_bytes(), Vec::new()).unwrap());
+        assert!(!confirm_overwrite(path, "\n".as_bytes(), Vec::new()).unwrap());
+        assert!(!confirm_overwrite(path, "nope\n".as_bytes(), Vec::new()).unwrap());
+    }
+}
@@ -0,0 +1,81 @@
+/// Note for a run of source lines that was left out of a prompt, so the model
+/// caveats its answer instead of guessing at the missing code.
+pub fn omitted_lines_note(first: usize, last: usize) -> String {
+    format!(
+        "[Note: {} lines were omitted between line {} and line {}.]",
+        last - first + 1,
+        first,
+        last
+    )
+}
+
+/// Note for one part of a file that is explained piece by piece.
+pub fn partial_file_note(part: usize, parts: usize, first: usize, last: usize) -> String {
+    format!(
+        "[Note: this is part {} of {} of the file (lines {}-{}); the other parts are explained separately.]",
+        part, parts, first, last
+    )
+}
+
+/// Note for content cut from the end of a message.
+pub fn omitted_bytes_note(bytes: usize) -> String {
+    format!("[Note: {} bytes were omitted from the end of this message.]", bytes)
+}
+
+/// Note for earlier messages of a conversation that were left out.
+pub fn omitted_messages_note(count: usize) -> String {
+    format!("[Note: {} earlier messages were omitted from this conversation.]", count)
+}
+
+/// Splits an `omitted_messages_note` off the start of `content`, returning
+/// the count it gives (0 without one) and the rest of the content.
+pub fn split_omitted_messages_note(content: &str) -> (usize, &str) {
+    content
+        .split_once("\n\n")
+        .and_then(|(first, rest)| {
+            let number = first
+                .strip_prefix("[Note: ")?
+                .strip_suffix(" earlier messages were omitted from this conversation.]")?;
+            Some((number.parse().ok()?, rest))
+        })
+        .unwrap_or((0, content))
+}
+
+/// Puts an `omitted_messages_note` for `count` more messages at the start of
+/// `content`, adding to the count of a note that is already there rather than
+/// stacking a second one.
+pub fn prepend_omitted_messages_note(content: &str, count: usize) -> String {
+    let (earlier, rest) = split_omitted_messages_note(content);
+    format!("{}\n\n{}", omitted_messages_note(earlier + count), rest)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_notes_are_consistent() {
+        assert_eq!(
+            omitted_lines_note(1, 9),
+            "[Note: 9 lines were omitted between line 1 and line 9.]"
+        );
+        assert_eq!(
+            omitted_bytes_note(400),
+            "[Note: 400 bytes were omitted from the end of this message.]"
+        );
+        assert_eq!(
+            omitted_messages_note(2),
+            "[Note: 2 earlier messages were omitted from this conversation.]"
+        );
+    }
+
+    #[test]
+    fn test_omitted_messages_notes_add_up() {
+        let noted = prepend_omitted_messages_note("hello", 2);
+        assert_eq!(noted, format!("{}\n\nhello", omitted_messages_note(2)));
+        assert_eq!(
+            prepend_omitted_messages_note(&noted, 3),
+            format!("{}\n\nhello", omitted_messages_note(5))
+        );
+    }
+}
@@ -0,0 +1,225 @@
+use anyhow::{Context, Result};
+use clap::Args;
+use serde::Deserialize;
+use std::io::{self, BufRead, Write};
+use std::path::Path;
+
+use super::spinner::Spinner;
+use crate::ai::{http_client, HttpOptions};
+
+/// Where new versions are published.
+const LATEST_RELEASE_URL: &str = "https://api.github.com/repos/Vasu014/monk-manager/releases/latest";
+
+#[derive(Args, Debug)]
+pub struct UpdateArgs {
+    /// Install the new version without asking first
+    #[arg(short, long)]
+    pub yes: bool,
+}
+
+/// A GitHub release, as much of it as `update` needs.
+#[derive(Debug, Deserialize)]
+struct Release {
+    tag_name: String,
+    #[serde(default)]
+    assets: Vec<Asset>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Asset {
+    name: String,
+    browser_download_url: String,
+}
+
+/// Replaces the running binary with the latest release when that is newer.
+/// Like `completions`, this works without a configuration; a proxy comes
+/// from `HTTPS_PROXY`.
+pub async fn execute(args: UpdateArgs) -> Result<()> {
+    let client = http_client(&HttpOptions::default())?;
+    let release = {
+        let _spinner = Spinner::start("Checking for updates...");
+        latest_release(&client, LATEST_RELEASE_URL).await?
+    };
+    let current = env!("CARGO_PKG_VERSION");
+    let latest = release.tag_name.trim_start_matches('v');
+    println!("Current version: {}", current);
+    println!("Latest version:  {}", latest);
+    if !is_newer(latest, current) {
+        println!("monk-manager is up to date.");
+        return Ok(());
+    }
+
+    let target = env!("MONK_TARGET");
+    let asset = asset_for(&release, target)
+        .with_context(|| format!("Release {} has no binary for {}", release.tag_name, target))?;
+    if !args.yes && !confirm_update(latest, io::stdin().lock(), io::stdout())? {
+        println!("Not updated.");
+        return Ok(());
+    }
+
+    let binary = {
+        let _spinner = Spinner::start("Downloading...");
+        download(&client, &asset.browser_download_url).await?
+    };
+    let exe = std::env::current_exe().context("Could not find the running binary")?;
+    replace_binary(&exe, &binary)?;
+    println!("Updated monk-manager to {}.", latest);
+    Ok(())
+}
+
+async fn latest_release(client: &reqwest::Client, url: &str) -> Result<Release> {
+    let response = client
+        .get(url)
+        // GitHub's API turns away requests without one
+        .header("User-Agent", concat!("monk-manager/", env!("CARGO_PKG_VERSION")))
+        .header("Accept", "application/vnd.github+json")
+        .send()
+        .await
+        .context("Failed to reach GitHub to check for updates")?;
+    let status = response.status();
+    if !status.is_success() {
+        anyhow::bail!("GitHub returned {} when asked for the latest release", status);
+    }
+    response.json().await.context("Failed to read the latest release from GitHub")
+}
+
+async fn download(client: &reqwest::Client, url: &str) -> Result<Vec<u8>> {
+    let response = client
+        .get(url)
+        .header("User-Agent", concat!("monk-manager/", env!("CARGO_PKG_VERSION")))
+        .send()
+        .await
+        .context("Failed to download the new version")?;
+    let status = response.status();
+    if !status.is_success() {
+        anyhow::bail!("Downloading the new version failed with {}", status);
+    }
+    Ok(response.bytes().await.context("Failed to download the new version")?.to_vec())
+}
+
+/// Whether `latest` is a later `major.minor.patch` than `current`. A version
+/// that doesn't parse is never newer.
+fn is_newer(latest: &str, current: &str) -> bool {
+    fn parse(version: &str) -> Option<(u64, u64, u64)> {
+        let mut parts = version.split(['.', '-', '+']).map(str::parse::<u64>);
+        Some((parts.next()?.ok()?, parts.next()?.ok()?, parts.next()?.ok()?))
+    }
+    match (parse(latest), parse(current)) {
+        (Some(latest), Some(current)) => latest > current,
+        _ => false,
+    }
+}
+
+/// The release binary built for `target`, such as `x86_64-unknown-linux-gnu`.
+/// Archives and checksums are passed over; only the bare binary can be swapped in.
+fn asset_for<'a>(release: &'a Release, target: &str) -> Option<&'a Asset> {
+    release.assets.iter().find(|asset| {
+        let name = asset.name.strip_suffix(".exe").unwrap_or(&asset.name);
+        name.ends_with(target) && !target.is_empty()
+    })
+}
+
+/// Asks before installing. Anything but yes leaves the current version.
+fn confirm_update(latest: &str, mut input: impl BufRead, mut output: impl Write) -> Result<bool> {
+    write!(output, "Install monk-manager {}? [y/N] ", latest)?;
+    output.flush()?;
+
+    let mut line = String::new();
+    input.read_line(&mut line)?;
+    Ok(matches!(line.trim().to_ascii_lowercase().as_str(), "y" | "yes"))
+}
+
+/// Swaps `binary` in for the file at `exe`. The new file is written next to
+/// it and renamed into place, so a failed download never leaves half a binary.
+fn replace_binary(exe: &Path, binary: &[u8]) -> Result<()> {
+    let staged = exe.with_extension("new");
+    std::fs::write(&staged, binary).with_context(|| format!("Failed to write {:?}", staged))?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(&staged, std::fs::Permissions::from_mode(0o755))
+            .with_context(|| format!("Failed to make {:?} executable", staged))?;
+    }
+    // Windows won't replace a running executable, but it will rename one
+    #[cfg(windows)]
+    std::fs::rename(exe, exe.with_extension("old")).with_context(|| format!("Failed to move {:?} aside", exe))?;
+    std::fs::rename(&staged, exe).with_context(|| format!("Failed to replace {:?}", exe))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wiremock::{
+        matchers::{header_exists, method, path},
+        Mock, MockServer, ResponseTemplate,
+    };
+
+    #[test]
+    fn test_is_newer() {
+        assert!(is_newer("0.2.0", "0.1.9"));
+        assert!(is_newer("1.0.0", "0.12.3"));
+        assert!(!is_newer("0.1.0", "0.1.0"));
+        assert!(!is_newer("0.0.9", "0.1.0"));
+        assert!(!is_newer("nightly", "0.1.0"));
+    }
+
+    #[test]
+    fn test_asset_for_target() {
+        let asset = |name: &str| Asset {
+            name: name.to_string(),
+            browser_download_url: format!("https://example.com/{}", name),
+        };
+        let release = Release {
+            tag_name: "v0.2.0".to_string(),
+            assets: vec![
+                asset("monk-manager-x86_64-unknown-linux-gnu.tar.gz"),
+                asset("monk-manager-x86_64-unknown-linux-gnu"),
+                asset("monk-manager-x86_64-pc-windows-msvc.exe"),
+            ],
+        };
+        let found = |target| asset_for(&release, target).map(|asset| asset.name.as_str());
+        assert_eq!(found("x86_64-unknown-linux-gnu"), Some("monk-manager-x86_64-unknown-linux-gnu"));
+        assert_eq!(found("x86_64-pc-windows-msvc"), Some("monk-manager-x86_64-pc-windows-msvc.exe"));
+        assert_eq!(found("aarch64-apple-darwin"), None);
+        assert_eq!(found(""), None);
+    }
+
+    #[tokio::test]
+    async fn test_latest_release() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/releases/latest"))
+            .and(header_exists("user-agent"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "tag_name": "v0.3.1",
+                "assets": [{"name": "monk-manager-aarch64-apple-darwin", "browser_download_url": "https://example.com/a"}]
+            })))
+            .mount(&server)
+            .await;
+
+        let client = reqwest::Client::new();
+        let release = latest_release(&client, &format!("{}/releases/latest", server.uri())).await.unwrap();
+        assert_eq!(release.tag_name, "v0.3.1");
+        assert_eq!(release.assets.len(), 1);
+
+        let error = latest_release(&client, &format!("{}/missing", server.uri())).await.unwrap_err();
+        assert!(error.to_string().contains("404"), "{}", error);
+    }
+
+    #[test]
+    fn test_replace_binary() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let exe = temp_dir.path().join("monk-manager");
+        std::fs::write(&exe, "old").unwrap();
+
+        replace_binary(&exe, b"new").unwrap();
+        assert_eq!(std::fs::read(&exe).unwrap(), b"new");
+        assert!(!exe.with_extension("new").exists());
+    }
+
+    #[test]
+    fn test_confirm_update_defaults_to_no() {
+        assert!(confirm_update("0.2.0", "yes\n".as_bytes(), Vec::new()).unwrap());
+        assert!(!confirm_update("0.2.0", "\n".as_bytes(), Vec::new()).unwrap());
+    }
+}
@@ -0,0 +1,49 @@
+use anyhow::Result;
+use serde::Serialize;
+
+use crate::ai::supported_providers;
+
+/// Build and capability information for tools that wrap monk-manager.
+#[derive(Debug, Serialize)]
+pub struct VersionInfo {
+    pub version: &'static str,
+    pub git_sha: &'static str,
+    pub build_date: &'static str,
+    pub supported_providers: Vec<&'static str>,
+}
+
+impl VersionInfo {
+    pub fn current() -> Self {
+        Self {
+            version: env!("CARGO_PKG_VERSION"),
+            git_sha: env!("MONK_GIT_SHA"),
+            build_date: env!("MONK_BUILD_DATE"),
+            supported_providers: supported_providers(),
+        }
+    }
+}
+
+/// Prints the version information as a single JSON object.
+pub fn print_json() -> Result<()> {
+    println!("{}", serde_json::to_string_pretty(&VersionInfo::current())?);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_version_json_contains_crate_version() {
+        let json = serde_json::to_string(&VersionInfo::current()).unwrap();
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(value["version"], env!("CARGO_PKG_VERSION"));
+        assert!(value["git_sha"].is_string());
+        assert!(value["build_date"].is_string());
+        assert!(value["supported_providers"]
+            .as_array()
+            .unwrap()
+            .contains(&serde_json::json!("anthropic")));
+    }
+}
@@ -0,0 +1,98 @@
+use std::path::{Path, PathBuf};
+use tracing::debug;
+
+/// Directories that are skipped even when no ignore file mentions them.
+const SKIPPED_DIRS: [&str; 3] = ["target", "node_modules", "dist"];
+
+/// A file or directory found by `walk`.
+#[derive(Debug, PartialEq)]
+pub struct Entry {
+    pub path: PathBuf,
+    /// 1 for entries directly under the root, 2 for theirs, and so on
+    pub depth: usize,
+    pub is_dir: bool,
+}
+
+/// Lists everything under `root` depth first, in name order, down to
+/// `max_depth` levels when given. With `respect_ignore`, entries matched by
+/// `.gitignore`, `.ignore` or the global git excludes are skipped, along with
+/// hidden entries and common build output directories; without it, nothing
+/// but `.git` is. Entries that can't be read are left out.
+pub fn walk(root: &Path, max_depth: Option<usize>, respect_ignore: bool) -> Vec<Entry> {
+    let mut builder = ignore::WalkBuilder::new(root);
+    builder
+        .max_depth(max_depth)
+        .standard_filters(respect_ignore)
+        .require_git(false)
+        .sort_by_file_name(|a, b| a.cmp(b))
+        .filter_entry(move |entry| {
+            let name = entry.file_name();
+            let is_dir = entry.file_type().is_some_and(|kind| kind.is_dir());
+            !(is_dir && (name == ".git" || respect_ignore && SKIPPED_DIRS.iter().any(|dir| name == *dir)))
+        });
+
+    builder
+        .build()
+        .filter_map(|entry| match entry {
+            Ok(entry) => Some(entry),
+            Err(e) => {
+                debug!("Skipping an entry while walking {:?}: {}", root, e);
+                None
+            }
+        })
+        .filter(|entry| entry.depth() > 0)
+        .map(|entry| Entry {
+            is_dir: entry.file_type().is_some_and(|kind| kind.is_dir()),
+            depth: entry.depth(),
+            path: entry.into_path(),
+        })
+        .collect()
+}
+
+/// The files under `root`, following the same rules as `walk`.
+pub fn files(root: &Path, respect_ignore: bool) -> Vec<PathBuf> {
+    walk(root, None, respect_ignore)
+        .into_iter()
+        .filter(|entry| !entry.is_dir)
+        .map(|entry| entry.path)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_walk_respects_ignore_rules() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let root = temp_dir.path();
+        std::fs::write(root.join(".gitignore"), "*.log\ngenerated/\n").unwrap();
+        std::fs::create_dir_all(root.join("src")).unwrap();
+        std::fs::write(root.join("src/main.rs"), "fn main() {}").unwrap();
+        std::fs::write(root.join("debug.log"), "noise").unwrap();
+        std::fs::create_dir_all(root.join("generated")).unwrap();
+        std::fs::write(root.join("generated/bindings.rs"), "").unwrap();
+        std::fs::create_dir_all(root.join("node_modules/left-pad")).unwrap();
+        std::fs::write(root.join("node_modules/left-pad/index.js"), "").unwrap();
+        std::fs::create_dir_all(root.join(".git")).unwrap();
+        std::fs::write(root.join(".git/HEAD"), "ref: refs/heads/main").unwrap();
+
+        assert_eq!(files(root, true), vec![root.join("src/main.rs")]);
+
+        let everything = files(root, false);
+        assert!(everything.contains(&root.join("debug.log")));
+        assert!(everything.contains(&root.join("generated/bindings.rs")));
+        assert!(everything.contains(&root.join("node_modules/left-pad/index.js")));
+        assert!(!everything.contains(&root.join(".git/HEAD")));
+
+        let top = walk(root, Some(1), true);
+        assert_eq!(
+            top,
+            vec![Entry {
+                path: root.join("src"),
+                depth: 1,
+                is_dir: true,
+            }]
+        );
+    }
+}
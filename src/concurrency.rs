@@ -0,0 +1,83 @@
+use futures::stream::{self, Stream, StreamExt};
+use std::future::Future;
+
+/// Default number of model requests allowed in flight at once when a command
+/// fans out (multiple files, multiple models, ...).
+pub const DEFAULT_MAX_CONCURRENCY: usize = 4;
+
+/// Runs `f` over every item with at most `limit` futures in flight at once.
+/// Results are returned in the same order as the input items.
+pub async fn run_bounded<I, T, F, Fut>(items: I, limit: usize, f: F) -> Vec<T>
+where
+    I: IntoIterator,
+    F: FnMut(I::Item) -> Fut,
+    Fut: Future<Output = T>,
+{
+    stream::iter(items)
+        .map(f)
+        .buffered(limit.max(1))
+        .collect()
+        .await
+}
+
+/// Like `run_bounded`, but yields each result as soon as it is ready, with
+/// the index of the item it came from.
+pub fn run_as_completed<I, T, F, Fut>(items: I, limit: usize, mut f: F) -> impl Stream<Item = (usize, T)>
+where
+    I: IntoIterator,
+    F: FnMut(I::Item) -> Fut,
+    Fut: Future<Output = T>,
+{
+    stream::iter(items.into_iter().enumerate())
+        .map(move |(i, item)| {
+            let future = f(item);
+            async move { (i, future.await) }
+        })
+        .buffer_unordered(limit.max(1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_run_bounded_preserves_order() {
+        let results = run_bounded(vec![30u64, 10, 20], 3, |delay| async move {
+            tokio::time::sleep(Duration::from_millis(delay)).await;
+            delay
+        })
+        .await;
+
+        assert_eq!(results, vec![30, 10, 20]);
+    }
+
+    #[tokio::test]
+    async fn test_run_as_completed_yields_fastest_first() {
+        let results: Vec<(usize, u64)> = run_as_completed(vec![30u64, 10, 20], 3, |delay| async move {
+            tokio::time::sleep(Duration::from_millis(delay)).await;
+            delay
+        })
+        .collect()
+        .await;
+
+        assert_eq!(results, vec![(1, 10), (2, 20), (0, 30)]);
+    }
+
+    #[tokio::test]
+    async fn test_run_bounded_respects_limit() {
+        let in_flight = AtomicUsize::new(0);
+        let peak = AtomicUsize::new(0);
+
+        run_bounded(0..10, 2, |_| async {
+            let current = in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+            peak.fetch_max(current, Ordering::SeqCst);
+            tokio::time::sleep(Duration::from_millis(5)).await;
+            in_flight.fetch_sub(1, Ordering::SeqCst);
+        })
+        .await;
+
+        assert!(peak.load(Ordering::SeqCst) <= 2);
+    }
+}
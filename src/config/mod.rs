@@ -4,10 +4,20 @@ use std::{
     env,
     fs::File,
     path::{Path, PathBuf},
+    sync::OnceLock,
 };
-use tracing::debug;
+use tracing::{debug, warn};
 
-use crate::ai::ModelConfig;
+use crate::{
+    ai::{EmbeddingConfig, ModelConfig, PromptTemplates},
+    concurrency::DEFAULT_MAX_CONCURRENCY,
+    error::ConfigError,
+};
+
+const CONFIG_NAMES: [&str; 4] = ["monk.toml", "monk.json", "monk.yaml", "monk.yml"];
+
+/// The config file given with `--config`, which overrides the search.
+static EXPLICIT_PATH: OnceLock<PathBuf> = OnceLock::new();
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Config {
@@ -16,6 +26,12 @@ pub struct Config {
     pub commands: CommandsConfig,
     pub security: SecurityConfig,
     pub repository_home: Option<String>,
+    /// The embeddings model used by `index`; not needed for anything else
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub embeddings: Option<EmbeddingConfig>,
+    /// House-style prompts used instead of the built-in ones
+    #[serde(default)]
+    pub prompts: PromptTemplates,
     #[serde(skip)] // Don't serialize this path to the config file itself
     pub config_file_path: Option<PathBuf>,
 }
@@ -34,34 +50,174 @@ pub struct CommandsConfig {
     pub default_format: String,
     pub timeout: u64,
     pub explain: ExplainConfig,
+    /// Upper bound on the bytes of conversation history kept in memory
+    #[serde(default = "default_max_history_bytes")]
+    pub max_history_bytes: usize,
+    /// Tell the model when code or history it is shown has been cut down
+    #[serde(default = "default_true")]
+    pub truncation_notices: bool,
+    /// How to fix up a history whose user/assistant turns no longer alternate
+    #[serde(default)]
+    pub role_repair: RoleRepair,
+    /// Sniff and transcode files that aren't UTF-8 instead of rejecting them
+    #[serde(default = "default_true")]
+    pub detect_encoding: bool,
+    /// Save the interactive conversation, and any open tabs, on exit
+    #[serde(default = "default_true")]
+    pub autosave: bool,
+    /// End an interactive session after this many seconds without input (0 = never)
+    #[serde(default)]
+    pub idle_timeout_secs: u64,
+    /// How many times a failed AI request is retried in interactive mode
+    #[serde(default = "default_max_retries")]
+    pub max_retries: u32,
+    /// Rough token budget for a chat request; older turns are left out to fit
+    #[serde(default = "default_max_context_tokens")]
+    pub max_context_tokens: usize,
+    /// Shown beside the spinner while waiting for an answer; empty for none
+    #[serde(default = "default_thinking_message")]
+    pub thinking_message: String,
+    /// Color of the thinking message: red, green, yellow, blue, magenta, cyan or white
+    #[serde(default = "default_thinking_color")]
+    pub thinking_color: String,
+    /// Most explain requests in flight at once when explaining several files
+    #[serde(default = "default_max_concurrency")]
+    pub max_concurrency: usize,
+}
+
+impl Default for CommandsConfig {
+    fn default() -> Self {
+        Self {
+            default_language: "rust".to_string(),
+            default_format: "markdown".to_string(),
+            timeout: 30,
+            explain: ExplainConfig {
+                max_context_lines: 10,
+                language_detection: true,
+                max_file_bytes: default_max_file_bytes(),
+            },
+            max_history_bytes: default_max_history_bytes(),
+            truncation_notices: true,
+            role_repair: RoleRepair::Merge,
+            detect_encoding: true,
+            autosave: true,
+            idle_timeout_secs: 0,
+            max_retries: default_max_retries(),
+            max_context_tokens: default_max_context_tokens(),
+            thinking_message: default_thinking_message(),
+            thinking_color: default_thinking_color(),
+            max_concurrency: default_max_concurrency(),
+        }
+    }
+}
+
+/// Strategy for repairing adjacent messages that share a role.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RoleRepair {
+    /// Join the messages into one, separated by a blank line
+    #[default]
+    Merge,
+    /// Insert a short message of the other role between them
+    Bridge,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_max_context_tokens() -> usize {
+    100_000
+}
+
+fn default_thinking_message() -> String {
+    "Thinking...".to_string()
+}
+
+fn default_thinking_color() -> String {
+    "yellow".to_string()
+}
+
+fn default_max_retries() -> u32 {
+    3
+}
+
+fn default_max_concurrency() -> usize {
+    DEFAULT_MAX_CONCURRENCY
+}
+
+fn default_max_history_bytes() -> usize {
+    2 * 1024 * 1024
+}
+
+fn default_max_file_bytes() -> u64 {
+    1024 * 1024
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ExplainConfig {
     pub max_context_lines: usize,
     pub language_detection: bool,
+    /// Files larger than this are refused rather than sent to the model whole
+    #[serde(default = "default_max_file_bytes")]
+    pub max_file_bytes: u64,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SecurityConfig {
     pub secrets_file: Option<PathBuf>,
+    /// Rewrite absolute paths in context sent to the model relative to
+    /// `repository_home`, or with the home directory shown as `~`
+    #[serde(default)]
+    pub redact_paths: bool,
 }
 
 impl Config {
+    /// Makes every later `load` read `path`, as given with `--config`.
+    pub fn use_path(path: PathBuf) {
+        let _ = EXPLICIT_PATH.set(path);
+    }
+
     pub fn load() -> Result<Self> {
-        let config_path = Self::find_config_file()?;
-        debug!("Loading configuration from: {:?}", config_path);
+        Self::load_from(EXPLICIT_PATH.get().map(PathBuf::as_path))
+    }
 
-        let config = match config_path.extension().and_then(|ext| ext.to_str()) {
-            Some("toml") => Self::load_toml(&config_path)?,
-            Some("json") => Self::load_json(&config_path)?,
-            Some("yaml") | Some("yml") => Self::load_yaml(&config_path)?,
-            _ => anyhow::bail!("Unsupported configuration file format"),
+    /// Loads the config from `explicit` when given, which must exist, or
+    /// else from the first config file found.
+    pub fn load_from(explicit: Option<&Path>) -> Result<Self> {
+        let config = Self::read(explicit)?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Loads the config like `load` but without validating it, so `doctor`
+    /// can report on one that `load` would reject.
+    pub fn load_unchecked() -> Result<Self> {
+        Self::read(EXPLICIT_PATH.get().map(PathBuf::as_path))
+    }
+
+    /// The file `load` reads: the `--config` path, or else the first config
+    /// file found, or where a new one would go.
+    pub fn path() -> Result<PathBuf> {
+        match EXPLICIT_PATH.get() {
+            Some(path) => Ok(path.clone()),
+            None => Self::find_config_file(),
+        }
+    }
+
+    /// Reads and parses the config file, with environment overrides applied.
+    fn read(explicit: Option<&Path>) -> Result<Self> {
+        let config_path = match explicit {
+            Some(path) if !path.exists() => {
+                return Err(ConfigError::LoadError(format!("Config file not found: {:?}", path)).into())
+            }
+            Some(path) => path.to_path_buf(),
+            None => Self::find_config_file()?,
         };
+        debug!("Loading configuration from: {:?}", config_path);
 
         // Apply environment variable overrides
-        let config = config.apply_env_overrides()?;
-        config.validate()?;
+        let config = Self::parse(&config_path)?.apply_env_overrides()?;
 
         let mut config_with_path = config;
         config_with_path.config_file_path = Some(config_path);
@@ -69,6 +225,71 @@ impl Config {
         Ok(config_with_path)
     }
 
+    /// Parses the file at `path` as it is, without environment overrides.
+    fn parse(path: &Path) -> Result<Self> {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("toml") => Self::load_toml(path),
+            Some("json") => Self::load_json(path),
+            Some("yaml") | Some("yml") => Self::load_yaml(path),
+            _ => Err(ConfigError::LoadError(format!("Unsupported configuration file format: {:?}", path)).into()),
+        }
+    }
+
+    /// Changes one setting in the config file and saves it, returning the
+    /// config as it now takes effect. Only what the file held is written
+    /// back, so a key from `ANTHROPIC_API_KEY` stays out of it.
+    pub fn update_file(&self, path: &str, value: &str) -> Result<Self> {
+        let file = self
+            .config_file_path
+            .clone()
+            .ok_or_else(|| anyhow::anyhow!("Config file path not set, cannot save."))?;
+        let mut saved = Self::parse(&file)?;
+        saved.set_value(path, value)?;
+
+        let mut effective = saved.clone().apply_env_overrides()?;
+        effective.validate()?;
+
+        saved.config_file_path = Some(file.clone());
+        saved.save()?;
+        effective.config_file_path = Some(file);
+        Ok(effective)
+    }
+
+    /// Sets the setting at a dotted `path` such as `ai.temperature` or
+    /// `commands.explain.max_file_bytes`. `value` is read as YAML, so numbers,
+    /// booleans and `null` keep their type and anything else is a string.
+    pub fn set_value(&mut self, path: &str, value: &str) -> Result<()> {
+        let unknown = || ConfigError::Invalid(format!("There is no setting called {}", path));
+        let value: serde_json::Value =
+            serde_yaml::from_str(value).unwrap_or_else(|_| serde_json::Value::String(value.to_string()));
+
+        let mut tree = serde_json::to_value(&*self)?;
+        let (parents, last) = match path.rsplit_once('.') {
+            Some((parents, last)) => (Some(parents), last),
+            None => (None, path),
+        };
+        let mut node = &mut tree;
+        for key in parents.into_iter().flat_map(|parents| parents.split('.')) {
+            node = node.get_mut(key).ok_or_else(unknown)?;
+        }
+        // Unset optional settings aren't serialized, so the last key may be new
+        node.as_object_mut().ok_or_else(unknown)?.insert(last.to_string(), value.clone());
+
+        let mut updated: Config = serde_json::from_value(tree)
+            .map_err(|e| ConfigError::Invalid(format!("Invalid value for {}: {}", path, e)))?;
+        // A key serde doesn't know is dropped rather than refused, so look for it
+        let written = path
+            .split('.')
+            .try_fold(serde_json::to_value(&updated)?, |node, key| node.get(key).cloned());
+        if written.is_none() && !value.is_null() {
+            return Err(unknown().into());
+        }
+
+        updated.config_file_path = self.config_file_path.take();
+        *self = updated;
+        Ok(())
+    }
+
     fn find_config_file() -> Result<PathBuf> {
         // First check environment variable
         if let Ok(path) = env::var("MONK_CONFIG") {
@@ -78,17 +299,19 @@ impl Config {
             }
         }
 
-        // Then check standard locations
+        // Then walk up from the current directory to the nearest project config
+        if let Some(path) = Self::find_in_ancestors(&env::current_dir()?) {
+            return Ok(path);
+        }
+
+        // Then check the global config directory
         let config_dirs = vec![
-            env::current_dir()?,
             dirs::config_dir().unwrap_or_else(|| PathBuf::from(".")),
             PathBuf::from("."),
         ];
 
-        let config_names = vec!["monk.toml", "monk.json", "monk.yaml", "monk.yml"];
-
         for dir in config_dirs {
-            for name in &config_names {
+            for name in CONFIG_NAMES {
                 let path = dir.join(name);
                 if path.exists() {
                     return Ok(path);
@@ -105,6 +328,24 @@ impl Config {
         Ok(default_path)
     }
 
+    /// Looks for a config file in `start` and each of its ancestors, stopping
+    /// at the first directory that is a git root (or at the filesystem root).
+    fn find_in_ancestors(start: &Path) -> Option<PathBuf> {
+        for dir in start.ancestors() {
+            for name in CONFIG_NAMES {
+                let path = dir.join(name);
+                if path.exists() {
+                    return Some(path);
+                }
+            }
+
+            if dir.join(".git").exists() {
+                break;
+            }
+        }
+        None
+    }
+
     fn load_toml(path: &Path) -> Result<Self> {
         let contents = std::fs::read_to_string(path)
             .with_context(|| format!("Failed to read config file: {:?}", path))?;
@@ -131,6 +372,10 @@ impl Config {
             self.ai.api_key = api_key;
         }
 
+        if let (Ok(api_key), Some(embeddings)) = (env::var("VOYAGE_API_KEY"), self.embeddings.as_mut()) {
+            embeddings.api_key = api_key;
+        }
+
         if let Ok(level) = env::var("MONK_LOG_LEVEL") {
             self.logging.level = level;
         }
@@ -138,22 +383,28 @@ impl Config {
         Ok(self)
     }
 
-    fn validate(&self) -> Result<()> {
-        if self.ai.api_key.is_empty() {
-            anyhow::bail!("AI API key is required");
+    pub fn validate(&self) -> Result<()> {
+        // A provider that makes no requests, like the mock one, needs no key
+        if self.ai.api_key.is_empty() && crate::ai::needs_api_key(&self.ai.provider) {
+            return Err(ConfigError::Missing("AI API key".to_string()).into());
         }
 
+        crate::ai::check_model_name(&self.ai.model_name).map_err(ConfigError::Invalid)?;
+
         if self.ai.max_tokens == 0 {
-            anyhow::bail!("Max tokens must be greater than 0");
+            return Err(ConfigError::Invalid("max_tokens must be greater than 0".to_string()).into());
         }
 
         if !(0.0..=1.0).contains(&self.ai.temperature) {
-            anyhow::bail!("Temperature must be between 0.0 and 1.0");
+            return Err(ConfigError::Invalid("temperature must be between 0.0 and 1.0".to_string()).into());
         }
 
+        self.prompts.validate().map_err(ConfigError::Invalid)?;
+
         Ok(())
     }
 
+    #[allow(dead_code)] // Nothing writes a starter config for the user yet
     fn create_default_config(path: &Path) -> Result<Self> {
         let config = Config {
             ai: ModelConfig {
@@ -163,6 +414,9 @@ impl Config {
                 temperature: 0.7,
                 max_tokens: 1024,
                 api_base_url: None,
+                system_prompt: None,
+                output_language: None,
+                http: Default::default(),
             },
             logging: LoggingConfig {
                 level: "info".to_string(),
@@ -170,19 +424,14 @@ impl Config {
                 output: "stderr".to_string(),
                 file: None,
             },
-            commands: CommandsConfig {
-                default_language: "rust".to_string(),
-                default_format: "markdown".to_string(),
-                timeout: 30,
-                explain: ExplainConfig {
-                    max_context_lines: 10,
-                    language_detection: true,
-                },
-            },
+            commands: CommandsConfig::default(),
             security: SecurityConfig {
                 secrets_file: None,
+                redact_paths: false,
             },
             repository_home: None,
+            embeddings: None,
+            prompts: PromptTemplates::default(),
             config_file_path: Some(path.to_path_buf()),
         };
 
@@ -194,6 +443,19 @@ impl Config {
         Ok(config)
     }
 
+    /// The directory the project lives in: `repository_home` when it is set
+    /// and exists, or else the current directory.
+    pub fn project_root(&self) -> Result<PathBuf> {
+        if let Some(home) = self.repository_home.as_deref() {
+            let home = PathBuf::from(home);
+            if home.is_dir() {
+                return Ok(home);
+            }
+            warn!("repository_home {:?} is not a directory; using the current directory", home);
+        }
+        env::current_dir().context("Failed to get the current directory")
+    }
+
     pub fn save(&self) -> Result<()> {
         let path = self.config_file_path.as_ref().ok_or_else(|| anyhow::anyhow!("Config file path not set, cannot save."))?;
         debug!("Saving configuration to: {:?}", path);
@@ -224,15 +486,12 @@ mod tests {
     use super::*;
     use tempfile::NamedTempFile;
 
-    #[test]
-    fn test_config_validation() {
-        let config = Config {
+    /// A valid config with the built-in settings and a dummy API key.
+    fn test_config() -> Config {
+        Config {
             ai: ModelConfig {
-                provider: "anthropic".to_string(),
                 model_name: "claude-3-5-haiku-20241022".to_string(),
-                api_key: "test-key".to_string(),
-                temperature: 0.7,
-                max_tokens: 1000,
+                ..crate::ai::test_model_config()
             },
             logging: LoggingConfig {
                 level: "info".to_string(),
@@ -240,55 +499,34 @@ mod tests {
                 output: "stderr".to_string(),
                 file: None,
             },
-            commands: CommandsConfig {
-                default_language: "rust".to_string(),
-                default_format: "markdown".to_string(),
-                timeout: 30,
-                explain: ExplainConfig {
-                    max_context_lines: 10,
-                    language_detection: true,
-                },
-            },
+            commands: CommandsConfig::default(),
             security: SecurityConfig {
                 secrets_file: None,
+                redact_paths: false,
             },
             repository_home: None,
+            embeddings: None,
+            prompts: PromptTemplates::default(),
             config_file_path: None,
-        };
+        }
+    }
 
-        assert!(config.validate().is_ok());
+    #[test]
+    fn test_config_validation() {
+        assert!(test_config().validate().is_ok());
     }
 
     #[test]
     fn test_config_validation_failure() {
+        let config = test_config();
         let config = Config {
             ai: ModelConfig {
-                provider: "anthropic".to_string(),
-                model_name: "claude-3-5-haiku-20241022".to_string(),
                 api_key: "".to_string(),
                 temperature: 1.5,
                 max_tokens: 0,
+                ..config.ai
             },
-            logging: LoggingConfig {
-                level: "info".to_string(),
-                format: "pretty".to_string(),
-                output: "stderr".to_string(),
-                file: None,
-            },
-            commands: CommandsConfig {
-                default_language: "rust".to_string(),
-                default_format: "markdown".to_string(),
-                timeout: 30,
-                explain: ExplainConfig {
-                    max_context_lines: 10,
-                    language_detection: true,
-                },
-            },
-            security: SecurityConfig {
-                secrets_file: None,
-            },
-            repository_home: None,
-            config_file_path: None,
+            ..config
         };
 
         assert!(config.validate().is_err());
@@ -343,6 +581,130 @@ mod tests {
         std::env::remove_var("ANTHROPIC_API_KEY");
     }
 
+    #[test]
+    fn test_explicit_config_path() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("ci.json");
+        let config = serde_json::json!({
+            "ai": {
+                "provider": "anthropic",
+                "model_name": "claude-3-5-haiku-20241022",
+                "api_key": "key",
+                "max_tokens": 1000,
+                "temperature": 0.5,
+            },
+            "logging": { "level": "info", "format": "pretty", "output": "stderr" },
+            "commands": {
+                "default_language": "rust",
+                "default_format": "markdown",
+                "timeout": 30,
+                "explain": { "max_context_lines": 10, "language_detection": true },
+            },
+            "security": { "secrets_file": null },
+            "repository_home": null,
+        });
+        std::fs::write(&path, config.to_string()).unwrap();
+
+        let loaded = Config::load_from(Some(&path)).unwrap();
+        assert_eq!(loaded.config_file_path.as_deref(), Some(path.as_path()));
+        assert_eq!(loaded.ai.temperature, 0.5);
+        assert!(loaded.prompts.explain.is_none());
+
+        // A template with a placeholder nothing fills is refused up front
+        let mut templated = config.clone();
+        templated["prompts"] = serde_json::json!({ "explain": "Explain {code} in {lang}" });
+        std::fs::write(&path, templated.to_string()).unwrap();
+        let error = Config::load_from(Some(&path)).unwrap_err();
+        assert!(error.to_string().contains("unknown placeholder {lang}"), "{}", error);
+        assert_eq!(crate::error::error_kind(&error), "config");
+
+        // A path that was asked for by name must exist; there is no falling back
+        let missing = temp_dir.path().join("missing.toml");
+        let error = Config::load_from(Some(&missing)).unwrap_err();
+        assert!(error.to_string().contains("Config file not found"));
+        assert_eq!(crate::error::error_kind(&error), "config");
+    }
+
+    #[test]
+    fn test_set_value_by_dotted_path() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let mut config = Config::create_default_config(&temp_dir.path().join("config.yaml")).unwrap();
+
+        config.set_value("ai.temperature", "0.2").unwrap();
+        config.set_value("commands.explain.max_file_bytes", "2048").unwrap();
+        config.set_value("ai.proxy", "http://proxy:8080").unwrap();
+        config.set_value("repository_home", "/work/app").unwrap();
+        assert_eq!(config.ai.temperature, 0.2);
+        assert_eq!(config.commands.explain.max_file_bytes, 2048);
+        assert_eq!(config.ai.http.proxy.as_deref(), Some("http://proxy:8080"));
+        assert_eq!(config.repository_home.as_deref(), Some("/work/app"));
+        assert!(config.config_file_path.is_some());
+
+        let error = config.set_value("ai.temprature", "0.5").unwrap_err();
+        assert!(error.to_string().contains("no setting called ai.temprature"), "{}", error);
+        assert!(config.set_value("nothing.here", "1").is_err());
+        let error = config.set_value("ai.max_tokens", "lots").unwrap_err();
+        assert!(error.to_string().contains("Invalid value for ai.max_tokens"), "{}", error);
+        assert_eq!(crate::error::error_kind(&error), "config");
+    }
+
+    #[test]
+    fn test_update_file_keeps_environment_keys_out() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("config.yaml");
+        let mut config = Config::create_default_config(&path).unwrap();
+        config.ai.api_key = "key-in-file".to_string();
+        config.save().unwrap();
+
+        let updated = config.update_file("ai.model_name", "claude-sonnet-4-5").unwrap();
+        assert_eq!(updated.ai.model_name, "claude-sonnet-4-5");
+        assert_eq!(updated.config_file_path.as_deref(), Some(path.as_path()));
+        let saved = Config::load_yaml(&path).unwrap();
+        assert_eq!(saved.ai.model_name, "claude-sonnet-4-5");
+        assert_eq!(saved.ai.api_key, "key-in-file");
+
+        // A change that fails validation leaves the file alone
+        assert!(config.update_file("ai.temperature", "3").is_err());
+        assert_eq!(Config::load_yaml(&path).unwrap().ai.temperature, 0.7);
+    }
+
+    #[test]
+    fn test_find_config_in_ancestor_directory() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let project = temp_dir.path().join("project");
+        let nested = project.join("src").join("nested");
+        std::fs::create_dir_all(&nested).unwrap();
+        std::fs::write(project.join("monk.toml"), "").unwrap();
+
+        assert_eq!(Config::find_in_ancestors(&nested), Some(project.join("monk.toml")));
+    }
+
+    #[test]
+    fn test_find_config_stops_at_git_root() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let repo = temp_dir.path().join("repo");
+        let nested = repo.join("crate");
+        std::fs::create_dir_all(&nested).unwrap();
+        std::fs::create_dir_all(repo.join(".git")).unwrap();
+        // Above the git root, so it must not be picked up
+        std::fs::write(temp_dir.path().join("monk.yaml"), "").unwrap();
+
+        assert_eq!(Config::find_in_ancestors(&nested), None);
+    }
+
+    #[test]
+    fn test_project_root_prefers_repository_home() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let mut config = Config::create_default_config(&temp_dir.path().join("config.yaml")).unwrap();
+        assert_eq!(config.project_root().unwrap(), env::current_dir().unwrap());
+
+        config.repository_home = Some(temp_dir.path().display().to_string());
+        assert_eq!(config.project_root().unwrap(), temp_dir.path());
+
+        config.repository_home = Some(temp_dir.path().join("missing").display().to_string());
+        assert_eq!(config.project_root().unwrap(), env::current_dir().unwrap());
+    }
+
     #[test]
     fn test_default_config_creation_and_save() -> Result<()> {
         let temp_dir = tempfile::tempdir()?;
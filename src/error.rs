@@ -1,5 +1,8 @@
+use serde_json::json;
 use thiserror::Error;
 
+pub use crate::ai::AIError;
+
 #[derive(Error, Debug)]
 pub enum MonkError {
     #[error("Configuration error: {0}")]
@@ -27,29 +30,146 @@ pub enum ConfigError {
     LoadError(String),
 }
 
-#[derive(Error, Debug)]
-pub enum AIError {
-    #[error("Model error: {0}")]
-    ModelError(String),
-
-    #[error("Rate limit exceeded")]
-    RateLimitExceeded,
-
-    #[error("Invalid response format: {0}")]
-    InvalidResponse(String),
-
-    #[error("Timeout: {0:?}")]
-    Timeout(std::time::Duration),
+impl MonkError {
+    /// A stable, machine-readable name for the kind of failure.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            MonkError::Config(_) => "config",
+            MonkError::AI(error) => error.kind(),
+            MonkError::Command(_) => "command",
+            MonkError::Io(_) => "io",
+        }
+    }
 }
 
 #[derive(Error, Debug)]
 pub enum CommandError {
-    #[error("Invalid command: {0}")]
-    InvalidCommand(String),
-
     #[error("Missing required argument: {0}")]
     MissingArgument(String),
 
     #[error("Invalid argument value: {0}")]
     InvalidArgument(String),
-} 
\ No newline at end of file
+}
+
+/// A failure that has already been shown to the user, e.g. as JSON on stdout.
+/// `main` exits with status 1 for it without printing it a second time.
+#[derive(Error, Debug)]
+#[error("{0}")]
+pub struct AlreadyReported(pub String);
+
+/// Classifies an error chain into a stable, machine-readable kind by looking
+/// for the first typed error it contains.
+pub fn error_kind(error: &anyhow::Error) -> &'static str {
+    for cause in error.chain() {
+        if let Some(monk_error) = cause.downcast_ref::<MonkError>() {
+            return monk_error.kind();
+        }
+        if let Some(ai_error) = cause.downcast_ref::<AIError>() {
+            return ai_error.kind();
+        }
+        if cause.is::<ConfigError>() {
+            return "config";
+        }
+        if cause.is::<CommandError>() {
+            return "command";
+        }
+        if cause.is::<std::io::Error>() {
+            return "io";
+        }
+        if cause.is::<reqwest::Error>() {
+            return "network";
+        }
+    }
+    "other"
+}
+
+/// Builds the `{ "error": { "kind", "message" } }` object reported by
+/// `--json-errors`, optionally naming the file the failure belongs to.
+pub fn error_json(error: &anyhow::Error, file: Option<&str>) -> serde_json::Value {
+    let mut body = json!({
+        "kind": error_kind(error),
+        "message": format!("{:#}", error),
+    });
+    if let Some(file) = file {
+        body["file"] = json!(file);
+    }
+    json!({ "error": body })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Context;
+    use std::time::Duration;
+
+    #[test]
+    fn test_error_kind_from_chain() {
+        let io = std::fs::read_to_string("/definitely/not/here")
+            .context("Failed to read file")
+            .unwrap_err();
+        assert_eq!(error_kind(&io), "io");
+
+        let timeout = anyhow::Error::new(AIError::Timeout(Duration::from_secs(30)))
+            .context("AI request timed out");
+        assert_eq!(error_kind(&timeout), "timeout");
+
+        let wrapped = anyhow::Error::new(MonkError::from(AIError::RateLimitExceeded));
+        assert_eq!(error_kind(&wrapped), "rate_limit");
+        let config = anyhow::Error::new(ConfigError::Missing("api_key".to_string()));
+        assert_eq!(error_kind(&config), "config");
+
+        assert_eq!(error_kind(&anyhow::anyhow!("plain")), "other");
+    }
+
+    #[test]
+    fn test_error_conversion() {
+        // Test IO error conversion
+        let io_error = std::io::Error::new(std::io::ErrorKind::NotFound, "File not found");
+        let monk_error: MonkError = io_error.into();
+        assert!(matches!(monk_error, MonkError::Io(_)));
+
+        // Test Config error conversion
+        let config_error = ConfigError::Missing("api_key".to_string());
+        let monk_error: MonkError = config_error.into();
+        assert!(matches!(monk_error, MonkError::Config(_)));
+
+        // Test AI error conversion
+        let ai_error = AIError::ModelError("Invalid response".to_string());
+        let monk_error: MonkError = ai_error.into();
+        assert!(matches!(monk_error, MonkError::AI(_)));
+
+        // Test Command error conversion
+        let command_error = CommandError::MissingArgument("--language".to_string());
+        let monk_error: MonkError = command_error.into();
+        assert!(matches!(monk_error, MonkError::Command(_)));
+    }
+
+    #[test]
+    fn test_error_display() {
+        let config_error = ConfigError::Missing("api_key".to_string());
+        assert_eq!(
+            config_error.to_string(),
+            "Missing configuration: api_key"
+        );
+
+        let ai_error = AIError::RateLimitExceeded;
+        assert_eq!(ai_error.to_string(), "Rate limit exceeded");
+        assert_eq!(MonkError::from(ai_error).to_string(), "AI model error: Rate limit exceeded");
+
+        let command_error = CommandError::InvalidArgument("--invalid".to_string());
+        assert_eq!(
+            command_error.to_string(),
+            "Invalid argument value: --invalid"
+        );
+    }
+
+    #[test]
+    fn test_error_context() {
+        let ai_error = AIError::Timeout(Duration::from_secs(30));
+        assert_eq!(
+            ai_error.to_string(),
+            "Timeout: 30s"
+        );
+    }
+}
+
@@ -0,0 +1,251 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::path::{Path, PathBuf};
+
+use crate::{
+    ai::{EmbeddingService, InputType},
+    parse,
+};
+
+/// Characters of source in each embedded chunk.
+const CHUNK_CHARS: usize = 2000;
+
+/// Vectors for every chunk of a codebase, with what's needed to tell which
+/// files changed since they were embedded.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Index {
+    /// The embeddings model the vectors came from; vectors from different
+    /// models can't be compared
+    pub model: String,
+    /// Indexed files, by path relative to the project root
+    pub files: BTreeMap<String, IndexedFile>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct IndexedFile {
+    /// `content_hash` of the file when it was embedded
+    pub hash: String,
+    pub chunks: Vec<IndexedChunk>,
+}
+
+/// One embedded run of lines. Lines are 1-based and inclusive.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct IndexedChunk {
+    pub start_line: usize,
+    pub end_line: usize,
+    pub vector: Vec<f32>,
+}
+
+/// What an `Index::update` did.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct IndexStats {
+    /// Files that were new or changed, and were embedded
+    pub embedded: usize,
+    /// Chunks embedded across those files
+    pub chunks: usize,
+    pub unchanged: usize,
+    /// Files that were indexed before but are gone now
+    pub removed: usize,
+}
+
+/// Where the index of the project at `root` is kept.
+pub fn index_path(root: &Path) -> Option<PathBuf> {
+    let name = format!("{:016x}.json", content_hash(root.to_string_lossy().as_bytes()));
+    dirs::config_dir().map(|dir| dir.join("monk-manager").join("indexes").join(name))
+}
+
+impl Index {
+    /// Reads the index at `path`, or starts an empty one if there is none yet.
+    pub fn load(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let json = std::fs::read_to_string(path).with_context(|| format!("Failed to read index: {:?}", path))?;
+        serde_json::from_str(&json).with_context(|| format!("Failed to parse index: {:?}", path))
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create index directory: {:?}", parent))?;
+        }
+        std::fs::write(path, serde_json::to_string(self)?).with_context(|| format!("Failed to write index: {:?}", path))
+    }
+
+    /// Brings the index up to date with `files`, given as paths relative to
+    /// the project root and their contents. New and changed files are chunked
+    /// and embedded, unchanged ones are kept as they are, and files that are
+    /// no longer there are dropped. A different embeddings model starts the
+    /// index over. Files embedded before a failure stay in the index.
+    pub async fn update(&mut self, service: &EmbeddingService, files: Vec<(String, String)>) -> Result<IndexStats> {
+        let model = &service.config().model;
+        if self.model != *model {
+            self.files.clear();
+            self.model = model.clone();
+        }
+
+        let mut stats = IndexStats::default();
+        let present: HashSet<&str> = files.iter().map(|(path, _)| path.as_str()).collect();
+        let before = self.files.len();
+        self.files.retain(|path, _| present.contains(path.as_str()));
+        stats.removed = before - self.files.len();
+
+        for (path, content) in &files {
+            let hash = format!("{:016x}", content_hash(content.as_bytes()));
+            if self.files.get(path).is_some_and(|file| file.hash == hash) {
+                stats.unchanged += 1;
+                continue;
+            }
+
+            // Each chunk is labelled with where it came from, which helps retrieval
+            let language = parse::detect_language(Some(Path::new(path)), content);
+            let chunks = parse::chunk_code(content, &language, CHUNK_CHARS);
+            let texts: Vec<String> = chunks
+                .iter()
+                .map(|chunk| format!("{}:{}-{}\n{}", path, chunk.start_line, chunk.end_line, chunk.text))
+                .collect();
+            let vectors = service
+                .embed(&texts, InputType::Document)
+                .await
+                .with_context(|| format!("Failed to embed {}", path))?;
+
+            stats.embedded += 1;
+            stats.chunks += chunks.len();
+            let chunks = chunks
+                .into_iter()
+                .zip(vectors)
+                .map(|(chunk, vector)| IndexedChunk {
+                    start_line: chunk.start_line,
+                    end_line: chunk.end_line,
+                    vector,
+                })
+                .collect();
+            self.files.insert(path.clone(), IndexedFile { hash, chunks });
+        }
+        Ok(stats)
+    }
+}
+
+/// 64-bit FNV-1a, which unlike `DefaultHasher` is the same on every run and
+/// every Rust version, so hashes stored in an index stay meaningful.
+fn content_hash(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::{EmbeddingClient, EmbeddingConfig};
+    use async_trait::async_trait;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    /// Embeds every text as its length, counting the texts embedded.
+    struct LengthEmbedder(Arc<AtomicUsize>);
+
+    #[async_trait]
+    impl EmbeddingClient for LengthEmbedder {
+        async fn embed(&self, texts: &[String], _input_type: InputType) -> Result<Vec<Vec<f32>>> {
+            self.0.fetch_add(texts.len(), Ordering::SeqCst);
+            Ok(texts.iter().map(|text| vec![text.len() as f32]).collect())
+        }
+    }
+
+    fn service(model: &str, embedded: Arc<AtomicUsize>) -> EmbeddingService {
+        let config = EmbeddingConfig {
+            provider: "voyage".to_string(),
+            model: model.to_string(),
+            api_key: "test-key".to_string(),
+            api_base_url: None,
+        };
+        EmbeddingService::from_client(Box::new(LengthEmbedder(embedded)), config)
+    }
+
+    fn files(entries: &[(&str, &str)]) -> Vec<(String, String)> {
+        entries
+            .iter()
+            .map(|(path, content)| (path.to_string(), content.to_string()))
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_update_only_embeds_changed_files() {
+        let embedded = Arc::new(AtomicUsize::new(0));
+        let service = service("voyage-code-3", embedded.clone());
+        let mut index = Index::default();
+
+        let stats = index
+            .update(&service, files(&[("src/a.rs", "fn a() {}"), ("src/b.rs", "fn b() {}")]))
+            .await
+            .unwrap();
+        assert_eq!((stats.embedded, stats.chunks), (2, 2));
+        assert_eq!(embedded.load(Ordering::SeqCst), 2);
+
+        // b changes and a new file appears; a is left alone
+        let stats = index
+            .update(
+                &service,
+                files(&[("src/a.rs", "fn a() {}"), ("src/b.rs", "fn b() { 1 }"), ("src/c.rs", "fn c() {}")]),
+            )
+            .await
+            .unwrap();
+        assert_eq!(
+            stats,
+            IndexStats {
+                embedded: 2,
+                chunks: 2,
+                unchanged: 1,
+                removed: 0,
+            }
+        );
+        assert_eq!(embedded.load(Ordering::SeqCst), 4);
+
+        // Deleted files are dropped without embedding anything
+        let stats = index.update(&service, files(&[("src/a.rs", "fn a() {}")])).await.unwrap();
+        assert_eq!((stats.removed, stats.unchanged, stats.embedded), (2, 1, 0));
+        assert_eq!(index.files.keys().collect::<Vec<_>>(), vec!["src/a.rs"]);
+    }
+
+    #[tokio::test]
+    async fn test_new_model_reembeds_everything() {
+        let embedded = Arc::new(AtomicUsize::new(0));
+        let mut index = Index::default();
+        let sources = files(&[("src/a.rs", "fn a() {}")]);
+
+        index.update(&service("voyage-code-3", embedded.clone()), sources.clone()).await.unwrap();
+        let stats = index.update(&service("voyage-3-lite", embedded.clone()), sources).await.unwrap();
+        assert_eq!(stats.embedded, 1);
+        assert_eq!(index.model, "voyage-3-lite");
+    }
+
+    #[test]
+    fn test_index_round_trips() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("indexes").join("project.json");
+        assert!(Index::load(&path).unwrap().files.is_empty());
+
+        let mut index = Index {
+            model: "voyage-code-3".to_string(),
+            ..Default::default()
+        };
+        index.files.insert(
+            "src/a.rs".to_string(),
+            IndexedFile {
+                hash: format!("{:016x}", content_hash(b"fn a() {}")),
+                chunks: vec![IndexedChunk {
+                    start_line: 1,
+                    end_line: 1,
+                    vector: vec![0.5, -0.25],
+                }],
+            },
+        );
+        index.save(&path).unwrap();
+
+        let loaded = Index::load(&path).unwrap();
+        assert_eq!(loaded.model, "voyage-code-3");
+        assert_eq!(loaded.files["src/a.rs"].chunks[0].vector, vec![0.5, -0.25]);
+    }
+}
@@ -1,34 +1,39 @@
-use anyhow::Result;
 use clap::Parser;
-// use tracing::info; // Commented out
+use std::process::ExitCode;
 
 mod ai;
 mod cli;
+mod concurrency;
 mod config;
 mod error;
-// mod tracing; // Commented out
-
-#[derive(Parser)]
-#[command(author, version, about, long_about = None)]
-struct Cli {
-    #[command(subcommand)]
-    command: Option<cli::Commands>,
-}
+mod indexer;
+mod parse;
+mod search;
+mod tracing;
 
 #[tokio::main]
-async fn main() -> Result<()> {
-    // Initialize tracing
-    // tracing::init_tracing()?; // Commented out
-
+async fn main() -> ExitCode {
     // Parse command line arguments
-    let _cli = Cli::parse();
+    let cli = cli::Cli::parse();
+    cli.apply_global_options();
 
-    // Load configuration
-    let _config = config::Config::load()?;
+    // Initialize tracing, following the logging config when there is one; a
+    // missing or broken config is reported by the command that needs it
+    let logging = config::Config::load().ok().map(|config| config.logging);
+    // Held until exit so file logs are flushed
+    let _log_guard = crate::tracing::init_tracing(logging.as_ref(), cli.verbose, cli.quiet).unwrap_or_else(|e| {
+        eprintln!("Warning: could not set up logging: {:#}", e);
+        None
+    });
 
-    // Always run in interactive mode
-    println!("\x1B[33mNote: monk-manager now always starts in interactive mode\x1B[0m");
-    cli::interactive::run_interactive_session().await?;
-
-    Ok(())
-} 
\ No newline at end of file
+    // Dispatch to the requested command (interactive mode when none is given)
+    match cli::execute(cli).await {
+        Ok(()) => ExitCode::SUCCESS,
+        // Already shown in the form the user asked for, e.g. JSON
+        Err(e) if e.is::<error::AlreadyReported>() => ExitCode::FAILURE,
+        Err(e) => {
+            eprintln!("Error: {:?}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
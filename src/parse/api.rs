@@ -0,0 +1,208 @@
+use anyhow::{Context, Result};
+use std::collections::HashSet;
+use syn::{Fields, ImplItem, Item, TraitItem, Type, Visibility};
+
+/// Placeholder body swapped in for function bodies, then printed as `;`.
+const ELIDED_BODY: &str = "__monk_elided_body__";
+
+/// Reduces a Rust source file to its public interface: `pub` items with their
+/// doc comments and attributes, function signatures without bodies, and only
+/// the `pub` fields of structs. Private items, and impls of types that aren't
+/// public here, are dropped.
+pub fn public_api(source: &str) -> Result<String> {
+    let file = syn::parse_file(source).context("Could not parse the file as Rust")?;
+
+    let public_types = file.items.iter().filter_map(public_type_name).collect();
+    let items = public_items(file.items, &public_types);
+    if items.is_empty() {
+        anyhow::bail!("The file has no public items");
+    }
+
+    let printed = prettyplease::unparse(&syn::File {
+        shebang: None,
+        attrs: Vec::new(),
+        items,
+    });
+    Ok(elide_bodies(&printed))
+}
+
+fn public_items(items: Vec<Item>, public_types: &HashSet<String>) -> Vec<Item> {
+    items
+        .into_iter()
+        .filter_map(|item| public_item(item, public_types))
+        .collect()
+}
+
+fn public_item(item: Item, public_types: &HashSet<String>) -> Option<Item> {
+    match item {
+        Item::Fn(mut function) if is_pub(&function.vis) => {
+            *function.block = elided_block();
+            Some(Item::Fn(function))
+        }
+        Item::Struct(mut strukt) if is_pub(&strukt.vis) => {
+            if let Fields::Named(fields) = &mut strukt.fields {
+                fields.named = std::mem::take(&mut fields.named)
+                    .into_pairs()
+                    .map(|pair| pair.into_value())
+                    .filter(|field| is_pub(&field.vis))
+                    .collect();
+            }
+            Some(Item::Struct(strukt))
+        }
+        Item::Trait(mut tr) if is_pub(&tr.vis) => {
+            for item in &mut tr.items {
+                if let TraitItem::Fn(method) = item {
+                    if method.default.is_some() {
+                        method.default = Some(elided_block());
+                    }
+                }
+            }
+            Some(Item::Trait(tr))
+        }
+        Item::Mod(mut module) if is_pub(&module.vis) => {
+            if let Some((_, items)) = module.content.take() {
+                module.content = Some((Default::default(), public_items(items, public_types)));
+            }
+            Some(Item::Mod(module))
+        }
+        Item::Impl(mut imp) => {
+            let self_name = type_name(&imp.self_ty)?;
+            if !public_types.contains(&self_name) {
+                return None;
+            }
+            // Inherent impls only expose their pub items; trait impls expose all
+            let inherent = imp.trait_.is_none();
+            imp.items.retain(|item| match item {
+                ImplItem::Fn(method) => !inherent || is_pub(&method.vis),
+                ImplItem::Const(constant) => !inherent || is_pub(&constant.vis),
+                ImplItem::Type(ty) => !inherent || is_pub(&ty.vis),
+                _ => !inherent,
+            });
+            if inherent && imp.items.is_empty() {
+                return None;
+            }
+            for item in &mut imp.items {
+                if let ImplItem::Fn(method) = item {
+                    method.block = elided_block();
+                }
+            }
+            Some(Item::Impl(imp))
+        }
+        Item::Enum(ref e) if is_pub(&e.vis) => Some(item),
+        Item::Type(ref t) if is_pub(&t.vis) => Some(item),
+        Item::Const(ref c) if is_pub(&c.vis) => Some(item),
+        Item::Static(ref s) if is_pub(&s.vis) => Some(item),
+        Item::Use(ref u) if is_pub(&u.vis) => Some(item),
+        _ => None,
+    }
+}
+
+/// The name of a type declared `pub` by `item`, for matching impls against.
+fn public_type_name(item: &Item) -> Option<String> {
+    let (vis, ident) = match item {
+        Item::Struct(item) => (&item.vis, &item.ident),
+        Item::Enum(item) => (&item.vis, &item.ident),
+        Item::Type(item) => (&item.vis, &item.ident),
+        Item::Trait(item) => (&item.vis, &item.ident),
+        Item::Union(item) => (&item.vis, &item.ident),
+        _ => return None,
+    };
+    is_pub(vis).then(|| ident.to_string())
+}
+
+fn type_name(ty: &Type) -> Option<String> {
+    match ty {
+        Type::Path(path) => path.path.segments.last().map(|segment| segment.ident.to_string()),
+        Type::Reference(reference) => type_name(&reference.elem),
+        _ => None,
+    }
+}
+
+fn is_pub(vis: &Visibility) -> bool {
+    matches!(vis, Visibility::Public(_))
+}
+
+fn elided_block() -> syn::Block {
+    syn::parse_str(&format!("{{ {} }}", ELIDED_BODY)).expect("placeholder body parses")
+}
+
+/// Turns each `{ <marker> }` body, however it was indented, into `;`.
+fn elide_bodies(printed: &str) -> String {
+    let mut result = String::with_capacity(printed.len());
+    let mut rest = printed;
+    while let Some(marker) = rest.find(ELIDED_BODY) {
+        let open = rest[..marker].rfind('{').unwrap_or(marker);
+        let close = rest[marker..].find('}').map_or(marker + ELIDED_BODY.len(), |i| marker + i + 1);
+        result.push_str(rest[..open].trim_end());
+        result.push(';');
+        rest = &rest[close..];
+    }
+    result.push_str(rest);
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_public_api_keeps_only_public_items() {
+        let source = r#"
+use std::collections::HashMap;
+
+/// A cache of parsed documents.
+pub struct Cache {
+    /// Most documents kept at once
+    pub capacity: usize,
+    entries: HashMap<String, String>,
+}
+
+struct Internal;
+
+impl Cache {
+    /// Creates an empty cache.
+    pub fn new(capacity: usize) -> Self {
+        let entries = HashMap::new();
+        Self { capacity, entries }
+    }
+
+    fn evict_oldest(&mut self) {
+        self.entries.clear();
+    }
+}
+
+impl Internal {
+    pub fn secret(&self) {}
+}
+
+/// Parses a document.
+pub fn parse(text: &str) -> Result<String, String> {
+    let trimmed = text.trim();
+    Ok(helper(trimmed))
+}
+
+fn helper(text: &str) -> String {
+    text.to_uppercase()
+}
+
+pub(crate) fn crate_only() {}
+"#;
+        let api = public_api(source).unwrap();
+
+        assert!(api.contains("/// A cache of parsed documents."));
+        assert!(api.contains("pub capacity: usize"));
+        assert!(api.contains("pub fn new(capacity: usize) -> Self;"));
+        assert!(api.contains("/// Parses a document."));
+        assert!(api.contains("pub fn parse(text: &str) -> Result<String, String>;"));
+
+        for private in ["entries", "evict_oldest", "Internal", "secret", "helper", "crate_only", "trimmed", "use std"] {
+            assert!(!api.contains(private), "{} leaked into:\n{}", private, api);
+        }
+    }
+
+    #[test]
+    fn test_public_api_rejects_unusable_input() {
+        assert!(public_api("def main():\n    pass\n").is_err());
+        assert!(public_api("fn private() {}\n").is_err());
+    }
+}
@@ -0,0 +1,133 @@
+/// A run of whole lines cut from a larger source. Lines are 1-based and inclusive.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Chunk {
+    pub start_line: usize,
+    pub end_line: usize,
+    pub text: String,
+}
+
+/// Splits `source` into chunks of at most about `max_chars` characters,
+/// cutting at the last logical boundary before the limit: the start of a
+/// top-level item (an unindented line) or of a paragraph (a line after a
+/// blank one). Without a boundary to cut at, a chunk ends at the line that
+/// would take it over the limit; a single longer line is a chunk of its own.
+pub fn chunk_source(source: &str, max_chars: usize) -> Vec<Chunk> {
+    split(source, max_chars, |_| false)
+}
+
+/// Like `chunk_source`, but with syntax trees for `language` a chunk is cut
+/// before a definition (a function, impl block, class and so on) wherever
+/// there is one to cut at, before falling back to the layout of the lines.
+#[cfg_attr(not(feature = "tree-sitter"), allow(unused_variables))]
+pub fn chunk_code(source: &str, language: &str, max_chars: usize) -> Vec<Chunk> {
+    #[cfg(feature = "tree-sitter")]
+    if let Some(starts) = super::syntax::item_starts(source, language) {
+        return split(source, max_chars, |line| starts.contains(&line));
+    }
+    chunk_source(source, max_chars)
+}
+
+/// Splits `source` as `chunk_source` describes, preferring to cut before a
+/// 0-based line for which `item_start` holds.
+fn split(source: &str, max_chars: usize, item_start: impl Fn(usize) -> bool) -> Vec<Chunk> {
+    let lines: Vec<&str> = source.lines().collect();
+    let width = |line: &str| line.chars().count() + 1;
+
+    let mut chunks = Vec::new();
+    let mut start = 0;
+    let mut size = 0;
+    for (i, line) in lines.iter().enumerate() {
+        if i > start && size + width(line) > max_chars {
+            let end = (start + 1..i)
+                .rev()
+                .find(|&j| item_start(j))
+                .or_else(|| (start + 1..i).rev().find(|&j| is_boundary(lines[j - 1], lines[j])))
+                .unwrap_or(i);
+            chunks.push(make_chunk(&lines, start, end));
+            size = lines[end..i].iter().map(|line| width(line)).sum();
+            start = end;
+        }
+        size += width(line);
+    }
+    if start < lines.len() {
+        chunks.push(make_chunk(&lines, start, lines.len()));
+    }
+    chunks
+}
+
+/// Whether a chunk may start at `line`, given the line before it.
+fn is_boundary(previous: &str, line: &str) -> bool {
+    let trimmed = line.trim();
+    if trimmed.is_empty() {
+        return false;
+    }
+    // A closing bracket finishes the item before it rather than starting one
+    if trimmed.starts_with(['}', ')', ']']) {
+        return false;
+    }
+    previous.trim().is_empty() || !line.starts_with(char::is_whitespace)
+}
+
+fn make_chunk(lines: &[&str], start: usize, end: usize) -> Chunk {
+    Chunk {
+        start_line: start + 1,
+        end_line: end,
+        text: lines[start..end].join("\n"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_small_source_is_one_chunk() {
+        let chunks = chunk_source("fn a() {}\nfn b() {}\n", 1000);
+        assert_eq!(
+            chunks,
+            vec![Chunk {
+                start_line: 1,
+                end_line: 2,
+                text: "fn a() {}\nfn b() {}".to_string(),
+            }]
+        );
+        assert!(chunk_source("", 1000).is_empty());
+    }
+
+    #[test]
+    fn test_chunks_break_between_items() {
+        let source = "fn one() {\n    let x = 1;\n}\n\nfn two() {\n    let y = 2;\n}\n\nfn three() {\n    let z = 3;\n}\n";
+        let chunks = chunk_source(source, 40);
+
+        // Every cut falls before a function, never inside one
+        let starts: Vec<&str> = chunks.iter().map(|chunk| chunk.text.lines().next().unwrap()).collect();
+        assert_eq!(starts, vec!["fn one() {", "fn two() {", "fn three() {"]);
+        assert_eq!((chunks[1].start_line, chunks[1].end_line), (5, 8));
+
+        // Nothing is lost or repeated
+        let rejoined: Vec<String> = chunks.iter().map(|chunk| chunk.text.clone()).collect();
+        assert_eq!(rejoined.join("\n"), source.trim_end());
+    }
+
+    #[test]
+    fn test_long_block_without_boundaries_is_cut_anyway() {
+        let source = format!("fn big() {{\n{}}}\n", "    let x = 1;\n".repeat(20));
+        let chunks = chunk_source(&source, 100);
+        assert!(chunks.len() > 1);
+        assert!(chunks.iter().all(|chunk| chunk.text.chars().count() <= 100));
+        assert_eq!(chunks.last().unwrap().end_line, 22);
+    }
+
+    #[cfg(feature = "tree-sitter")]
+    #[test]
+    fn test_chunk_code_cuts_before_definitions() {
+        let source = "fn one() {\n    1;\n}\nfn two() {\n    let a = 1;\n\n    let b = 2;\n    a + b\n}\n";
+
+        // The blank line inside `two` looks like the later boundary to the layout rules
+        let starts: Vec<usize> = chunk_source(source, 65).iter().map(|chunk| chunk.start_line).collect();
+        assert_eq!(starts, vec![1, 7]);
+
+        let starts: Vec<usize> = chunk_code(source, "rust", 65).iter().map(|chunk| chunk.start_line).collect();
+        assert_eq!(starts, vec![1, 4]);
+    }
+}
@@ -0,0 +1,116 @@
+use anyhow::Result;
+use chardetng::EncodingDetector;
+use encoding_rs::Encoding;
+use thiserror::Error;
+
+/// How much of a file is sampled to tell whether it is binary.
+const BINARY_SAMPLE_BYTES: usize = 8192;
+
+/// A file whose contents look like binary data rather than text.
+#[derive(Debug, Error)]
+#[error("{0} looks like a binary file; only text files can be read")]
+pub struct BinaryFile(pub String);
+
+/// Guesses from the start of `bytes` whether they are binary data: a null
+/// byte, or text that isn't UTF-8 and is over 10% control characters. Text
+/// with a UTF-16 or UTF-8 byte order mark is never binary, and neither is
+/// text in a legacy encoding such as Latin-1.
+pub fn is_probably_binary(bytes: &[u8]) -> bool {
+    if Encoding::for_bom(bytes).is_some() {
+        return false;
+    }
+    let sample = &bytes[..bytes.len().min(BINARY_SAMPLE_BYTES)];
+    if sample.contains(&0) {
+        return true;
+    }
+    match std::str::from_utf8(sample) {
+        Ok(_) => false,
+        // The sample may end partway through a character
+        Err(e) if e.error_len().is_none() => false,
+        Err(_) => {
+            let control = sample
+                .iter()
+                .filter(|&&byte| (byte < 0x20 && !b"\t\n\r\x0c".contains(&byte)) || byte == 0x7f)
+                .count();
+            control * 10 > sample.len()
+        }
+    }
+}
+
+/// Turns raw file bytes into text. UTF-8 passes straight through. Anything
+/// else is transcoded when `detect` is set: by its byte order mark if it has
+/// one, otherwise by sniffing the bytes. Returns the text and, when it had to
+/// be transcoded, the name of the encoding it was read as.
+pub fn decode_source(bytes: Vec<u8>, detect: bool) -> Result<(String, Option<&'static str>)> {
+    let bytes = match String::from_utf8(bytes) {
+        Ok(text) => return Ok((text.trim_start_matches('\u{feff}').to_string(), None)),
+        Err(e) => e.into_bytes(),
+    };
+    if !detect {
+        anyhow::bail!("File is not valid UTF-8 (set commands.detect_encoding to transcode it)");
+    }
+
+    let encoding = match Encoding::for_bom(&bytes) {
+        Some((encoding, _)) => encoding,
+        None => {
+            let mut detector = EncodingDetector::new();
+            detector.feed(&bytes, true);
+            let (encoding, confident) = detector.guess_assess(None, false);
+            if !confident {
+                anyhow::bail!(
+                    "Could not reliably detect the file's encoding (best guess: {}); convert it to UTF-8 first",
+                    encoding.name()
+                );
+            }
+            encoding
+        }
+    };
+
+    // decode() strips a BOM and replaces any malformed sequences
+    let (text, used, _) = encoding.decode(&bytes);
+    Ok((text.into_owned(), Some(used.name())))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_latin1_is_transcoded() {
+        let text = "Le café était déjà fermé, alors nous sommes allés à la crêperie près de la gare.";
+        let (latin1, _, _) = encoding_rs::WINDOWS_1252.encode(text);
+        assert!(std::str::from_utf8(&latin1).is_err());
+
+        let (decoded, encoding) = decode_source(latin1.to_vec(), true).unwrap();
+        assert_eq!(decoded, text);
+        assert_eq!(encoding, Some("windows-1252"));
+
+        assert!(decode_source(latin1.to_vec(), false).is_err());
+    }
+
+    #[test]
+    fn test_is_probably_binary() {
+        assert!(!is_probably_binary(b"fn main() {}\n"));
+        assert!(!is_probably_binary("caf\u{e9} cr\u{ea}pe".as_bytes()));
+        let (latin1, _, _) = encoding_rs::WINDOWS_1252.encode("caf\u{e9} cr\u{ea}pe");
+        assert!(!is_probably_binary(&latin1));
+
+        let mut utf16 = vec![0xFF, 0xFE];
+        utf16.extend("x = 1".encode_utf16().flat_map(|unit| unit.to_le_bytes()));
+        assert!(!is_probably_binary(&utf16));
+
+        assert!(is_probably_binary(&[0x89, b'P', b'N', b'G', 0x0d, 0x0a, 0x1a, 0x0a, 0, 0, 0, 13]));
+        assert!(is_probably_binary(&[0x7f, b'E', b'L', b'F', 0x02, 0x01, 0x01, 0xff, 0x03, 0x04]));
+    }
+
+    #[test]
+    fn test_utf8_and_utf16_bom() {
+        let (decoded, encoding) = decode_source("fn main() {}".as_bytes().to_vec(), true).unwrap();
+        assert_eq!((decoded.as_str(), encoding), ("fn main() {}", None));
+
+        let mut utf16 = vec![0xFF, 0xFE];
+        utf16.extend("x = 1".encode_utf16().flat_map(|unit| unit.to_le_bytes()));
+        let (decoded, encoding) = decode_source(utf16, true).unwrap();
+        assert_eq!((decoded.as_str(), encoding), ("x = 1", Some("UTF-16LE")));
+    }
+}
@@ -0,0 +1,183 @@
+use std::fmt;
+use std::path::Path;
+
+/// Canonical language names for common file extensions.
+const EXTENSION_LANGUAGES: &[(&str, &str)] = &[
+    ("rs", "rust"),
+    ("py", "python"),
+    ("pyi", "python"),
+    ("js", "javascript"),
+    ("mjs", "javascript"),
+    ("cjs", "javascript"),
+    ("jsx", "javascript"),
+    ("ts", "typescript"),
+    ("tsx", "typescript"),
+    ("go", "go"),
+    ("rb", "ruby"),
+    ("java", "java"),
+    ("kt", "kotlin"),
+    ("kts", "kotlin"),
+    ("swift", "swift"),
+    ("c", "c"),
+    ("h", "c"),
+    ("cc", "cpp"),
+    ("cpp", "cpp"),
+    ("cxx", "cpp"),
+    ("hpp", "cpp"),
+    ("cs", "csharp"),
+    ("php", "php"),
+    ("sh", "bash"),
+    ("bash", "bash"),
+    ("zsh", "zsh"),
+    ("ps1", "powershell"),
+    ("lua", "lua"),
+    ("pl", "perl"),
+    ("r", "r"),
+    ("scala", "scala"),
+    ("hs", "haskell"),
+    ("ex", "elixir"),
+    ("exs", "elixir"),
+    ("erl", "erlang"),
+    ("clj", "clojure"),
+    ("sql", "sql"),
+    ("html", "html"),
+    ("htm", "html"),
+    ("css", "css"),
+    ("scss", "scss"),
+    ("md", "markdown"),
+    ("json", "json"),
+    ("yaml", "yaml"),
+    ("yml", "yaml"),
+    ("toml", "toml"),
+    ("xml", "xml"),
+];
+
+/// Well-known file names that carry no extension.
+const KNOWN_FILENAMES: &[(&str, &str)] = &[
+    ("Makefile", "make"),
+    ("GNUmakefile", "make"),
+    ("makefile", "make"),
+    ("Dockerfile", "dockerfile"),
+    ("Containerfile", "dockerfile"),
+    ("Rakefile", "ruby"),
+    ("Gemfile", "ruby"),
+    ("Vagrantfile", "ruby"),
+    ("Jenkinsfile", "groovy"),
+    ("Justfile", "just"),
+    ("justfile", "just"),
+    (".bashrc", "bash"),
+    (".bash_profile", "bash"),
+    (".profile", "sh"),
+    (".zshrc", "zsh"),
+];
+
+/// How sure a language guess is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Confidence {
+    /// An extension outside the table, or nothing to go on at all
+    Low,
+    /// A shebang line
+    Medium,
+    /// A known extension or file name
+    High,
+}
+
+impl fmt::Display for Confidence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Confidence::Low => "low",
+            Confidence::Medium => "medium",
+            Confidence::High => "high",
+        })
+    }
+}
+
+/// Guesses the language of `content` read from `path`: by extension, then by
+/// well-known file name, then by a `#!` shebang line. Extensions missing from
+/// the table are returned as-is (lowercased), which still tells the model
+/// something; with nothing to go on the result is `"unknown"`.
+pub fn detect_language(path: Option<&Path>, content: &str) -> String {
+    detect_language_with_confidence(path, content).0
+}
+
+/// Like [`detect_language`], also saying how reliable the guess is.
+pub fn detect_language_with_confidence(path: Option<&Path>, content: &str) -> (String, Confidence) {
+    if let Some(extension) = path.and_then(|path| path.extension()).and_then(|ext| ext.to_str()) {
+        let extension = extension.to_ascii_lowercase();
+        return EXTENSION_LANGUAGES
+            .iter()
+            .find(|(known, _)| *known == extension)
+            .map_or((extension, Confidence::Low), |(_, language)| {
+                (language.to_string(), Confidence::High)
+            });
+    }
+
+    let file_name = path.and_then(|path| path.file_name()).and_then(|name| name.to_str());
+    if let Some((_, language)) = KNOWN_FILENAMES.iter().find(|(name, _)| Some(*name) == file_name) {
+        return (language.to_string(), Confidence::High);
+    }
+
+    match content.lines().next().and_then(shebang_language) {
+        Some(language) => (language, Confidence::Medium),
+        None => ("unknown".to_string(), Confidence::Low),
+    }
+}
+
+/// Maps the interpreter named on a `#!` line to a language.
+fn shebang_language(line: &str) -> Option<String> {
+    let mut words = line.strip_prefix("#!")?.split_whitespace();
+    let mut interpreter = words.next()?.rsplit('/').next()?;
+
+    // `#!/usr/bin/env [-S] python3` names the interpreter as an argument
+    if interpreter == "env" {
+        interpreter = words.find(|word| !word.starts_with('-'))?;
+    }
+
+    // python3.11 -> python, perl5 -> perl
+    let name = interpreter.trim_end_matches(|c: char| c.is_ascii_digit() || c == '.');
+    let language = match name {
+        "" => return None,
+        "node" | "nodejs" => "javascript",
+        "deno" | "ts-node" | "tsx" => "typescript",
+        "Rscript" => "r",
+        other => other,
+    };
+    Some(language.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect_language_sources() {
+        assert_eq!(detect_language(Some(Path::new("src/main.rs")), ""), "rust");
+        assert_eq!(detect_language(Some(Path::new("App.TSX")), ""), "typescript");
+        assert_eq!(detect_language(Some(Path::new("schema.proto")), ""), "proto");
+        assert_eq!(detect_language(Some(Path::new("build/Makefile")), "all:\n"), "make");
+        assert_eq!(detect_language(Some(Path::new("Dockerfile")), "FROM rust\n"), "dockerfile");
+        assert_eq!(detect_language(Some(Path::new("notes")), "just text\n"), "unknown");
+        assert_eq!(detect_language(None, ""), "unknown");
+    }
+
+    #[test]
+    fn test_detect_language_from_shebang() {
+        let script = |line: &str| detect_language(Some(Path::new("bin/tool")), &format!("{}\necho\n", line));
+        assert_eq!(script("#!/usr/bin/env python3"), "python");
+        assert_eq!(script("#!/usr/bin/python3.11 -u"), "python");
+        assert_eq!(script("#!/bin/bash"), "bash");
+        assert_eq!(script("#!/usr/bin/env -S node --no-warnings"), "javascript");
+        assert_eq!(script("#!/usr/bin/env"), "unknown");
+    }
+
+    #[test]
+    fn test_detection_confidence() {
+        let confidence = |path: &str, content: &str| detect_language_with_confidence(Some(Path::new(path)), content).1;
+        assert_eq!(confidence("lib.rs", ""), Confidence::High);
+        assert_eq!(confidence("Makefile", ""), Confidence::High);
+        assert_eq!(confidence("tool", "#!/bin/sh
+"), Confidence::Medium);
+        assert_eq!(confidence("schema.proto", ""), Confidence::Low);
+        assert_eq!(confidence("notes", "text\n"), Confidence::Low);
+    }
+}
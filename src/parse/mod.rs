@@ -0,0 +1,283 @@
+//! Lightweight, language-agnostic source scanning. Definitions are found by
+//! keyword (`fn`, `def`, `function`, `func`) and their extent by brace
+//! matching, or by indentation for Python `def`s and other blocks introduced
+//! with a trailing `:`. Built with the `tree-sitter` feature, languages
+//! with a grammar use real syntax trees instead, falling back to scanning
+//! for the rest.
+
+mod api;
+mod chunk;
+mod encoding;
+mod language;
+#[cfg(feature = "tree-sitter")]
+mod syntax;
+
+pub use api::public_api;
+pub use chunk::chunk_code;
+pub use encoding::{decode_source, is_probably_binary, BinaryFile};
+pub use language::{detect_language, detect_language_with_confidence, Confidence};
+
+/// Keywords that introduce a function definition in the supported languages.
+const FUNCTION_KEYWORDS: [&str; 4] = ["fn", "def", "function", "func"];
+
+/// A function found in a source file. Lines are 1-based and inclusive, and
+/// include any doc comments, attributes, or decorators directly above it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FunctionSpan {
+    pub name: String,
+    pub start_line: usize,
+    pub end_line: usize,
+    pub text: String,
+}
+
+/// Finds the first definition called `name` in `source`. With syntax trees
+/// for `language` that can be any item, such as a struct, class or impl
+/// block; otherwise it is a function found by keyword.
+#[cfg_attr(not(feature = "tree-sitter"), allow(unused_variables))]
+pub fn find_definition(source: &str, language: &str, name: &str) -> Option<FunctionSpan> {
+    #[cfg(feature = "tree-sitter")]
+    if let Some(found) = syntax::find_item(source, language, name) {
+        return found;
+    }
+    find_function(source, name)
+}
+
+/// Finds the first function called `name` in `source`.
+pub fn find_function(source: &str, name: &str) -> Option<FunctionSpan> {
+    let lines: Vec<&str> = source.lines().collect();
+    let (index, keyword) = lines.iter().enumerate().find_map(|(index, line)| {
+        let (keyword, defined) = definition(line)?;
+        (defined == name).then_some((index, keyword))
+    })?;
+
+    let start = leading_annotations_start(&lines, index);
+    let end = if keyword == "def" || lines[index].trim_end().ends_with(':') {
+        indented_block_end(&lines, index, signature_end(&lines, index))
+    } else {
+        // Only Rust (`fn`) uses `'` for lifetimes rather than strings
+        braced_block_end(&lines, index, keyword != "fn")
+    };
+
+    Some(FunctionSpan {
+        name: name.to_string(),
+        start_line: start + 1,
+        end_line: end + 1,
+        text: lines[start..=end].join("\n"),
+    })
+}
+
+/// Names of every function-like definition in `source`, in order.
+pub fn function_names(source: &str) -> Vec<String> {
+    source.lines().filter_map(|line| Some(definition(line)?.1)).collect()
+}
+
+/// Returns the keyword and name defined on `line`, if it starts a function
+/// definition.
+fn definition(line: &str) -> Option<(&'static str, String)> {
+    let mut rest = line.trim_start();
+    if rest.starts_with("//") || rest.starts_with('#') {
+        return None;
+    }
+
+    // Only look at the start of the line, past modifiers like `pub async`
+    for _ in 0..6 {
+        let (word, tail) = rest.split_once(char::is_whitespace)?;
+        rest = tail.trim_start();
+        let Some(keyword) = FUNCTION_KEYWORDS.iter().find(|keyword| **keyword == word) else {
+            continue;
+        };
+
+        // Go methods put the receiver between `func` and the name
+        if word == "func" && rest.starts_with('(') {
+            rest = rest.split_once(')')?.1.trim_start();
+        }
+
+        let name: String = rest
+            .chars()
+            .take_while(|c| c.is_alphanumeric() || *c == '_')
+            .collect();
+        return (!name.is_empty()).then_some((keyword, name));
+    }
+
+    None
+}
+
+/// Walks upward from a definition over doc comments, attributes, and decorators.
+fn leading_annotations_start(lines: &[&str], index: usize) -> usize {
+    let mut start = index;
+    while start > 0 {
+        let above = lines[start - 1].trim_start();
+        if above.starts_with("///") || above.starts_with("#[") || above.starts_with('@') {
+            start -= 1;
+        } else {
+            break;
+        }
+    }
+    start
+}
+
+/// The last line of a brace-delimited body starting at `index`. Braces in
+/// string and character literals don't count; `'` starts a string when
+/// `single_quote_strings` is set, and otherwise only a Rust character literal
+/// (as opposed to a lifetime).
+fn braced_block_end(lines: &[&str], index: usize, single_quote_strings: bool) -> usize {
+    let mut depth = 0usize;
+    let mut opened = false;
+
+    for (offset, line) in lines[index..].iter().enumerate() {
+        let mut in_string: Option<char> = None;
+        let mut chars = line.chars().peekable();
+        while let Some(c) = chars.next() {
+            match (in_string, c) {
+                (Some(_), '\\') => {
+                    chars.next();
+                }
+                (Some(quote), c) if c == quote => in_string = None,
+                (Some(_), _) => {}
+                (None, '"') | (None, '`') => in_string = Some(c),
+                (None, '\'') if single_quote_strings => in_string = Some(c),
+                (None, '\'') => skip_char_literal(&mut chars),
+                (None, '/') if chars.peek() == Some(&'/') => break,
+                (None, '{') => {
+                    depth += 1;
+                    opened = true;
+                }
+                (None, '}') => {
+                    depth = depth.saturating_sub(1);
+                    if opened && depth == 0 {
+                        return index + offset;
+                    }
+                }
+                // A declaration without a body, e.g. a trait method
+                (None, ';') if !opened => return index + offset,
+                _ => {}
+            }
+        }
+    }
+
+    lines.len() - 1
+}
+
+/// Skips past a Rust character literal such as `'{'` or `'\''`, whose
+/// opening quote has just been read. A lifetime like `'a` is left alone.
+fn skip_char_literal(chars: &mut std::iter::Peekable<std::str::Chars>) {
+    let mut ahead = chars.clone();
+    let literal_len = match ahead.next() {
+        // An escape runs up to the closing quote
+        Some('\\') => ahead.position(|c| c == '\'').map(|len| len + 2),
+        Some(_) if ahead.next() == Some('\'') => Some(2),
+        _ => None,
+    };
+    for _ in 0..literal_len.unwrap_or(0) {
+        chars.next();
+    }
+}
+
+/// The line ending the signature that starts at `index`: the first line
+/// ending in `:` once all brackets are closed, as in a Python `def` spread
+/// over several lines.
+fn signature_end(lines: &[&str], index: usize) -> usize {
+    let mut depth = 0i32;
+    for (offset, line) in lines[index..].iter().enumerate() {
+        let code = line.split('#').next().unwrap_or(line);
+        for c in code.chars() {
+            match c {
+                '(' | '[' | '{' => depth += 1,
+                ')' | ']' | '}' => depth -= 1,
+                _ => {}
+            }
+        }
+        if depth <= 0 && code.trim_end().ends_with(':') {
+            return index + offset;
+        }
+    }
+    index
+}
+
+/// The last line of an indentation-delimited body for the definition at
+/// `index`, whose signature ends on line `body_start`.
+fn indented_block_end(lines: &[&str], index: usize, body_start: usize) -> usize {
+    let indent = |line: &str| line.len() - line.trim_start().len();
+    let base = indent(lines[index]);
+
+    let mut end = body_start;
+    for (offset, line) in lines[body_start + 1..].iter().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        if indent(line) <= base {
+            break;
+        }
+        end = body_start + 1 + offset;
+    }
+    end
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RUST: &str = r#"use std::fmt;
+
+/// Adds two numbers.
+#[inline]
+pub fn add(a: i32, b: i32) -> i32 {
+    let s = "}";
+    a + b
+}
+
+impl Thing {
+    pub async fn run(&self) -> Result<()> {
+        if self.ready { println!("{}", 1); }
+        Ok(())
+    }
+}
+"#;
+
+    #[test]
+    fn test_find_rust_function_with_docs() {
+        let span = find_function(RUST, "add").unwrap();
+        assert_eq!((span.start_line, span.end_line), (3, 8));
+        assert!(span.text.starts_with("/// Adds two numbers."));
+        assert!(span.text.ends_with("a + b\n}"));
+
+        let span = find_function(RUST, "run").unwrap();
+        assert_eq!((span.start_line, span.end_line), (11, 14));
+    }
+
+    #[test]
+    fn test_find_python_function_by_indentation() {
+        let source = "import os\n\n@cache\ndef load(path):\n    with open(path) as f:\n\n        return f.read()\n\ndef other():\n    pass\n";
+        let span = find_function(source, "load").unwrap();
+        assert_eq!((span.start_line, span.end_line), (3, 7));
+        assert!(!span.text.contains("other"));
+    }
+
+    #[test]
+    fn test_quotes_do_not_unbalance_braces() {
+        let rust = "fn open<'a>(s: &'a str) -> char {\n    if s.is_empty() { '{' } else { '\\'' }\n}\n\nfn next() {}\n";
+        let span = find_function(rust, "open").unwrap();
+        assert_eq!((span.start_line, span.end_line), (1, 3));
+
+        let js = "function tag(name) {\n    return '<' + name + '{';\n}\n\nfunction other() {}\n";
+        let span = find_function(js, "tag").unwrap();
+        assert_eq!((span.start_line, span.end_line), (1, 3));
+    }
+
+    #[test]
+    fn test_find_python_function_with_multi_line_signature() {
+        let source = "def build(\n    name,\n    size=1,\n):\n    return {name: size}\n\ndef other():\n    pass\n";
+        let span = find_function(source, "build").unwrap();
+        assert_eq!((span.start_line, span.end_line), (1, 5));
+    }
+
+    #[test]
+    fn test_function_names_lists_definitions() {
+        assert_eq!(function_names(RUST), vec!["add", "run"]);
+        assert_eq!(
+            function_names("func (s *Server) Start() {}\nfunction helper() {}\n// fn commented()"),
+            vec!["Start", "helper"]
+        );
+        assert!(find_function(RUST, "missing").is_none());
+    }
+}
@@ -0,0 +1,180 @@
+//! Definitions and item boundaries from tree-sitter syntax trees, for the
+//! languages with a grammar built in. Everything here returns `None` for
+//! other languages so callers can fall back to keyword scanning.
+
+use std::collections::BTreeSet;
+use tree_sitter::{Language, Node, Parser, Tree};
+
+use super::{leading_annotations_start, FunctionSpan};
+
+/// Node kinds that are a definition worth explaining or chunking on their own.
+const ITEM_KINDS: &[&str] = &[
+    // Rust
+    "function_item",
+    "function_signature_item",
+    "impl_item",
+    "struct_item",
+    "enum_item",
+    "trait_item",
+    "mod_item",
+    "macro_definition",
+    // Python
+    "function_definition",
+    "class_definition",
+    "decorated_definition",
+    // JavaScript and TypeScript
+    "function_declaration",
+    "generator_function_declaration",
+    "class_declaration",
+    "method_definition",
+    "interface_declaration",
+    "type_alias_declaration",
+    "enum_declaration",
+    // Go
+    "method_declaration",
+    "type_declaration",
+];
+
+fn grammar(language: &str) -> Option<Language> {
+    Some(match language {
+        "rust" => tree_sitter_rust::LANGUAGE.into(),
+        "python" => tree_sitter_python::LANGUAGE.into(),
+        "javascript" => tree_sitter_javascript::LANGUAGE.into(),
+        "typescript" => tree_sitter_typescript::LANGUAGE_TSX.into(),
+        "go" => tree_sitter_go::LANGUAGE.into(),
+        _ => return None,
+    })
+}
+
+fn parse(source: &str, language: &str) -> Option<Tree> {
+    let mut parser = Parser::new();
+    parser.set_language(&grammar(language)?).ok()?;
+    parser.parse(source, None)
+}
+
+/// Finds the first function, impl block, struct, class or other item called
+/// `name`. The outer `Option` is `None` when `language` has no grammar; the
+/// inner one when there is no such item.
+pub fn find_item(source: &str, language: &str, name: &str) -> Option<Option<FunctionSpan>> {
+    let tree = parse(source, language)?;
+    let node = items(tree.root_node())
+        .into_iter()
+        .find(|node| item_name(*node, source) == Some(name));
+    Some(node.map(|node| span(source, node, name)))
+}
+
+/// The 0-based lines where items start, counting any doc comments,
+/// attributes or decorators directly above them. `None` when `language` has
+/// no grammar.
+pub fn item_starts(source: &str, language: &str) -> Option<BTreeSet<usize>> {
+    let tree = parse(source, language)?;
+    let lines: Vec<&str> = source.lines().collect();
+    Some(
+        items(tree.root_node())
+            .into_iter()
+            .map(|node| leading_annotations_start(&lines, node.start_position().row))
+            .collect(),
+    )
+}
+
+/// Every item node under `root`, in source order.
+fn items(root: Node) -> Vec<Node> {
+    let mut found = Vec::new();
+    let mut stack = vec![root];
+    while let Some(node) = stack.pop() {
+        if ITEM_KINDS.contains(&node.kind()) {
+            found.push(node);
+        }
+        let mut cursor = node.walk();
+        let children: Vec<Node> = node.named_children(&mut cursor).collect();
+        stack.extend(children.into_iter().rev());
+    }
+    found
+}
+
+/// What an item is called: its name, or for a Rust `impl` the type it is for.
+fn item_name<'a>(node: Node, source: &'a str) -> Option<&'a str> {
+    let named = match node.kind() {
+        "impl_item" => node.child_by_field_name("type"),
+        // The definition inside carries the name
+        "decorated_definition" => return None,
+        // Go puts the name on the `type_spec` inside
+        "type_declaration" => {
+            let mut cursor = node.walk();
+            let spec = node.named_children(&mut cursor).find(|child| child.kind() == "type_spec");
+            spec.and_then(|spec| spec.child_by_field_name("name"))
+        }
+        _ => node.child_by_field_name("name"),
+    }?;
+    let text = named.utf8_text(source.as_bytes()).ok()?;
+    // `impl<T> Wrapper<T>` is found by `Wrapper`
+    Some(text.split('<').next().unwrap_or(text))
+}
+
+fn span(source: &str, node: Node, name: &str) -> FunctionSpan {
+    // Decorators belong to a Python definition
+    let node = match node.parent() {
+        Some(parent) if parent.kind() == "decorated_definition" => parent,
+        _ => node,
+    };
+    let lines: Vec<&str> = source.lines().collect();
+    let start = leading_annotations_start(&lines, node.start_position().row);
+    let end = node.end_position().row.min(lines.len().saturating_sub(1));
+    FunctionSpan {
+        name: name.to_string(),
+        start_line: start + 1,
+        end_line: end + 1,
+        text: lines[start..=end].join("\n"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RUST: &str = r#"/// A pair of numbers.
+#[derive(Debug)]
+pub struct Pair {
+    a: i32,
+    b: i32,
+}
+
+impl<T> Wrapper<T> {
+    fn inner(&self) -> &T {
+        let brace = "{";
+        &self.0
+    }
+}
+"#;
+
+    #[test]
+    fn test_find_rust_struct_and_impl() {
+        let span = find_item(RUST, "rust", "Pair").unwrap().unwrap();
+        assert_eq!((span.start_line, span.end_line), (1, 6));
+
+        let span = find_item(RUST, "rust", "Wrapper").unwrap().unwrap();
+        assert_eq!((span.start_line, span.end_line), (8, 13));
+
+        let span = find_item(RUST, "rust", "inner").unwrap().unwrap();
+        assert_eq!((span.start_line, span.end_line), (9, 12));
+
+        assert!(find_item(RUST, "rust", "missing").unwrap().is_none());
+        assert!(find_item(RUST, "ruby", "Pair").is_none());
+    }
+
+    #[test]
+    fn test_find_decorated_python_method() {
+        let source = "class Store:\n    @cached\n    def load(self):\n        return {\n    }\n\n    def save(self):\n        pass\n";
+        let span = find_item(source, "python", "load").unwrap().unwrap();
+        assert_eq!((span.start_line, span.end_line), (2, 5));
+        let span = find_item(source, "python", "Store").unwrap().unwrap();
+        assert_eq!((span.start_line, span.end_line), (1, 8));
+    }
+
+    #[test]
+    fn test_item_starts() {
+        let starts = item_starts(RUST, "rust").unwrap();
+        assert_eq!(starts.into_iter().collect::<Vec<_>>(), vec![0, 7, 8]);
+        assert!(item_starts(RUST, "markdown").is_none());
+    }
+}
@@ -0,0 +1,149 @@
+use anyhow::{Context, Result};
+use std::path::Path;
+
+use crate::{
+    ai::{EmbeddingService, InputType},
+    indexer::{self, Index},
+};
+
+/// How many chunks a search returns unless told otherwise.
+pub const DEFAULT_TOP_K: usize = 5;
+
+/// A chunk of the index that matched a query. Lines are 1-based and inclusive.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Hit {
+    /// Path relative to the project root
+    pub path: String,
+    pub start_line: usize,
+    pub end_line: usize,
+    /// Cosine similarity to the query, from -1 to 1
+    pub score: f32,
+}
+
+/// Finds the `k` chunks of the index for `root` closest in meaning to
+/// `query`, best first. Errors when the project hasn't been indexed yet, or
+/// was indexed with a different embeddings model.
+pub async fn search(root: &Path, service: &EmbeddingService, query: &str, k: usize) -> Result<Vec<Hit>> {
+    // The index is keyed by the absolute path it was built from
+    let root = std::path::absolute(root).with_context(|| format!("Failed to resolve {:?}", root))?;
+    let index_path = indexer::index_path(&root).context("Could not determine the config directory")?;
+    let index = Index::load(&index_path)?;
+    if index.files.is_empty() {
+        anyhow::bail!("{} has not been indexed yet; run `monk-manager index` first", root.display());
+    }
+    if index.model != service.config().model {
+        anyhow::bail!(
+            "The index was built with {}, not {}; run `monk-manager index` again",
+            index.model,
+            service.config().model
+        );
+    }
+
+    let query = service.embed(&[query.to_string()], InputType::Query).await?;
+    let query = query.first().context("No embedding returned for the query")?;
+    Ok(nearest(&index, query, k))
+}
+
+/// The `k` chunks of `index` with the highest cosine similarity to `query`, best first.
+pub fn nearest(index: &Index, query: &[f32], k: usize) -> Vec<Hit> {
+    let mut hits: Vec<Hit> = index
+        .files
+        .iter()
+        .flat_map(|(path, file)| {
+            file.chunks.iter().map(move |chunk| Hit {
+                path: path.clone(),
+                start_line: chunk.start_line,
+                end_line: chunk.end_line,
+                score: cosine_similarity(query, &chunk.vector),
+            })
+        })
+        .collect();
+    hits.sort_by(|a, b| b.score.total_cmp(&a.score));
+    hits.truncate(k);
+    hits
+}
+
+/// Cosine similarity of two vectors; 0 when either is all zeros or their
+/// lengths differ.
+pub fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
+    if a.len() != b.len() {
+        return 0.0;
+    }
+    let dot: f32 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+    let norm = |v: &[f32]| v.iter().map(|x| x * x).sum::<f32>().sqrt();
+    let norms = norm(a) * norm(b);
+    if norms == 0.0 {
+        0.0
+    } else {
+        dot / norms
+    }
+}
+
+/// The lines of a hit, read from the file under `root` as it is now.
+pub fn snippet(root: &Path, hit: &Hit) -> Result<String> {
+    let path = root.join(&hit.path);
+    let content = std::fs::read_to_string(&path).with_context(|| format!("Failed to read file: {:?}", path))?;
+    Ok(content
+        .lines()
+        .skip(hit.start_line - 1)
+        .take(hit.end_line + 1 - hit.start_line)
+        .collect::<Vec<_>>()
+        .join("\n"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::indexer::{IndexedChunk, IndexedFile};
+
+    #[test]
+    fn test_cosine_similarity() {
+        assert!((cosine_similarity(&[1.0, 0.0], &[2.0, 0.0]) - 1.0).abs() < 1e-6);
+        assert!(cosine_similarity(&[1.0, 0.0], &[0.0, 3.0]).abs() < 1e-6);
+        assert!((cosine_similarity(&[1.0, 1.0], &[-1.0, -1.0]) + 1.0).abs() < 1e-6);
+        assert_eq!(cosine_similarity(&[0.0, 0.0], &[1.0, 0.0]), 0.0);
+        assert_eq!(cosine_similarity(&[1.0], &[1.0, 0.0]), 0.0);
+    }
+
+    #[test]
+    fn test_nearest_ranks_chunks() {
+        let chunk = |start_line, vector| IndexedChunk {
+            start_line,
+            end_line: start_line + 9,
+            vector,
+        };
+        let mut index = Index::default();
+        index.files.insert(
+            "src/config.rs".to_string(),
+            IndexedFile {
+                hash: String::new(),
+                chunks: vec![chunk(1, vec![0.9, 0.1]), chunk(11, vec![0.0, 1.0])],
+            },
+        );
+        index.files.insert(
+            "src/main.rs".to_string(),
+            IndexedFile {
+                hash: String::new(),
+                chunks: vec![chunk(1, vec![0.7, 0.7])],
+            },
+        );
+
+        let hits = nearest(&index, &[1.0, 0.0], 2);
+        let found: Vec<(&str, usize)> = hits.iter().map(|hit| (hit.path.as_str(), hit.start_line)).collect();
+        assert_eq!(found, vec![("src/config.rs", 1), ("src/main.rs", 1)]);
+        assert!(hits[0].score > hits[1].score);
+    }
+
+    #[test]
+    fn test_snippet_reads_the_hit_lines() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        std::fs::write(temp_dir.path().join("lib.rs"), "one\ntwo\nthree\nfour\n").unwrap();
+        let hit = Hit {
+            path: "lib.rs".to_string(),
+            start_line: 2,
+            end_line: 3,
+            score: 0.5,
+        };
+        assert_eq!(snippet(temp_dir.path(), &hit).unwrap(), "two\nthree");
+    }
+}
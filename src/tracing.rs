@@ -1,42 +1,137 @@
-use anyhow::Result;
-use tracing::{Level, Subscriber};
+use anyhow::{Context, Result};
+use std::fs::OpenOptions;
+use std::io::IsTerminal;
+use std::path::Path;
+use tracing::Subscriber;
+use tracing_appender::non_blocking::WorkerGuard;
 use tracing_subscriber::{
-    fmt::{format::FmtSpan, time::UtcTime},
+    fmt::{format::FmtSpan, time::UtcTime, MakeWriter},
     prelude::*,
-    EnvFilter,
+    registry::LookupSpan,
+    EnvFilter, Layer,
 };
 
-pub fn init_tracing() -> Result<()> {
-    let fmt_layer = tracing_subscriber::fmt::layer()
+use crate::config::LoggingConfig;
+
+/// Level used when neither the environment nor a config names one.
+const DEFAULT_LEVEL: &str = "info";
+
+/// Sets up logging to stderr, or to `logging.file` when `logging.output` is
+/// `"file"` (or to both with `"both"`), as JSON lines when `logging.format`
+/// is `"json"`. `RUST_LOG` wins if set; otherwise
+/// the level comes from `verbose` (`-v`, `-vv`), `quiet` (errors only),
+/// `MONK_LOG_LEVEL` or `logging.level`. Does nothing if logging is already set up, so calling it
+/// twice (as tests may) is harmless.
+///
+/// File logs are written on a background thread; keep the returned guard
+/// alive until exit so they are flushed.
+pub fn init_tracing(logging: Option<&LoggingConfig>, verbose: u8, quiet: bool) -> Result<Option<WorkerGuard>> {
+    if tracing::dispatcher::has_been_set() {
+        return Ok(None);
+    }
+
+    let (to_stderr, to_file) = log_outputs(logging.map_or("stderr", |logging| logging.output.as_str()));
+    let json = json_format(logging.map_or("pretty", |logging| logging.format.as_str()));
+    let stderr_layer = to_stderr.then(|| fmt_layer(std::io::stderr, json, std::io::stderr().is_terminal()));
+    let (file_layer, guard) = if to_file {
+        let path = logging
+            .and_then(|logging| logging.file.as_deref())
+            .context("logging.output sends logs to a file, but logging.file isn't set")?;
+        let (writer, guard) = tracing_appender::non_blocking(open_log_file(path)?);
+        (Some(fmt_layer(writer, json, false)), Some(guard))
+    } else {
+        (None, None)
+    };
+
+    let filter_layer = match EnvFilter::try_from_default_env() {
+        Ok(filter) => filter,
+        Err(_) => EnvFilter::try_new(format!("monk_manager={}", log_level(logging, verbose, quiet)))?,
+    };
+
+    tracing_subscriber::registry()
+        .with(filter_layer)
+        .with(stderr_layer)
+        .with(file_layer)
+        .try_init()?;
+
+    Ok(guard)
+}
+
+fn fmt_layer<S, W>(writer: W, json: bool, ansi: bool) -> Box<dyn Layer<S> + Send + Sync>
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+    W: for<'w> MakeWriter<'w> + Send + Sync + 'static,
+{
+    let layer = tracing_subscriber::fmt::layer()
         .with_target(false)
         .with_thread_ids(true)
         .with_file(true)
         .with_line_number(true)
         .with_thread_names(true)
-        .with_ansi(true)
         .with_level(true)
         .with_timer(UtcTime::rfc_3339())
-        .with_writer(std::io::stderr)
+        .with_writer(writer)
         .with_span_events(FmtSpan::CLOSE);
+    if json {
+        layer.json().boxed()
+    } else {
+        layer.with_ansi(ansi).boxed()
+    }
+}
 
-    let filter_layer = EnvFilter::try_from_default_env()
-        .or_else(|_| EnvFilter::try_new("monk_manager=info"))?;
-
-    tracing_subscriber::registry()
-        .with(filter_layer)
-        .with(fmt_layer)
-        .init();
+/// Whether `logging.format` asks for JSON lines rather than the pretty format.
+fn json_format(format: &str) -> bool {
+    match format {
+        "json" => true,
+        "pretty" => false,
+        other => {
+            eprintln!("Warning: unknown logging.format '{}'; using pretty", other);
+            false
+        }
+    }
+}
 
-    Ok(())
+/// Whether `logging.output` sends logs to stderr and to the log file.
+fn log_outputs(output: &str) -> (bool, bool) {
+    match output {
+        "stderr" => (true, false),
+        "file" => (false, true),
+        "both" => (true, true),
+        other => {
+            eprintln!("Warning: unknown logging.output '{}'; logging to stderr", other);
+            (true, false)
+        }
+    }
 }
 
-pub fn set_log_level(level: Level) {
-    let filter_layer = EnvFilter::try_new(format!("monk_manager={}", level.as_str())).unwrap();
-    let subscriber = tracing_subscriber::registry()
-        .with(filter_layer)
-        .with(tracing_subscriber::fmt::layer());
+/// Opens the log file for appending, creating it and its directories as needed.
+fn open_log_file(path: &Path) -> Result<std::fs::File> {
+    if let Some(parent) = path.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+        std::fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create log directory: {:?}", parent))?;
+    }
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .with_context(|| format!("Failed to open log file: {:?}", path))
+}
 
-    tracing::subscriber::set_global_default(subscriber).unwrap();
+/// The level to log at: debug for `-v` and trace for `-vv`, errors only
+/// for `--quiet`, otherwise `MONK_LOG_LEVEL`, then `logging.level`, then the
+/// default.
+fn log_level(logging: Option<&LoggingConfig>, verbose: u8, quiet: bool) -> String {
+    match (verbose, quiet) {
+        (0, true) => return "error".to_string(),
+        (0, false) => {}
+        (1, _) => return "debug".to_string(),
+        _ => return "trace".to_string(),
+    }
+    std::env::var("MONK_LOG_LEVEL")
+        .ok()
+        .or_else(|| logging.map(|logging| logging.level.clone()))
+        .filter(|level| !level.is_empty())
+        .unwrap_or_else(|| DEFAULT_LEVEL.to_string())
 }
 
 #[cfg(test)]
@@ -45,13 +140,43 @@ mod tests {
 
     #[test]
     fn test_tracing_initialization() {
-        let result = init_tracing();
+        let result = init_tracing(None, 0, false);
         assert!(result.is_ok());
+        // A second call leaves the first setup in place
+        assert!(init_tracing(None, 0, false).is_ok());
     }
 
     #[test]
-    fn test_log_level_setting() {
-        set_log_level(Level::DEBUG);
-        // No assertion needed as this is just testing that it doesn't panic
+    fn test_verbose_overrides_level() {
+        let logging = LoggingConfig {
+            level: "warn".to_string(),
+            format: "pretty".to_string(),
+            output: "stderr".to_string(),
+            file: None,
+        };
+        assert_eq!(log_level(Some(&logging), 1, false), "debug");
+        assert_eq!(log_level(Some(&logging), 2, false), "trace");
+        assert_eq!(log_level(None, 3, false), "trace");
+        assert_eq!(log_level(Some(&logging), 0, true), "error");
     }
-} 
\ No newline at end of file
+
+    #[test]
+    fn test_log_formats() {
+        assert!(json_format("json"));
+        assert!(!json_format("pretty"));
+        assert!(!json_format("logfmt"));
+    }
+
+    #[test]
+    fn test_log_file_outputs() {
+        assert_eq!(log_outputs("stderr"), (true, false));
+        assert_eq!(log_outputs("file"), (false, true));
+        assert_eq!(log_outputs("both"), (true, true));
+        assert_eq!(log_outputs("syslog"), (true, false));
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("logs").join("monk.log");
+        open_log_file(&path).unwrap();
+        assert!(path.exists());
+    }
+}